@@ -0,0 +1,109 @@
+//! Lighting evaluated on the CPU and stored in vertex colors
+//!
+//! Baked static meshes can be drawn with hardware lighting disabled while still looking shaded
+
+use psp::sys::VertexType;
+
+use crate::{
+    color::Color32,
+    light::{LightComponents, LightKind, Lighting},
+    math::{Mat4, Vec3},
+    vertex::Vertex,
+};
+
+type Rgb = [f32; 3];
+
+fn to_rgb(color: Color32) -> Rgb {
+    [color.r(), color.g(), color.b()].map(|c| c as f32 / 255.)
+}
+
+fn to_color(rgb: Rgb, alpha: u8) -> Color32 {
+    let [r, g, b] = rgb.map(|c| (c.clamp(0., 1.) * 255. + 0.5) as u32);
+    Color32::from_rgba(r << 24 | g << 16 | b << 8 | alpha as u32)
+}
+
+impl Lighting {
+    /// Compute the lit color of a surface point the same way the GE does, except for the specular term
+    ///
+    /// `position` and `normal` are in world space, `normal` has to be normalized.
+    /// The alpha is taken from the material ambient color
+    pub fn shade(&self, position: Vec3, normal: Vec3) -> Color32 {
+        let material = &self.material;
+        let material_ambient = to_rgb(material.ambient);
+        let material_diffuse = to_rgb(material.diffuse);
+        let global_ambient = to_rgb(self.ambient);
+        let mut color = to_rgb(material.emissive);
+        for i in 0..3 {
+            color[i] += global_ambient[i] * material_ambient[i];
+        }
+
+        for light in self.lights.iter().flatten() {
+            let (to_light, distance) = match light.kind {
+                LightKind::Directional { direction } => (-direction.normalize(), None),
+                LightKind::Point { position: origin }
+                | LightKind::Spot {
+                    position: origin, ..
+                } => {
+                    let offset = origin - position;
+                    (offset.normalize(), Some(offset.length()))
+                }
+            };
+
+            let mut factor = 1.;
+            if let Some(d) = distance {
+                let att = light.attenuation;
+                factor /= (att.constant + att.linear * d + att.quadratic * d * d).max(f32::EPSILON);
+            }
+            if let LightKind::Spot { direction, .. } = light.kind {
+                let cos = (-to_light).dot(direction.normalize());
+                factor *= if cos >= libm::cosf(light.spot.cutoff()) {
+                    libm::powf(cos, light.spot.exponent())
+                } else {
+                    0.
+                };
+            }
+
+            let n_dot_l = normal.dot(to_light).max(0.);
+            let diffuse = match light.components {
+                LightComponents::PoweredDiffuse => libm::powf(n_dot_l, material.specular_power),
+                _ => n_dot_l,
+            };
+            let light_ambient = to_rgb(light.ambient);
+            let light_diffuse = to_rgb(light.diffuse);
+            for i in 0..3 {
+                color[i] += factor
+                    * (light_ambient[i] * material_ambient[i]
+                        + diffuse * light_diffuse[i] * material_diffuse[i]);
+            }
+        }
+
+        to_color(color, material.ambient.a())
+    }
+}
+
+/// Bake the lighting into the vertex colors
+///
+/// - `model`: transform from the vertex positions into world space, where the lights are.
+///   Normals are transformed by it too, so it shouldn't contain non-uniform scaling
+///
+/// Existing vertex colors and [`Material::vertex_color`](crate::material::Material::vertex_color) are ignored
+///
+/// # Panics
+///
+/// Panics if the vertex layout has no normals or no color
+pub fn bake_vertex_colors<V: Vertex>(vertices: &mut [V], model: &Mat4, lighting: &Lighting) {
+    // The 32-bit variants have all bits of their format fields set
+    assert!(
+        V::vtype().intersects(VertexType::NORMAL_32BITF)
+            && V::vtype().intersects(VertexType::COLOR_8888),
+        "light baking requires a vertex layout with normals and a color"
+    );
+    for vertex in vertices {
+        let normal = vertex.normal().unwrap_or(Vec3::Y);
+        let color = lighting.shade(
+            model.transform_point(vertex.position()),
+            model.transform_vector(normal).normalize(),
+        );
+        vertex.set_color(color);
+    }
+}
@@ -0,0 +1,184 @@
+//! Pixel-exact copies of texture regions to the screen
+
+use alloc::vec::Vec;
+use psp::sys::{GuPrimitive, TextureColorComponent, TextureEffect};
+
+use crate::{
+    Frame,
+    rect::Rect,
+    texture::{Texture, TextureWrap},
+    viewport::FULL_SCREEN,
+};
+
+crate::define_vertex_layout! {
+    pub(crate) BlitVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_2D,
+        texture: TEXTURE_32BITF,
+    }
+}
+
+/// Maximum width of a single sprite drawn by [`Frame::blit`], in texels
+///
+/// Narrow strips keep the source inside of the texture cache, which is a lot faster
+/// than drawing a single wide sprite
+pub const BLIT_STRIP_WIDTH: i32 = 64;
+
+/// Append the sprite strips copying `src` to `dst`
+pub(crate) fn push_strips(vertices: &mut Vec<BlitVertex>, src: Rect, dst: Rect) {
+    if src.is_empty() || dst.is_empty() {
+        return;
+    }
+    let inset = if src.w == dst.w && src.h == dst.h {
+        0.
+    } else {
+        0.5
+    };
+    let scale_x = dst.w as f32 / src.w as f32;
+    let (v0, v1) = (src.y as f32 + inset, (src.y + src.h) as f32 - inset);
+    let (y0, y1) = (dst.y as f32, (dst.y + dst.h) as f32);
+    let strips = (src.w as u32).div_ceil(BLIT_STRIP_WIDTH as u32) as i32;
+    for strip in 0..strips {
+        let start = strip * BLIT_STRIP_WIDTH;
+        let end = (start + BLIT_STRIP_WIDTH).min(src.w);
+        let u0 = if start == 0 { inset } else { 0. };
+        let u1 = if end == src.w { -inset } else { 0. };
+        vertices.extend([
+            BlitVertex {
+                u: (src.x + start) as f32 + u0,
+                v: v0,
+                x: dst.x as f32 + start as f32 * scale_x,
+                y: y0,
+                ..BlitVertex::DEFAULT
+            },
+            BlitVertex {
+                u: (src.x + end) as f32 + u1,
+                v: v1,
+                x: dst.x as f32 + end as f32 * scale_x,
+                y: y1,
+                ..BlitVertex::DEFAULT
+            },
+        ]);
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Copy the `src` region of a texture (in texels) to the `dst` region of the screen (in pixels)
+    ///
+    /// When the sizes match, every texel lands on exactly one pixel. When `dst` is scaled,
+    /// the source edges are inset by half a texel so filtering doesn't pull in texels from
+    /// outside of `src`. The copy is split into strips of at most [`BLIT_STRIP_WIDTH`] texels
+    pub fn blit(&self, texture: &'gfx Texture, src: Rect, dst: Rect) {
+        self.blit_many(texture, [(src, dst)]);
+    }
+
+    /// Do several [`Frame::blit`]s from the same texture with a single draw call
+    ///
+    /// `regions` are pairs of the `src` and `dst` rectangles
+    pub fn blit_many(
+        &self,
+        texture: &'gfx Texture,
+        regions: impl IntoIterator<Item = (Rect, Rect)>,
+    ) {
+        let mut vertices = Vec::new();
+        for (src, dst) in regions {
+            push_strips(&mut vertices, src, dst);
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        self.bind_texture(texture);
+        self.set_texture_function(TextureEffect::Replace, TextureColorComponent::Rgba);
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+    }
+
+    /// Repeat a texture across `rect` (in pixels), with the top-left corner of the first copy in the
+    /// top-left corner of `rect`
+    ///
+    /// - `uv_scale`: size of a texel on screen, in pixels
+    ///
+    /// The copies are drawn as a single sprite with [`TextureWrap::Repeat`], whatever their number.
+    /// The whole texture is repeated, including the padding of a
+    /// [`RenderTarget::texture`](crate::render_target::RenderTarget::texture),
+    /// use [`Frame::draw_region_tiled`] to repeat only a part of it. The wrap mode is restored afterwards
+    pub fn draw_texture_tiled(&self, texture: &'gfx Texture, rect: Rect, uv_scale: f32) {
+        if rect.is_empty() || uv_scale <= 0. {
+            return;
+        }
+        let (x, y) = (rect.x as f32, rect.y as f32);
+        let vertices = [
+            BlitVertex {
+                x,
+                y,
+                ..BlitVertex::DEFAULT
+            },
+            BlitVertex {
+                u: rect.w as f32 / uv_scale,
+                v: rect.h as f32 / uv_scale,
+                x: x + rect.w as f32,
+                y: y + rect.h as f32,
+                ..BlitVertex::DEFAULT
+            },
+        ];
+        let wrap = self.texture_wrap();
+        self.bind_texture(texture);
+        self.set_texture_wrap(TextureWrap::Repeat, TextureWrap::Repeat);
+        self.set_texture_function(TextureEffect::Replace, TextureColorComponent::Rgba);
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+        self.set_texture_wrap(wrap.0, wrap.1);
+    }
+
+    /// Repeat the `src` region of a texture (in texels) across `rect` (in pixels), like [`Frame::draw_texture_tiled`]
+    ///
+    /// Every copy is drawn as its own sprite, cropped at the right and bottom edges of `rect`
+    pub fn draw_region_tiled(&self, texture: &'gfx Texture, src: Rect, rect: Rect, uv_scale: f32) {
+        if src.is_empty() || rect.is_empty() || uv_scale <= 0. {
+            return;
+        }
+        let (tile_w, tile_h) = (src.w as f32 * uv_scale, src.h as f32 * uv_scale);
+        let (right, bottom) = ((rect.x + rect.w) as f32, (rect.y + rect.h) as f32);
+        let mut vertices = Vec::new();
+        let mut y = rect.y as f32;
+        while y < bottom {
+            let h = (bottom - y).min(tile_h);
+            let mut x = rect.x as f32;
+            while x < right {
+                let w = (right - x).min(tile_w);
+                vertices.extend([
+                    BlitVertex {
+                        u: src.x as f32,
+                        v: src.y as f32,
+                        x,
+                        y,
+                        ..BlitVertex::DEFAULT
+                    },
+                    BlitVertex {
+                        u: src.x as f32 + w / uv_scale,
+                        v: src.y as f32 + h / uv_scale,
+                        x: x + w,
+                        y: y + h,
+                        ..BlitVertex::DEFAULT
+                    },
+                ]);
+                x += tile_w;
+            }
+            y += tile_h;
+        }
+        self.bind_texture(texture);
+        self.set_texture_function(TextureEffect::Replace, TextureColorComponent::Rgba);
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+    }
+
+    /// Copy a whole texture to the screen at its original size, with the top-left corner at the position
+    pub fn blit_at(&self, texture: &'gfx Texture, x: i32, y: i32) {
+        let (w, h) = (texture.width() as i32, texture.height() as i32);
+        self.blit(texture, Rect::new(0, 0, w, h), Rect::new(x, y, w, h));
+    }
+
+    /// Copy the top-left screen-sized region of a texture to the whole screen, without scaling
+    ///
+    /// Meant for 512x512 (or 512x256 with the bottom row cropped) background images
+    pub fn blit_fullscreen(&self, texture: &'gfx Texture) {
+        self.blit(texture, FULL_SCREEN, FULL_SCREEN);
+    }
+}
@@ -0,0 +1,184 @@
+//! Multi-pass blurs and bloom built from render targets
+//!
+//! Blurs are drawn as several copies of a render target offset by whole texels and added up,
+//! so they are cheapest on small render targets
+
+use psp::sys::{DisplayPixelFormat, GuPrimitive, TextureColorComponent, TextureEffect};
+
+use crate::{
+    Frame, PspGfx,
+    color::Color32,
+    post_process::PostProcessState,
+    rect::Rect,
+    render_target::{self, RenderTarget},
+    state::{BlendFactor, BlendMode, BlendOp, RenderState},
+    texture::{TextureFilter, TextureWrap},
+};
+
+crate::define_vertex_layout! {
+    FillVertex {
+        vertex: VERTEX_16BIT,
+        transform: TRANSFORM_2D,
+        color: COLOR_8888,
+    }
+}
+
+/// Sums up the taps of a blur, each already weighted by the vertex color
+const SUM: BlendMode = BlendMode::new(BlendOp::Add, BlendFactor::ONE, BlendFactor::ONE);
+
+/// Axis a [`Frame::blur`] pass averages along
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlurDirection {
+    Horizontal,
+    Vertical,
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw `src` scaled to the size of `dst` with linear filtering, e.g. into a quarter-size
+    /// render target before blurring
+    pub fn downsample(&self, src: &'gfx RenderTarget, dst: &'gfx RenderTarget) {
+        self.render_to(dst, |frame| {
+            let state = PostProcessState::COPY.with_filter(TextureFilter::Linear);
+            frame.post_process(src, &state);
+        });
+    }
+
+    /// Box blur `src` into `dst` along one axis, averaging `2 * radius + 1` texels
+    ///
+    /// Both render targets should have the same size. The edges are clamped, which only works
+    /// for render targets that fill their whole texture, i.e. with power of two sizes of at least 64 texels wide
+    pub fn blur(
+        &self,
+        src: &'gfx RenderTarget,
+        dst: &'gfx RenderTarget,
+        direction: BlurDirection,
+        radius: u32,
+    ) {
+        let taps = 2 * radius + 1;
+        let weight = 255 / taps;
+        let color = Color32::from_rgba(weight << 24 | weight << 16 | weight << 8 | 0xff);
+        let wrap = self.texture_wrap();
+        self.set_texture_wrap(TextureWrap::Clamp, TextureWrap::Clamp);
+        self.render_to(dst, |frame| {
+            for tap in 0..taps {
+                let offset = tap as i32 - radius as i32;
+                let (x, y) = match direction {
+                    BlurDirection::Horizontal => (offset, 0),
+                    BlurDirection::Vertical => (0, offset),
+                };
+                let src_rect = Rect::new(x, y, src.width() as i32, src.height() as i32);
+                let state = PostProcessState::COPY
+                    .with_blend((tap > 0).then_some(SUM))
+                    .with_effect(TextureEffect::Modulate, TextureColorComponent::Rgb)
+                    .with_color(color);
+                frame.post_process_region(src, src_rect, &state);
+            }
+        });
+        self.set_texture_wrap(wrap.0, wrap.1);
+    }
+
+    /// Box blur a render target along both axes, using `scratch` (of the same size) for the intermediate result
+    ///
+    /// See [`Frame::blur`]
+    pub fn blur_2d(&self, target: &'gfx RenderTarget, scratch: &'gfx RenderTarget, radius: u32) {
+        self.blur(target, scratch, BlurDirection::Horizontal, radius);
+        self.blur(scratch, target, BlurDirection::Vertical, radius);
+    }
+
+    /// Add a render target stretched over the screen (or the render target being drawn into)
+    /// with linear filtering, scaled by `intensity` from 0 to 255
+    pub fn composite_additive(&self, src: &'gfx RenderTarget, intensity: u8) {
+        let state = PostProcessState::ADDITIVE
+            .with_color(Color32::from_rgba(0xffffff00 | intensity as u32))
+            .with_filter(TextureFilter::Linear);
+        self.post_process(src, &state);
+    }
+
+    /// Subtract `threshold` from every channel of the screen (or the render target being drawn into),
+    /// keeping only what's brighter
+    pub fn subtract_threshold(&self, threshold: u8) {
+        let saved = self.render_state();
+        let pass = RenderState {
+            blend: Some(BlendMode::SUBTRACT),
+            ..RenderState::DEFAULT
+        };
+        RenderState::diff_apply(&saved, &pass);
+        let bounds = render_target::bounds();
+        let scissor = self.scissor();
+        self.set_scissor(bounds);
+        let value = threshold as u32;
+        let color = Color32::from_rgba(value << 24 | value << 16 | value << 8 | 0xff);
+        let vertices = [
+            FillVertex::from_position2_color(bounds.x as u16, bounds.y as u16, color),
+            FillVertex::from_position2_color(
+                (bounds.x + bounds.w) as u16,
+                (bounds.y + bounds.h) as u16,
+                color,
+            ),
+        ];
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+        RenderState::restore(&saved);
+        self.set_scissor(scissor);
+    }
+}
+
+/// Glow around the bright parts of a scene, drawn from a pair of small 16-bit render targets
+pub struct Bloom {
+    small: RenderTarget,
+    scratch: RenderTarget,
+    /// Brightness subtracted before blurring, only brighter parts glow
+    pub threshold: u8,
+    /// Strength of the glow added to the screen, from 0 to 255
+    pub intensity: u8,
+    /// Radius of the box blur, in texels of the small render targets
+    pub radius: u32,
+}
+
+impl Bloom {
+    /// Allocate the two `width` by `height` render targets the scene is downsampled and blurred in
+    ///
+    /// Returns `None` if there's not enough VRAM left. See [`Frame::blur`] for the size restrictions
+    ///
+    /// # Panics
+    ///
+    /// Panics if the render targets don't fit the screen's depth buffer they share,
+    /// see [`RenderTarget::fits_screen_depth`]
+    pub fn new(gfx: &PspGfx, width: u32, height: u32) -> Option<Self> {
+        Some(Self {
+            small: RenderTarget::with_format(
+                gfx,
+                width,
+                height,
+                false,
+                DisplayPixelFormat::Psm5650,
+            )?,
+            scratch: RenderTarget::with_format(
+                gfx,
+                width,
+                height,
+                false,
+                DisplayPixelFormat::Psm5650,
+            )?,
+            threshold: 160,
+            intensity: 255,
+            radius: 2,
+        })
+    }
+
+    /// Add the glow of `scene` to the screen (or the render target being drawn into)
+    ///
+    /// Draw the scene itself first, e.g. with [`Frame::post_process`]
+    pub fn apply<'gfx>(&'gfx self, frame: &Frame<'gfx>, scene: &'gfx RenderTarget) {
+        frame.downsample(scene, &self.small);
+        frame.render_to(&self.small, |frame| {
+            frame.subtract_threshold(self.threshold)
+        });
+        frame.blur_2d(&self.small, &self.scratch, self.radius);
+        frame.composite_additive(&self.small, self.intensity);
+    }
+
+    /// Get the blurred bright parts of the last scene passed to [`Bloom::apply`]
+    pub fn glow(&self) -> &RenderTarget {
+        &self.small
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::ffi::c_void;
 use core::marker::PhantomData;
 
@@ -38,6 +39,8 @@ impl<'frame, T: Clone + Copy> TransientBuffer<'frame, T> {
         let len = data.len();
         let len_bytes = core::mem::size_of_val(data);
         assert!(len_bytes < i32::MAX as usize);
+        // The memory is placed inside the list, after a jump over it
+        crate::reserve_list(len_bytes + 8);
         let ptr = unsafe { psp::sys::sceGuGetMemory(len_bytes as i32) };
         unsafe {
             core::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut T, len);
@@ -48,6 +51,33 @@ impl<'frame, T: Clone + Copy> TransientBuffer<'frame, T> {
             _phantom: PhantomData,
         }
     }
+
+    /// Get `len` items of memory from sceGuGetMemory, filled in place with `f(index)`
+    ///
+    /// Use `frame.get_memory_with` for a safe alternative
+    ///
+    /// Safety:
+    /// - Must not outlive current frame.
+    pub unsafe fn get_memory_with_static<'a>(
+        len: usize,
+        mut f: impl FnMut(usize) -> T,
+    ) -> TransientBuffer<'a, T> {
+        let len_bytes = len * core::mem::size_of::<T>();
+        assert!(len_bytes < i32::MAX as usize);
+        // The memory is placed inside the list, after a jump over it
+        crate::reserve_list(len_bytes + 8);
+        let ptr = unsafe { psp::sys::sceGuGetMemory(len_bytes as i32) };
+        for index in 0..len {
+            unsafe {
+                (ptr as *mut T).add(index).write(f(index));
+            }
+        }
+        TransientBuffer {
+            ptr,
+            size: len_bytes as i32,
+            _phantom: PhantomData,
+        }
+    }
 }
 
 unsafe impl<'frame, T: Clone + Copy> Buffer for TransientBuffer<'frame, T> {
@@ -61,3 +91,52 @@ unsafe impl<'frame, T: Clone + Copy> Buffer for TransientBuffer<'frame, T> {
         self.size as usize
     }
 }
+
+/// Buffer backed by a heap allocation, for data that outlives a single frame
+pub struct OwnedBuffer<T: Clone + Copy> {
+    data: Vec<T>,
+}
+
+impl<T: Clone + Copy> OwnedBuffer<T> {
+    /// Create a new buffer and flush its contents from the data cache
+    pub fn new(data: Vec<T>) -> Self {
+        let buffer = Self { data };
+        buffer.writeback();
+        buffer
+    }
+
+    /// Get the contents of the buffer
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Modify the contents of the buffer
+    ///
+    /// The data cache is flushed after `f` returns so the GE sees the changes
+    pub fn modify<R>(&mut self, f: impl FnOnce(&mut [T]) -> R) -> R {
+        let result = f(&mut self.data);
+        self.writeback();
+        result
+    }
+
+    fn writeback(&self) {
+        unsafe {
+            psp::sys::sceKernelDcacheWritebackRange(
+                self.data.as_ptr() as *const c_void,
+                core::mem::size_of_val(self.data.as_slice()) as u32,
+            );
+        }
+    }
+}
+
+unsafe impl<T: Clone + Copy> Buffer for OwnedBuffer<T> {
+    type Item = T;
+
+    fn as_ptr(&self) -> *const c_void {
+        self.data.as_ptr() as *const c_void
+    }
+
+    fn byte_size(&self) -> usize {
+        core::mem::size_of_val(self.data.as_slice())
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    Frame,
+    math::{Mat4, Vec3},
+    matrix::MatrixKind,
+};
+
+/// Simple camera defined by its position and the point it's looking at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+}
+
+impl Camera {
+    pub const fn new(eye: Vec3, target: Vec3) -> Self {
+        Self {
+            eye,
+            target,
+            up: Vec3::Y,
+        }
+    }
+
+    /// Direction the camera is facing (normalized)
+    pub fn forward(&self) -> Vec3 {
+        (self.target - self.eye).normalize()
+    }
+
+    /// Get the view matrix for this camera
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at(self.eye, self.target, self.up)
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Load the view matrix of the camera
+    pub fn set_camera(&self, camera: &Camera) {
+        self.matrix(MatrixKind::View).load(&camera.view_matrix());
+    }
+
+    /// Load a view matrix for a camera at `eye` looking at `target`
+    pub fn look_at(&self, eye: Vec3, target: Vec3, up: Vec3) {
+        self.matrix(MatrixKind::View)
+            .load(&Mat4::look_at(eye, target, up));
+    }
+}
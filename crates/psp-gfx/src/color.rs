@@ -1,4 +1,4 @@
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct Color32(u32);
 
@@ -58,4 +58,17 @@ impl Color32 {
     pub const fn a(&self) -> u8 {
         (self.0 >> 24) as u8
     }
+
+    /// Blend every component linearly towards `other`, with `t` going from 0 to 1
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0., 1.);
+        let blend = |shift: u32| {
+            let (a, b) = (
+                (self.0 >> shift) as u8 as f32,
+                (other.0 >> shift) as u8 as f32,
+            );
+            ((a + (b - a) * t + 0.5) as u32) << shift
+        };
+        Self(blend(0) | blend(8) | blend(16) | blend(24))
+    }
 }
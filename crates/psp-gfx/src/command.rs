@@ -0,0 +1,273 @@
+//! Retained command recording, decoupling scene traversal from GE submission
+//!
+//! Commands are recorded into a [`CommandBuffer`], which can be sorted before it's submitted to a [`Frame`]
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, marker::PhantomData};
+use psp::sys::{self, ClearBuffer, GuPrimitive, VertexType};
+
+use crate::{
+    Frame, buffer::Buffer, color::Color32, index::IndexItem, math::Mat4, matrix::MatrixKind,
+    state::RenderState, vertex::Vertex,
+};
+
+/// Draw call referencing vertex and index data that has to stay alive until it's executed
+#[derive(Clone, Copy, Debug)]
+pub struct DrawCall<'a> {
+    primitive: GuPrimitive,
+    vtype: i32,
+    count: usize,
+    indices: *const c_void,
+    vertices: *const c_void,
+    _data: PhantomData<&'a ()>,
+}
+
+impl<'a> DrawCall<'a> {
+    pub fn new<V: Buffer>(primitive: GuPrimitive, vertex_buf: &'a V) -> Self
+    where
+        V::Item: Vertex,
+    {
+        Self {
+            primitive,
+            vtype: V::Item::vtype().bits(),
+            count: vertex_buf.len(),
+            indices: core::ptr::null(),
+            vertices: vertex_buf.as_ptr(),
+            _data: PhantomData,
+        }
+    }
+
+    pub fn new_indexed<V: Buffer, I: Buffer>(
+        primitive: GuPrimitive,
+        vertex_buf: &'a V,
+        index_buf: &'a I,
+    ) -> Self
+    where
+        V::Item: Vertex,
+        I::Item: IndexItem,
+    {
+        Self {
+            primitive,
+            vtype: (V::Item::vtype() | I::Item::vtype()).bits(),
+            count: index_buf.len(),
+            indices: index_buf.as_ptr(),
+            vertices: vertex_buf.as_ptr(),
+            _data: PhantomData,
+        }
+    }
+
+    pub fn primitive(&self) -> GuPrimitive {
+        self.primitive
+    }
+
+    /// Get the number of vertices, or indices for indexed draws
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        !self.indices.is_null()
+    }
+}
+
+/// Single recorded command
+#[derive(Clone, Copy, Debug)]
+pub enum Command<'a> {
+    /// Clear the buffers that have a value
+    Clear {
+        color: Option<Color32>,
+        depth: Option<u32>,
+        stencil: Option<u8>,
+    },
+    /// Switch to a render state
+    State(RenderState),
+    /// Replace the current matrix of a matrix stack
+    Matrix(MatrixKind, Mat4),
+    /// Draw call, sorted by `key` with [`CommandBuffer::sort_draws`]
+    Draw { key: u32, call: DrawCall<'a> },
+}
+
+/// List of recorded commands that can be reordered and submitted to a [`Frame`]
+///
+/// Commands are only stored, nothing is sent to the GE until [`CommandBuffer::submit`].
+/// The buffer keeps its allocation when cleared, so it can be reused every frame
+#[derive(Clone, Debug, Default)]
+pub struct CommandBuffer<'a> {
+    commands: Vec<Command<'a>>,
+    key: u32,
+}
+
+impl<'a> CommandBuffer<'a> {
+    pub const fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            key: 0,
+        }
+    }
+
+    /// Get the recorded commands
+    pub fn commands(&self) -> &[Command<'a>] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Remove all commands and reset the sort key
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.key = 0;
+    }
+
+    /// Record a raw command
+    pub fn push(&mut self, command: Command<'a>) {
+        self.commands.push(command);
+    }
+
+    /// Record a clear of the color buffer
+    pub fn clear_color(&mut self, color: Color32) {
+        self.push(Command::Clear {
+            color: Some(color),
+            depth: None,
+            stencil: None,
+        });
+    }
+
+    /// Record a clear of both color and depth buffers
+    pub fn clear_color_depth(&mut self, color: Color32, depth: u32) {
+        self.push(Command::Clear {
+            color: Some(color),
+            depth: Some(depth),
+            stencil: None,
+        });
+    }
+
+    /// Record a render state change
+    ///
+    /// Nothing is recorded if the state is the same as the last recorded one
+    pub fn set_state(&mut self, state: RenderState) {
+        let last = self
+            .commands
+            .iter()
+            .rev()
+            .find_map(|command| match command {
+                Command::State(state) => Some(state),
+                _ => None,
+            });
+        if last != Some(&state) {
+            self.push(Command::State(state));
+        }
+    }
+
+    /// Record a matrix change
+    pub fn set_matrix(&mut self, kind: MatrixKind, matrix: Mat4) {
+        self.push(Command::Matrix(kind, matrix));
+    }
+
+    /// Set the sort key of the following draws
+    pub fn set_sort_key(&mut self, key: u32) {
+        self.key = key;
+    }
+
+    /// Record a draw call
+    pub fn draw(&mut self, call: DrawCall<'a>) {
+        self.push(Command::Draw {
+            key: self.key,
+            call,
+        });
+    }
+
+    pub fn draw_array<V: Buffer>(&mut self, primitive: GuPrimitive, vertex_buf: &'a V)
+    where
+        V::Item: Vertex,
+    {
+        self.draw(DrawCall::new(primitive, vertex_buf));
+    }
+
+    pub fn draw_array_indexed<V: Buffer, I: Buffer>(
+        &mut self,
+        primitive: GuPrimitive,
+        vertex_buf: &'a V,
+        index_buf: &'a I,
+    ) where
+        V::Item: Vertex,
+        I::Item: IndexItem,
+    {
+        self.draw(DrawCall::new_indexed(primitive, vertex_buf, index_buf));
+    }
+
+    /// Sort consecutive draws by their sort key
+    ///
+    /// Draws are never moved across other commands, and draws with the same key keep their order
+    pub fn sort_draws(&mut self) {
+        for run in self
+            .commands
+            .split_mut(|command| !matches!(command, Command::Draw { .. }))
+        {
+            run.sort_by_key(|command| match command {
+                Command::Draw { key, .. } => *key,
+                _ => unreachable!(),
+            });
+        }
+    }
+
+    /// Submit the commands to the frame
+    ///
+    /// State changes are uploaded as a diff against the current state,
+    /// and matrices equal to the previously submitted matrix of the same kind are skipped
+    pub fn submit(&self, frame: &Frame) {
+        let mut matrices: [Option<Mat4>; 4] = [None; 4];
+        for command in &self.commands {
+            match command {
+                Command::Clear {
+                    color,
+                    depth,
+                    stencil,
+                } => {
+                    let mut flags = ClearBuffer::empty();
+                    unsafe {
+                        if let Some(color) = color {
+                            sys::sceGuClearColor(color.as_abgr());
+                            flags |= ClearBuffer::COLOR_BUFFER_BIT;
+                        }
+                        if let Some(depth) = depth {
+                            sys::sceGuClearDepth(*depth);
+                            flags |= ClearBuffer::DEPTH_BUFFER_BIT;
+                        }
+                        if let Some(stencil) = stencil {
+                            sys::sceGuClearStencil(*stencil as u32);
+                            flags |= ClearBuffer::STENCIL_BUFFER_BIT;
+                        }
+                        if !flags.is_empty() {
+                            sys::sceGuClear(flags);
+                        }
+                    }
+                }
+                Command::State(state) => {
+                    RenderState::diff_apply(&frame.render_state(), state);
+                }
+                Command::Matrix(kind, matrix) => {
+                    let last = &mut matrices[*kind as usize];
+                    if *last != Some(*matrix) {
+                        frame.matrix(*kind).load(matrix);
+                        *last = Some(*matrix);
+                    }
+                }
+                Command::Draw { call, .. } => unsafe {
+                    crate::draw_raw(
+                        call.primitive,
+                        VertexType::from_bits_retain(call.vtype),
+                        call.count,
+                        call.indices,
+                        call.vertices,
+                    );
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Curves evaluated on the CPU and drawn as lines or ribbons, e.g. for trails and paths
+//!
+//! Unlike the patches of [`patch`](crate::patch), the geometry is generated every frame straight
+//! into display list memory, so the curve can change freely between frames
+
+use psp::sys::GuPrimitive;
+
+use crate::{Frame, color::Color32, math::Vec3};
+
+crate::define_vertex_layout! {
+    CurveVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_3D,
+        color: COLOR_8888,
+    }
+}
+
+crate::define_vertex_layout! {
+    RibbonVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_3D,
+        texture: TEXTURE_32BITF,
+        color: COLOR_8888,
+    }
+}
+
+/// How the control points of a [`Curve`] are interpreted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveKind {
+    /// Cubic bezier segments sharing their end points, the curve only passes through every third point
+    Bezier,
+    /// Catmull-Rom spline passing through every point
+    CatmullRom,
+}
+
+/// Piecewise cubic curve through a list of control points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Curve<'a> {
+    kind: CurveKind,
+    points: &'a [Vec3],
+}
+
+impl<'a> Curve<'a> {
+    /// Build a bezier curve from `3n + 1` control points
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of points isn't `3n + 1`
+    pub fn bezier(points: &'a [Vec3]) -> Self {
+        assert!(
+            points.len() >= 4 && (points.len() - 1).is_multiple_of(3),
+            "bezier curves need 3n + 1 control points, got {}",
+            points.len()
+        );
+        Self {
+            kind: CurveKind::Bezier,
+            points,
+        }
+    }
+
+    /// Build a Catmull-Rom spline through at least 2 points
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 2 points
+    pub fn catmull_rom(points: &'a [Vec3]) -> Self {
+        assert!(
+            points.len() >= 2,
+            "catmull-rom splines need at least 2 points, got {}",
+            points.len()
+        );
+        Self {
+            kind: CurveKind::CatmullRom,
+            points,
+        }
+    }
+
+    pub fn kind(&self) -> CurveKind {
+        self.kind
+    }
+
+    pub fn points(&self) -> &'a [Vec3] {
+        self.points
+    }
+
+    /// Get the number of cubic segments the curve is made of
+    pub fn segment_count(&self) -> usize {
+        match self.kind {
+            CurveKind::Bezier => (self.points.len() - 1) / 3,
+            CurveKind::CatmullRom => self.points.len() - 1,
+        }
+    }
+
+    /// Get the 4 control points of a segment, with the end points repeated for Catmull-Rom splines
+    fn segment(&self, index: usize) -> [Vec3; 4] {
+        match self.kind {
+            CurveKind::Bezier => {
+                let start = index * 3;
+                [0, 1, 2, 3].map(|i| self.points[start + i])
+            }
+            CurveKind::CatmullRom => {
+                let last = self.points.len() - 1;
+                [
+                    index.saturating_sub(1),
+                    index,
+                    index + 1,
+                    (index + 2).min(last),
+                ]
+                .map(|i| self.points[i])
+            }
+        }
+    }
+
+    /// Map `t` over the whole curve to a segment and the position within it
+    fn locate(&self, t: f32) -> ([Vec3; 4], f32) {
+        let count = self.segment_count();
+        let scaled = t.clamp(0., 1.) * count as f32;
+        let index = (scaled as usize).min(count - 1);
+        (self.segment(index), scaled - index as f32)
+    }
+
+    /// Get the position on the curve, with `t` going from 0 at the start to 1 at the end
+    pub fn point(&self, t: f32) -> Vec3 {
+        let ([p0, p1, p2, p3], t) = self.locate(t);
+        let (t2, t3) = (t * t, t * t * t);
+        match self.kind {
+            CurveKind::Bezier => {
+                let s = 1. - t;
+                p0 * (s * s * s) + p1 * (3. * s * s * t) + p2 * (3. * s * t2) + p3 * t3
+            }
+            CurveKind::CatmullRom => {
+                (p1 * 2.
+                    + (p2 - p0) * t
+                    + (p0 * 2. - p1 * 5. + p2 * 4. - p3) * t2
+                    + (p1 * 3. - p0 - p2 * 3. + p3) * t3)
+                    * 0.5
+            }
+        }
+    }
+
+    /// Get the direction of the curve at `t`, not normalized
+    pub fn tangent(&self, t: f32) -> Vec3 {
+        let ([p0, p1, p2, p3], t) = self.locate(t);
+        match self.kind {
+            CurveKind::Bezier => {
+                let s = 1. - t;
+                (p1 - p0) * (3. * s * s) + (p2 - p1) * (6. * s * t) + (p3 - p2) * (3. * t * t)
+            }
+            CurveKind::CatmullRom => {
+                ((p2 - p0)
+                    + (p0 * 2. - p1 * 5. + p2 * 4. - p3) * (2. * t)
+                    + (p1 * 3. - p0 - p2 * 3. + p3) * (3. * t * t))
+                    * 0.5
+            }
+        }
+    }
+
+    /// Number of points sampled along the curve with `subdivisions` lines per segment
+    fn sample_count(&self, subdivisions: u32) -> usize {
+        self.segment_count() * subdivisions.max(1) as usize + 1
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw a curve as a line strip made of `subdivisions` lines per segment
+    pub fn draw_curve(&self, curve: &Curve, subdivisions: u32, color: Color32) {
+        let count = curve.sample_count(subdivisions);
+        let vertices = self.get_memory_with(count, |i| {
+            let point = curve.point(i as f32 / (count - 1) as f32);
+            CurveVertex::from_position_color(point.x, point.y, point.z, color)
+        });
+        self.draw_array(GuPrimitive::LineStrip, &vertices);
+    }
+
+    /// Draw a curve as a `width` wide triangle strip made of `subdivisions` quads per segment
+    ///
+    /// The ribbon is widened perpendicular to both the curve and `facing`, e.g. the direction
+    /// towards the camera for a trail that always faces it. The color fades from `colors[0]`
+    /// at the start to `colors[1]` at the end, the texture runs from U 0 to 1 along the curve,
+    /// and from V 0 to 1 across it
+    pub fn draw_ribbon(
+        &self,
+        curve: &Curve,
+        subdivisions: u32,
+        width: f32,
+        facing: Vec3,
+        colors: [Color32; 2],
+    ) {
+        let count = curve.sample_count(subdivisions);
+        // Both vertices of a pair share the sample, keep the last side in case the tangent lines up with `facing`
+        let mut sample = (usize::MAX, Vec3::ZERO, Vec3::ZERO);
+        let vertices = self.get_memory_with(count * 2, |i| {
+            let t = (i / 2) as f32 / (count - 1) as f32;
+            if sample.0 != i / 2 {
+                let side = curve.tangent(t).cross(facing).normalize();
+                let side = if side.length() > 0.5 { side } else { sample.2 };
+                sample = (i / 2, curve.point(t), side);
+            }
+            let (point, side) = (sample.1, sample.2);
+            let v = (i % 2) as f32;
+            let position = point + side * ((v - 0.5) * width);
+            RibbonVertex {
+                u: t,
+                v,
+                color: colors[0].lerp(colors[1], t),
+                x: position.x,
+                y: position.y,
+                z: position.z,
+                ..RibbonVertex::DEFAULT
+            }
+        });
+        self.draw_array(GuPrimitive::TriangleStrip, &vertices);
+    }
+}
@@ -0,0 +1,89 @@
+//! Text overlay with a built-in 8x8 font, drawn through the GE
+
+use alloc::vec::Vec;
+
+use crate::{
+    Frame,
+    color::Color32,
+    rect::Rect,
+    sprite::{Sprite, SpriteBatch},
+    state::{AlphaTest, DepthState, RenderState},
+    texture::{Texture, TextureFormat},
+};
+
+/// Width and height of a debug font character, in pixels
+pub const DEBUG_FONT_SIZE: i32 = 8;
+
+/// MSX font from the PSPSDK, 256 characters of 8 rows with the leftmost pixel in the highest bit
+const FONT: &[u8; 2048] = include_bytes!("debug_font.bin");
+
+/// The characters are laid out in a 16x16 grid
+const ATLAS_SIZE: u32 = 16 * DEBUG_FONT_SIZE as u32;
+
+static mut ATLAS: Option<Texture> = None;
+
+fn atlas() -> &'static Texture {
+    unsafe {
+        ATLAS.get_or_insert_with(|| {
+            let mut pixels = Vec::with_capacity((ATLAS_SIZE * ATLAS_SIZE * 2) as usize);
+            for y in 0..ATLAS_SIZE as usize {
+                for x in 0..ATLAS_SIZE as usize {
+                    let c = y / 8 * 16 + x / 8;
+                    let set = FONT[c * 8 + y % 8] & (0x80 >> (x % 8)) != 0;
+                    let pixel: u16 = if set { 0xffff } else { 0 };
+                    pixels.extend_from_slice(&pixel.to_le_bytes());
+                }
+            }
+            Texture::new(ATLAS_SIZE, ATLAS_SIZE, TextureFormat::Psm4444, &pixels)
+        })
+    }
+}
+
+/// Queue text drawn with the debug font into a sprite batch, see [`Frame::debug_text_color`]
+pub(crate) fn queue_text(batch: &mut SpriteBatch, x: i32, y: i32, text: &str, color: Color32) {
+    let atlas = atlas();
+    let (mut pen_x, mut pen_y) = (x, y);
+    for c in text.chars() {
+        if c == '\n' {
+            (pen_x, pen_y) = (x, pen_y + DEBUG_FONT_SIZE);
+            continue;
+        }
+        let index = u8::try_from(c).unwrap_or(b'?') as i32;
+        if c != ' ' {
+            let uv = Rect::new(
+                index % 16 * DEBUG_FONT_SIZE,
+                index / 16 * DEBUG_FONT_SIZE,
+                DEBUG_FONT_SIZE,
+                DEBUG_FONT_SIZE,
+            );
+            batch.draw(
+                atlas,
+                &Sprite::new(pen_x as f32, pen_y as f32, uv).with_color(color),
+            );
+        }
+        pen_x += DEBUG_FONT_SIZE;
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw white text with the built-in debug font, see [`Frame::debug_text_color`]
+    pub fn debug_text(&self, x: i32, y: i32, text: &str) {
+        self.debug_text_color(x, y, text, Color32::WHITE);
+    }
+
+    /// Draw text with the built-in debug font, with the top-left corner at the position (in pixels)
+    ///
+    /// Lines are separated by `\n`, characters outside of the Latin-1 range are drawn as `?`.
+    /// The text is drawn over everything else, the render state is restored afterwards
+    pub fn debug_text_color(&self, x: i32, y: i32, text: &str, color: Color32) {
+        let saved = self.render_state();
+        let overlay = RenderState {
+            alpha_test: Some(AlphaTest::CUTOUT),
+            depth: DepthState::DISABLED,
+            ..saved
+        };
+        RenderState::diff_apply(&saved, &overlay);
+        queue_text(&mut self.sprite_batch(), x, y, text, color);
+        RenderState::restore(&saved);
+    }
+}
@@ -0,0 +1,165 @@
+//! Decoder turning GE command streams into readable text, for debugging lists that hang the GE
+//!
+//! ```ignore
+//! decode::print_list(gfx.last_frame_list());
+//! ```
+
+use core::{ffi::c_void, fmt};
+use psp::sys::{self, GeCommand};
+
+/// Single command of a GE command stream
+#[derive(Clone, Copy, Debug)]
+pub struct DecodedCommand {
+    /// Offset from the start of the stream in bytes
+    pub offset: usize,
+    pub command: GeCommand,
+    /// 24-bit argument
+    pub argument: u32,
+    /// Upper address bits set by the last `BASE` command, for commands taking addresses
+    pub base: u32,
+}
+
+impl DecodedCommand {
+    /// Get the argument as a 24-bit float
+    pub fn float_argument(&self) -> f32 {
+        f32::from_bits(self.argument << 8)
+    }
+
+    /// Get the full address for commands taking an address
+    pub fn address(&self) -> u32 {
+        self.base | self.argument
+    }
+
+    fn is_float(&self) -> bool {
+        use GeCommand::*;
+        let op = self.command as u8;
+        matches!(
+            self.command,
+            MorphWeight0
+                | MorphWeight1
+                | MorphWeight2
+                | MorphWeight3
+                | MorphWeight4
+                | MorphWeight5
+                | MorphWeight6
+                | MorphWeight7
+                | BoneMatrixData
+                | WorldMatrixData
+                | ViewMatrixData
+                | ProjMatrixData
+                | TGenMatrixData
+                | ViewportXScale
+                | ViewportYScale
+                | ViewportZScale
+                | ViewportXCenter
+                | ViewportYCenter
+                | ViewportZCenter
+                | TexScaleU
+                | TexScaleV
+                | TexOffsetU
+                | TexOffsetV
+                | MaterialSpecularCoef
+                | Fog1
+                | Fog2
+                | TexLodSlope
+        ) || (GeCommand::Light0X as u8..=GeCommand::Light3CutoffAtten as u8).contains(&op)
+    }
+}
+
+impl fmt::Display for DecodedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use GeCommand::*;
+        write!(f, "{:06x}: {:?}", self.offset, self.command)?;
+        match self.command {
+            Nop | Ret | End | Finish if self.argument == 0 => Ok(()),
+            Prim | Bezier | Spline => write!(
+                f,
+                " type={} count={}",
+                self.argument >> 16 & 7,
+                self.argument & 0xffff
+            ),
+            Vaddr | Iaddr | Jump | BJump | Call | Origin => {
+                write!(f, " {:#010x}", self.address())
+            }
+            _ if self.is_float() => write!(f, " {}", self.float_argument()),
+            _ => write!(f, " {:#08x}", self.argument),
+        }
+    }
+}
+
+/// Iterator over the commands of a GE command stream, see [`decode`]
+#[derive(Clone)]
+pub struct Decoder<'a> {
+    list: &'a [u32],
+    position: usize,
+    base: u32,
+    finished: bool,
+    done: bool,
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = DecodedCommand;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let word = *self.list.get(self.position)?;
+        // Every 8-bit value is a variant of GeCommand
+        let command = unsafe { core::mem::transmute::<u8, GeCommand>((word >> 24) as u8) };
+        let decoded = DecodedCommand {
+            offset: self.position * 4,
+            command,
+            argument: word & 0xff_ffff,
+            base: self.base,
+        };
+        self.position += 1;
+        match command {
+            GeCommand::Base => self.base = (decoded.argument << 8) & 0x0f00_0000,
+            GeCommand::Ret => self.done = true,
+            GeCommand::End => self.done = self.finished,
+            _ => {}
+        }
+        self.finished = matches!(command, GeCommand::Finish);
+        Some(decoded)
+    }
+}
+
+/// Decode a GE command stream
+///
+/// Decoding stops at the end of the list (`FINISH` followed by `END`), at a `RET`, or at the end of the slice.
+/// Jumps and calls aren't followed
+pub fn decode(list: &[u32]) -> Decoder<'_> {
+    Decoder {
+        list,
+        position: 0,
+        base: 0,
+        finished: false,
+        done: false,
+    }
+}
+
+/// Write the decoded commands, one per line
+pub fn write_list(out: &mut impl fmt::Write, list: &[u32]) -> fmt::Result {
+    for command in decode(list) {
+        writeln!(out, "{command}")?;
+    }
+    Ok(())
+}
+
+/// Writer printing to stdout, which is shown on the PSPLINK console
+pub struct Stdout;
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        unsafe {
+            sys::sceIoWrite(sys::sceKernelStdout(), s.as_ptr() as *const c_void, s.len());
+        }
+        Ok(())
+    }
+}
+
+/// Print the decoded commands to stdout
+pub fn print_list(list: &[u32]) {
+    let _ = write_list(&mut Stdout, list);
+}
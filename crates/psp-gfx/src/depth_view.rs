@@ -0,0 +1,110 @@
+//! Readback of the depth buffer, for debugging depth range and precision problems
+
+use alloc::{vec, vec::Vec};
+use psp::{BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::{
+    PspGfx,
+    color::Color32,
+    rect::Rect,
+    texture::{Texture, TextureFormat},
+    viewport::DepthRange,
+};
+
+/// Uncached mirror of VRAM reading the depth buffer in linear order, the GE stores it swizzled
+const LINEAR_DEPTH_MIRROR: usize = 0x4460_0000;
+
+/// Size of the texture holding the grayscale image, the smallest power of two fitting the screen
+const TEXTURE_SIZE: u32 = 512;
+
+impl PspGfx {
+    /// Copy the depth buffer of the last finished frame into `depth`, row by row without padding
+    ///
+    /// Waits for the GE to finish the frame first
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` holds fewer than `SCREEN_WIDTH * SCREEN_HEIGHT` values
+    pub fn read_depth(&mut self, depth: &mut [u16]) {
+        let (width, height) = (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
+        assert!(
+            depth.len() >= width * height,
+            "depth readback needs room for {width}x{height} values, got {}",
+            depth.len()
+        );
+        self.sync();
+        let base = (LINEAR_DEPTH_MIRROR + self.zbp as usize) as *const u16;
+        for (y, row) in depth.chunks_exact_mut(width).take(height).enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = unsafe { base.add(y * BUF_WIDTH as usize + x).read_volatile() };
+            }
+        }
+    }
+}
+
+/// Grayscale image of the depth buffer, with nearer pixels being brighter
+///
+/// Draw it over the screen with [`Frame::blit_fullscreen`](crate::Frame::blit_fullscreen)
+pub struct DepthView {
+    texture: Texture,
+    depth: Vec<u16>,
+}
+
+impl DepthView {
+    pub fn new() -> Self {
+        let mut texture = Texture::new(
+            TEXTURE_SIZE,
+            TEXTURE_SIZE,
+            TextureFormat::T8,
+            &vec![0; (TEXTURE_SIZE * TEXTURE_SIZE) as usize],
+        );
+        let palette: Vec<Color32> = (0..=255u32)
+            .map(|v| Color32::from_rgba(v << 24 | v << 16 | v << 8 | 0xff))
+            .collect();
+        texture.set_palette(&palette);
+        Self {
+            texture,
+            depth: vec![0; (SCREEN_WIDTH * SCREEN_HEIGHT) as usize],
+        }
+    }
+
+    /// Read back the depth buffer and rebuild the image
+    ///
+    /// `range` is stretched over the full brightness range, `None` stretches the range
+    /// of values actually present, ignoring the cleared far value
+    pub fn update(&mut self, gfx: &mut PspGfx, range: Option<DepthRange>) {
+        gfx.read_depth(&mut self.depth);
+        let range = range.unwrap_or_else(|| {
+            let written = self.depth.iter().copied().filter(|&depth| depth != 0);
+            let (min, max) = written.fold((u16::MAX, 0), |(min, max), depth| {
+                (min.min(depth), max.max(depth))
+            });
+            DepthRange::new(max, min.min(max))
+        });
+        let (near, far) = (range.near as i32, range.far as i32);
+        let span = (near - far).max(1);
+        let pixels: Vec<u8> = self
+            .depth
+            .iter()
+            .map(|&depth| ((depth as i32 - far).clamp(0, span) * 255 / span) as u8)
+            .collect();
+        let screen = Rect::new(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+        self.texture.write(screen, &pixels);
+    }
+
+    /// Get the texture holding the image in its top-left corner
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Get the depth values read by the last [`DepthView::update`], row by row
+    pub fn depth(&self) -> &[u16] {
+        &self.depth
+    }
+}
+
+impl Default for DepthView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
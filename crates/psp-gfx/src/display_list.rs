@@ -0,0 +1,299 @@
+//! Display lists recorded once and replayed with a single call command
+//!
+//! Static geometry like level chunks can be recorded into a [`DisplayList`] up front,
+//! so the GE commands aren't generated again every frame
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{ffi::c_void, marker::PhantomData};
+use psp::{
+    Align16,
+    sys::{self, GeCommand, GuContextType, GuPrimitive},
+};
+
+use crate::{
+    Frame,
+    buffer::Buffer,
+    color::Color32,
+    command::CommandBuffer,
+    index::IndexItem,
+    material::Material,
+    math::Mat4,
+    matrix::{self, MatrixKind, MatrixStack},
+    mesh::Mesh,
+    state::{self, RenderState},
+    vertex::Vertex,
+};
+
+static mut RECORDING: bool = false;
+
+/// Size of the base address and call commands calling a list
+const CALL_COMMANDS_SIZE: usize = 8;
+
+/// GE commands recorded into their own buffer, see [`Frame::record_list`]
+///
+/// The list borrows the vertex and index data it references for `'a`,
+/// so that data can't be freed or modified while the list can still be called.
+/// Dropping the list waits until the GE is idle, as a finished frame may still be executing it
+pub struct DisplayList<'a> {
+    buffer: Vec<Align16<[u32; 4]>>,
+    size: usize,
+    state: RenderState,
+    _data: PhantomData<&'a ()>,
+}
+
+impl<'a> DisplayList<'a> {
+    /// Get the size of the recorded commands in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Get the size of the buffer in bytes
+    pub fn capacity(&self) -> usize {
+        core::mem::size_of_val(self.buffer.as_slice())
+    }
+
+    /// Get the recorded commands, e.g. for [`decode`](crate::decode::decode)
+    pub fn commands(&self) -> &[u32] {
+        // Align16 only adds alignment, the buffer is a contiguous array of words
+        unsafe { core::slice::from_raw_parts(self.buffer.as_ptr() as *const u32, self.size / 4) }
+    }
+
+    /// Get the render state that's active after the list was executed
+    pub fn render_state(&self) -> RenderState {
+        self.state
+    }
+}
+
+impl Drop for DisplayList<'_> {
+    fn drop(&mut self) {
+        // Waits for every queued list, unlike `PspGfx::sync` this doesn't need to know if one was sent
+        unsafe {
+            sys::sceGuSync(sys::GuSyncMode::Finish, sys::GuSyncBehavior::Wait);
+        }
+    }
+}
+
+/// Recording context passed to the callback of [`Frame::record_list`]
+///
+/// Only data that lives for `'a` can be drawn, data copied with [`ListRecorder::draw_inline`]
+/// is stored in the list itself
+pub struct ListRecorder<'a, 'frame> {
+    frame: &'frame Frame<'frame>,
+    _data: PhantomData<&'a ()>,
+}
+
+impl<'a, 'frame> ListRecorder<'a, 'frame> {
+    pub fn render_state(&self) -> RenderState {
+        self.frame.render_state()
+    }
+
+    /// Switch to a render state, only recording the parts that changed
+    pub fn set_render_state(&self, state: &RenderState) {
+        RenderState::diff_apply(&self.frame.render_state(), state);
+    }
+
+    /// Get a handle to the matrix stack of the specified kind
+    pub fn matrix(&self, kind: MatrixKind) -> MatrixStack<'frame> {
+        self.frame.matrix(kind)
+    }
+
+    pub fn set_color(&self, color: Color32) {
+        self.frame.set_color(color);
+    }
+
+    pub fn set_material(&self, material: &Material) {
+        self.frame.set_material(material);
+    }
+
+    pub fn draw_array<V: Buffer>(&self, primitive: GuPrimitive, vertex_buf: &'a V)
+    where
+        V::Item: Vertex,
+    {
+        self.frame.draw_array(primitive, vertex_buf);
+    }
+
+    pub fn draw_array_indexed<V: Buffer, I: Buffer>(
+        &self,
+        primitive: GuPrimitive,
+        vertex_buf: &'a V,
+        index_buf: &'a I,
+    ) where
+        V::Item: Vertex,
+        I::Item: IndexItem + Default,
+    {
+        self.frame
+            .draw_array_indexed(primitive, vertex_buf, index_buf);
+    }
+
+    pub fn draw_mesh<V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy + Default>(
+        &self,
+        mesh: &'a Mesh<V, I>,
+    ) {
+        self.frame.draw_mesh(mesh);
+    }
+
+    /// Copy the vertices into the list and draw them
+    pub fn draw_inline<V: Vertex + Clone + Copy>(&self, primitive: GuPrimitive, vertices: &[V]) {
+        self.frame
+            .draw_array(primitive, &self.frame.get_memory(vertices));
+    }
+
+    /// Record the commands of a [`CommandBuffer`]
+    pub fn submit(&self, commands: &CommandBuffer<'a>) {
+        commands.submit(self.frame);
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Record the commands generated by `record` into a new display list instead of submitting them
+    ///
+    /// - `capacity`: size of the list buffer in bytes
+    ///
+    /// The list starts by uploading the current render state, so it draws the same no matter what
+    /// state it's called from. Only matrices changed while recording are stored in the list,
+    /// the others are taken from the frame when the list is called, so e.g. the camera can still move.
+    /// Neither the GE nor the state tracked by the frame are affected by recording,
+    /// but matrices changed through the matrix stacks keep their new values
+    ///
+    /// # Panics
+    ///
+    /// Panics if called while already recording, or as soon as a draw or [`Frame::get_memory`]
+    /// wouldn't fit into what's left of `capacity`
+    pub fn record_list<'a>(
+        &self,
+        capacity: usize,
+        record: impl FnOnce(&ListRecorder<'a, '_>),
+    ) -> DisplayList<'a> {
+        unsafe {
+            self.record_list_unchecked(capacity, |frame| {
+                record(&ListRecorder {
+                    frame,
+                    _data: PhantomData,
+                })
+            })
+        }
+    }
+
+    /// Record a display list with full access to the frame, see [`Frame::record_list`]
+    ///
+    /// Memory from [`Frame::get_memory`] is allocated inside the list and stays valid with it
+    ///
+    /// # Safety
+    ///
+    /// All other memory referenced by the recorded commands has to outlive `'a`
+    pub unsafe fn record_list_unchecked<'a>(
+        &self,
+        capacity: usize,
+        record: impl FnOnce(&Self),
+    ) -> DisplayList<'a> {
+        assert!(
+            unsafe { !core::mem::replace(&mut RECORDING, true) },
+            "display lists can't be recorded while recording another one"
+        );
+        let mut buffer = vec![Align16([0; 4]); capacity.div_ceil(16)];
+        let saved = self.render_state();
+        unsafe {
+            // The list is written through the uncached mirror, drop the zeroed lines from the cache
+            // so they can't be written back over it later
+            sys::sceKernelDcacheWritebackInvalidateRange(
+                buffer.as_ptr() as *const c_void,
+                core::mem::size_of_val(buffer.as_slice()) as u32,
+            );
+            // Pending matrix changes belong to the frame, not the list
+            sys::sceGumUpdateMatrix();
+            sys::sceGuStart(GuContextType::Call, buffer.as_mut_ptr() as *mut c_void);
+        }
+        let frame_capacity =
+            crate::replace_list_capacity(core::mem::size_of_val(buffer.as_slice()));
+        saved.apply();
+        record(self);
+        unsafe {
+            // Flush matrices changed at the end of `record` into the list
+            sys::sceGumUpdateMatrix();
+        }
+        let size = unsafe { sys::sceGuFinish() } as usize;
+        crate::replace_list_capacity(frame_capacity);
+        let state = state::replace_current(saved);
+        // The matrices were only uploaded inside of the list
+        matrix::invalidate_matrices();
+        unsafe {
+            RECORDING = false;
+        }
+        DisplayList {
+            buffer,
+            size,
+            state,
+            _data: PhantomData,
+        }
+    }
+
+    /// Execute a recorded display list
+    ///
+    /// The list has to outlive the frame, as the GE reads it after this returns.
+    /// The state tracked by the frame is updated to [`DisplayList::render_state`],
+    /// and all matrices are uploaded again before the next draw
+    pub fn call_list(&self, list: &'gfx DisplayList<'_>) {
+        crate::reserve_list(CALL_COMMANDS_SIZE);
+        unsafe {
+            sys::sceGuCallList(list.buffer.as_ptr() as *const c_void);
+        }
+        state::replace_current(list.state);
+        matrix::invalidate_matrices();
+    }
+
+    /// Execute a recorded display list once for every model matrix, for cheap instancing of props
+    ///
+    /// The list shouldn't change the model matrix itself, or it overrides the instance transforms.
+    /// The model matrix of the frame is restored afterwards
+    pub fn call_list_instanced<'m>(
+        &self,
+        list: &'gfx DisplayList<'_>,
+        transforms: impl IntoIterator<Item = &'m Mat4>,
+    ) {
+        let model = self.matrix(MatrixKind::Model);
+        let _guard = model.push();
+        for transform in transforms {
+            // The matrix upload is as large as the one of a draw
+            crate::reserve_list(crate::DRAW_COMMANDS_SIZE + CALL_COMMANDS_SIZE);
+            model.load(transform);
+            unsafe {
+                sys::sceGumUpdateMatrix();
+                sys::sceGuCallList(list.buffer.as_ptr() as *const c_void);
+            }
+        }
+        state::replace_current(list.state);
+        matrix::invalidate_matrices();
+    }
+
+    /// Execute a GE command stream built outside of this crate, e.g. baked offline
+    ///
+    /// The stream is called like a [`DisplayList`], and all matrices are uploaded again before the next draw.
+    /// State changed by the stream isn't tracked, so apply a full [`RenderState`] afterwards
+    /// if the stream changes any of it
+    ///
+    /// # Safety
+    ///
+    /// - The stream has to end with a `RET` command
+    /// - Every address referenced by the stream, including `BASE`-relative ones,
+    ///   has to point to valid data that outlives the frame
+    /// - The stream must not contain `FINISH`, `END` or `SIGNAL` commands that
+    ///   would end the frame's list early
+    pub unsafe fn submit_raw_list<const N: usize>(&self, list: &'gfx Align16<[u32; N]>) {
+        debug_assert!(
+            list.0
+                .iter()
+                .any(|command| command >> 24 == GeCommand::Ret as u32),
+            "raw display lists have to return with a RET command"
+        );
+        crate::reserve_list(CALL_COMMANDS_SIZE);
+        unsafe {
+            sys::sceKernelDcacheWritebackRange(
+                list.0.as_ptr() as *const c_void,
+                core::mem::size_of_val(&list.0) as u32,
+            );
+            sys::sceGuCallList(list.0.as_ptr() as *const c_void);
+        }
+        matrix::invalidate_matrices();
+    }
+}
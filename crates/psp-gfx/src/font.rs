@@ -0,0 +1,187 @@
+//! Bitmap fonts in the BMFont format
+
+use alloc::collections::BTreeMap;
+
+use crate::{
+    Frame,
+    color::Color32,
+    rect::Rect,
+    sprite::{Sprite, SpriteBatch},
+    texture::Texture,
+};
+
+/// Metrics of a single glyph, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Glyph {
+    /// Region of the glyph in the font texture
+    pub rect: Rect,
+    /// Offset from the pen position to the top-left corner of the glyph
+    pub x_offset: i32,
+    pub y_offset: i32,
+    /// Distance the pen moves after the glyph
+    pub x_advance: i32,
+}
+
+/// Font made of glyphs packed into a single texture
+pub struct BitmapFont {
+    texture: Texture,
+    glyphs: BTreeMap<char, Glyph>,
+    kerning: BTreeMap<(char, char), i32>,
+    line_height: i32,
+}
+
+impl BitmapFont {
+    /// Character drawn in place of characters missing from the font
+    pub const FALLBACK: char = '?';
+
+    pub fn new(texture: Texture, line_height: i32) -> Self {
+        Self {
+            texture,
+            glyphs: BTreeMap::new(),
+            kerning: BTreeMap::new(),
+            line_height,
+        }
+    }
+
+    /// Load a font from a BMFont text descriptor (`.fnt`) and its page texture
+    ///
+    /// Only single page fonts are supported. Returns `None` if the descriptor is malformed
+    pub fn parse(texture: Texture, descriptor: &str) -> Option<Self> {
+        let mut font = Self::new(texture, 0);
+        for line in descriptor.lines() {
+            let mut words = line.split_whitespace();
+            let Some(tag) = words.next() else {
+                continue;
+            };
+            let mut values = BTreeMap::new();
+            for word in words {
+                if let Some((key, value)) = word.split_once('=') {
+                    values.insert(key, value);
+                }
+            }
+            let get = |key: &str| values.get(key)?.parse::<i32>().ok();
+            match tag {
+                "common" => font.line_height = get("lineHeight")?,
+                "char" => {
+                    let id = char::from_u32(get("id")? as u32)?;
+                    let glyph = Glyph {
+                        rect: Rect::new(get("x")?, get("y")?, get("width")?, get("height")?),
+                        x_offset: get("xoffset")?,
+                        y_offset: get("yoffset")?,
+                        x_advance: get("xadvance")?,
+                    };
+                    font.set_glyph(id, glyph);
+                }
+                "kerning" => {
+                    let first = char::from_u32(get("first")? as u32)?;
+                    let second = char::from_u32(get("second")? as u32)?;
+                    font.set_kerning(first, second, get("amount")?);
+                }
+                _ => {}
+            }
+        }
+        Some(font)
+    }
+
+    pub fn set_glyph(&mut self, c: char, glyph: Glyph) {
+        self.glyphs.insert(c, glyph);
+    }
+
+    /// Set the extra distance between two consecutive characters
+    pub fn set_kerning(&mut self, first: char, second: char, amount: i32) {
+        self.kerning.insert((first, second), amount);
+    }
+
+    /// Get the glyph of a character, or the [`FALLBACK`](Self::FALLBACK) glyph if it's missing
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.glyphs.get(&Self::FALLBACK))
+    }
+
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0)
+    }
+
+    /// Get the distance between the baselines of two lines
+    pub fn line_height(&self) -> i32 {
+        self.line_height
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub(crate) fn texture_mut(&mut self) -> &mut Texture {
+        &mut self.texture
+    }
+
+    pub(crate) fn clear_glyphs(&mut self) {
+        self.glyphs.clear();
+    }
+
+    /// Lay out `text`, calling `place` with the pen position and glyph of every character
+    fn layout(&self, text: &str, mut place: impl FnMut(i32, i32, &Glyph)) {
+        let (mut x, mut y) = (0, 0);
+        let mut previous = None;
+        for c in text.chars() {
+            if c == '\n' {
+                (x, y) = (0, y + self.line_height);
+                previous = None;
+                continue;
+            }
+            let Some(glyph) = self.glyph(c) else {
+                continue;
+            };
+            if let Some(previous) = previous {
+                x += self.kerning(previous, c);
+            }
+            place(x, y, glyph);
+            x += glyph.x_advance;
+            previous = Some(c);
+        }
+    }
+
+    /// Get the width and height of the text, in pixels
+    ///
+    /// Lines are separated by `\n`
+    pub fn measure(&self, text: &str) -> (i32, i32) {
+        if text.is_empty() {
+            return (0, 0);
+        }
+        let mut width = 0;
+        self.layout(text, |x, _, glyph| width = width.max(x + glyph.x_advance));
+        let lines = text.split('\n').count() as i32;
+        (width, lines * self.line_height)
+    }
+
+    /// Queue the text into a sprite batch, with the top-left corner of the first line at the position
+    pub fn draw<'gfx>(
+        &'gfx self,
+        batch: &mut SpriteBatch<'_, 'gfx>,
+        x: f32,
+        y: f32,
+        text: &str,
+        color: Color32,
+    ) {
+        self.layout(text, |pen_x, pen_y, glyph| {
+            if glyph.rect.is_empty() {
+                return;
+            }
+            let sprite = Sprite::new(
+                x + (pen_x + glyph.x_offset) as f32,
+                y + (pen_y + glyph.y_offset) as f32,
+                glyph.rect,
+            )
+            .with_color(color);
+            batch.draw(&self.texture, &sprite);
+        });
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw text with a bitmap font, see [`BitmapFont::draw`]
+    pub fn draw_text(&self, font: &'gfx BitmapFont, x: f32, y: f32, text: &str, color: Color32) {
+        font.draw(&mut self.sprite_batch(), x, y, text, color);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    Frame,
+    math::{Aabb, Mat4, Vec3},
+};
+
+/// Plane in the `normal · p + d = 0` form
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub d: f32,
+}
+
+impl Plane {
+    /// Create the plane through `point` facing `normal`
+    pub fn from_point_normal(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self {
+            normal,
+            d: -normal.dot(point),
+        }
+    }
+
+    fn from_row([a, b, c, d]: [f32; 4]) -> Self {
+        let normal = Vec3::new(a, b, c);
+        let inv_len = 1. / normal.length();
+        Self {
+            normal: normal * inv_len,
+            d: d * inv_len,
+        }
+    }
+
+    /// Signed distance from the plane, positive on the side the normal points to
+    pub fn distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.d
+    }
+}
+
+/// View frustum used for CPU-side visibility culling
+///
+/// All plane normals point inside the frustum
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extract the frustum planes from a `projection * view` matrix
+    ///
+    /// If a `projection * view * model` matrix is used instead, tests should be done in model space
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| view_projection.row(i));
+        let add = |a: [f32; 4], b: [f32; 4]| core::array::from_fn(|i| a[i] + b[i]);
+        let sub = |a: [f32; 4], b: [f32; 4]| core::array::from_fn(|i| a[i] - b[i]);
+        Self {
+            planes: [
+                Plane::from_row(add(r3, r0)),
+                Plane::from_row(sub(r3, r0)),
+                Plane::from_row(add(r3, r1)),
+                Plane::from_row(sub(r3, r1)),
+                Plane::from_row(add(r3, r2)),
+                Plane::from_row(sub(r3, r2)),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.distance(point) >= 0.)
+    }
+
+    /// Check if the sphere is at least partially inside the frustum
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance(center) >= -radius)
+    }
+
+    /// Check if the box is at least partially inside the frustum
+    ///
+    /// This is conservative, large boxes near the frustum corners may be reported as visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Test the corner furthest along the plane normal
+            let n = plane.normal;
+            let corner = Vec3::new(
+                if n.x >= 0. { aabb.max.x } else { aabb.min.x },
+                if n.y >= 0. { aabb.max.y } else { aabb.min.y },
+                if n.z >= 0. { aabb.max.z } else { aabb.min.z },
+            );
+            plane.distance(corner) >= 0.
+        })
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Extract the world-space view frustum from the current projection and view matrices
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection())
+    }
+}
@@ -1,9 +1,56 @@
+use alloc::vec::Vec;
+use core::f32::consts::TAU;
 use psp::sys::GuPrimitive;
 
-use crate::{Frame, define_vertex_layout, rect::Rect};
+use crate::{Frame, color::Color32, define_vertex_layout, rect::Rect};
+
+define_vertex_layout! {
+    ShapeVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_2D,
+    }
+}
+
+define_vertex_layout! {
+    GradientVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_2D,
+        color: COLOR_8888,
+    }
+}
+
+/// Get the number of segments used to approximate an arc
+fn arc_segments(radius: f32, angle: f32) -> usize {
+    let full = (radius * 0.5).clamp(8., 64.);
+    libm::ceilf(full * angle.abs() / TAU).max(1.) as usize
+}
+
+fn draw_points(frame: &Frame, primitive: GuPrimitive, points: &[(f32, f32)]) {
+    let vertices: Vec<ShapeVertex> = points
+        .iter()
+        .map(|&(x, y)| ShapeVertex::from_position2(x, y))
+        .collect();
+    frame.draw_array(primitive, &frame.get_memory(&vertices));
+}
 
 pub trait GfxExt {
     fn gfx_rect(&self, rect: Rect);
+    fn gfx_rect_outline(&self, rect: Rect, thickness: f32);
+    fn gfx_line(&self, from: (f32, f32), to: (f32, f32), thickness: f32);
+    fn gfx_line_strip(&self, points: &[(f32, f32)], thickness: f32);
+    fn gfx_circle(&self, center: (f32, f32), radius: f32);
+    fn gfx_circle_outline(&self, center: (f32, f32), radius: f32, thickness: f32);
+    fn gfx_arc(&self, center: (f32, f32), radius: f32, start: f32, end: f32, thickness: f32);
+    fn gfx_polygon(&self, points: &[(f32, f32)]);
+    fn gfx_rounded_rect(&self, rect: Rect, radii: [f32; 4]);
+    fn gfx_rect_gradient(&self, rect: Rect, colors: [Color32; 4]);
+    fn gfx_line_gradient(
+        &self,
+        from: (f32, f32),
+        to: (f32, f32),
+        thickness: f32,
+        colors: [Color32; 2],
+    );
 }
 
 impl<'gfx> GfxExt for Frame<'gfx> {
@@ -23,4 +70,179 @@ impl<'gfx> GfxExt for Frame<'gfx> {
         ]);
         self.draw_array(GuPrimitive::Sprites, &vertex_buf);
     }
+
+    /// Draw the outline of a rectangle, with the border inside of the rectangle
+    fn gfx_rect_outline(&self, rect: Rect, thickness: f32) {
+        let (x0, y0) = (rect.x as f32, rect.y as f32);
+        let (x1, y1) = (x0 + rect.w as f32, y0 + rect.h as f32);
+        let t = thickness.min(rect.w as f32 / 2.).min(rect.h as f32 / 2.);
+        // Outer and inner corners, walked around as a closed strip
+        draw_points(
+            self,
+            GuPrimitive::TriangleStrip,
+            &[
+                (x0, y0),
+                (x0 + t, y0 + t),
+                (x1, y0),
+                (x1 - t, y0 + t),
+                (x1, y1),
+                (x1 - t, y1 - t),
+                (x0, y1),
+                (x0 + t, y1 - t),
+                (x0, y0),
+                (x0 + t, y0 + t),
+            ],
+        );
+    }
+
+    /// Draw a line segment
+    fn gfx_line(&self, from: (f32, f32), to: (f32, f32), thickness: f32) {
+        self.gfx_line_strip(&[from, to], thickness);
+    }
+
+    /// Draw connected line segments through the points
+    fn gfx_line_strip(&self, points: &[(f32, f32)], thickness: f32) {
+        let mut quads = Vec::with_capacity(points.len().saturating_sub(1) * 6);
+        for segment in points.windows(2) {
+            let [(x0, y0), (x1, y1)] = [segment[0], segment[1]];
+            let (dx, dy) = (x1 - x0, y1 - y0);
+            let length = libm::sqrtf(dx * dx + dy * dy);
+            if length <= f32::EPSILON {
+                continue;
+            }
+            let (nx, ny) = (-dy / length * thickness / 2., dx / length * thickness / 2.);
+            let corners = [
+                (x0 + nx, y0 + ny),
+                (x1 + nx, y1 + ny),
+                (x1 - nx, y1 - ny),
+                (x0 - nx, y0 - ny),
+            ];
+            quads.extend([0, 1, 2, 0, 2, 3].map(|i| corners[i]));
+        }
+        if !quads.is_empty() {
+            draw_points(self, GuPrimitive::Triangles, &quads);
+        }
+    }
+
+    /// Draw a filled circle
+    fn gfx_circle(&self, center: (f32, f32), radius: f32) {
+        let segments = arc_segments(radius, TAU);
+        let mut points = Vec::with_capacity(segments + 2);
+        points.push(center);
+        for i in 0..=segments {
+            let angle = TAU * i as f32 / segments as f32;
+            points.push((
+                center.0 + libm::cosf(angle) * radius,
+                center.1 + libm::sinf(angle) * radius,
+            ));
+        }
+        draw_points(self, GuPrimitive::TriangleFan, &points);
+    }
+
+    /// Draw the outline of a circle, with the border inside of the circle
+    fn gfx_circle_outline(&self, center: (f32, f32), radius: f32, thickness: f32) {
+        self.gfx_arc(center, radius, 0., TAU, thickness);
+    }
+
+    /// Draw an arc from angle `start` to `end`, in radians clockwise from the positive x axis
+    ///
+    /// The border is drawn inside of the radius
+    fn gfx_arc(&self, center: (f32, f32), radius: f32, start: f32, end: f32, thickness: f32) {
+        let inner = (radius - thickness).max(0.);
+        let segments = arc_segments(radius, end - start);
+        let mut points = Vec::with_capacity((segments + 1) * 2);
+        for i in 0..=segments {
+            let angle = start + (end - start) * i as f32 / segments as f32;
+            let (sin, cos) = (libm::sinf(angle), libm::cosf(angle));
+            points.push((center.0 + cos * radius, center.1 + sin * radius));
+            points.push((center.0 + cos * inner, center.1 + sin * inner));
+        }
+        draw_points(self, GuPrimitive::TriangleStrip, &points);
+    }
+
+    /// Fill a convex polygon, with the points in order around its outline
+    fn gfx_polygon(&self, points: &[(f32, f32)]) {
+        if points.len() >= 3 {
+            draw_points(self, GuPrimitive::TriangleFan, points);
+        }
+    }
+
+    /// Fill a rectangle with rounded corners
+    ///
+    /// `radii` are the top-left, top-right, bottom-right and bottom-left corner radii,
+    /// shrunk to at most half of the rectangle size
+    fn gfx_rounded_rect(&self, rect: Rect, radii: [f32; 4]) {
+        let (x0, y0) = (rect.x as f32, rect.y as f32);
+        let (x1, y1) = (x0 + rect.w as f32, y0 + rect.h as f32);
+        let max_radius = (rect.w.min(rect.h) as f32 / 2.).max(0.);
+        // Corner centers with the angle each corner arc starts at, walking clockwise
+        let corners = [
+            (x0, y0, TAU / 2.),
+            (x1, y0, TAU * 3. / 4.),
+            (x1, y1, 0.),
+            (x0, y1, TAU / 4.),
+        ];
+        let mut points = Vec::new();
+        for ((x, y, start), radius) in corners.into_iter().zip(radii) {
+            let radius = radius.clamp(0., max_radius);
+            let (cx, cy) = (
+                if x == x0 { x + radius } else { x - radius },
+                if y == y0 { y + radius } else { y - radius },
+            );
+            if radius == 0. {
+                points.push((x, y));
+                continue;
+            }
+            let segments = arc_segments(radius, TAU / 4.);
+            for i in 0..=segments {
+                let angle = start + TAU / 4. * i as f32 / segments as f32;
+                points.push((
+                    cx + libm::cosf(angle) * radius,
+                    cy + libm::sinf(angle) * radius,
+                ));
+            }
+        }
+        self.gfx_polygon(&points);
+    }
+
+    /// Fill a rectangle with colors interpolated between its corners
+    ///
+    /// `colors` are the top-left, top-right, bottom-right and bottom-left corner colors.
+    /// The colors are only interpolated with [`ShadingModel::Smooth`](psp::sys::ShadingModel::Smooth),
+    /// see [`Frame::set_shading_model`]
+    fn gfx_rect_gradient(&self, rect: Rect, colors: [Color32; 4]) {
+        let (x0, y0) = (rect.x as f32, rect.y as f32);
+        let (x1, y1) = (x0 + rect.w as f32, y0 + rect.h as f32);
+        let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+        let vertices = [0, 1, 3, 2].map(|i| {
+            let (x, y) = corners[i];
+            GradientVertex::from_position2_color(x, y, colors[i])
+        });
+        self.draw_array(GuPrimitive::TriangleStrip, &self.get_memory(&vertices));
+    }
+
+    /// Draw a line segment with the color fading from `colors[0]` to `colors[1]`
+    ///
+    /// See [`GfxExt::gfx_rect_gradient`] for the shading model requirement
+    fn gfx_line_gradient(
+        &self,
+        from: (f32, f32),
+        to: (f32, f32),
+        thickness: f32,
+        colors: [Color32; 2],
+    ) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = libm::sqrtf(dx * dx + dy * dy);
+        if length <= f32::EPSILON {
+            return;
+        }
+        let (nx, ny) = (-dy / length * thickness / 2., dx / length * thickness / 2.);
+        let vertices = [
+            GradientVertex::from_position2_color(from.0 + nx, from.1 + ny, colors[0]),
+            GradientVertex::from_position2_color(from.0 - nx, from.1 - ny, colors[0]),
+            GradientVertex::from_position2_color(to.0 + nx, to.1 + ny, colors[1]),
+            GradientVertex::from_position2_color(to.0 - nx, to.1 - ny, colors[1]),
+        ];
+        self.draw_array(GuPrimitive::TriangleStrip, &self.get_memory(&vertices));
+    }
 }
@@ -0,0 +1,155 @@
+//! Glyphs rasterized on demand into an 8-bit atlas, for text that isn't known up front
+
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+use crate::{
+    color::Color32,
+    font::{BitmapFont, Glyph},
+    rect::Rect,
+    texture::{Texture, TextureFormat},
+};
+
+/// Coverage bitmap and metrics of a single rasterized glyph
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RasterGlyph {
+    /// Size of the bitmap, in pixels
+    pub width: i32,
+    pub height: i32,
+    /// Offset from the pen position to the top-left corner of the bitmap, with the pen at the top of the line
+    pub x_offset: i32,
+    pub y_offset: i32,
+    /// Distance the pen moves after the glyph
+    pub x_advance: i32,
+    /// Coverage of every pixel, row by row, from 0 (empty) to 255 (fully covered)
+    pub coverage: Vec<u8>,
+}
+
+/// Source of the glyphs of a [`GlyphCache`]
+pub trait GlyphRasterizer {
+    /// Get the distance between the tops of two lines
+    fn line_height(&self) -> i32;
+
+    /// Rasterize a single character, `None` if the source doesn't contain it
+    fn rasterize_glyph(&self, c: char) -> Option<RasterGlyph>;
+}
+
+/// [`BitmapFont`] whose glyphs are rasterized the first time they're used
+///
+/// The glyphs are stored in a [`TextureFormat::T8`] atlas, with the coverage as the palette index.
+/// When the atlas is full, it's cleared and only the glyphs of the text being prepared are added again
+pub struct GlyphCache<R: GlyphRasterizer> {
+    source: R,
+    font: BitmapFont,
+    size: i32,
+    /// Characters already looked up, including the ones missing from the source
+    cached: BTreeSet<char>,
+    shelf_x: i32,
+    shelf_y: i32,
+    shelf_height: i32,
+}
+
+impl<R: GlyphRasterizer> GlyphCache<R> {
+    /// Create an empty cache with a `size` by `size` atlas
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` isn't a power of two up to [`MAX_TEXTURE_SIZE`](crate::texture::MAX_TEXTURE_SIZE)
+    pub fn new(source: R, size: u32) -> Self {
+        let mut texture = Texture::new(
+            size,
+            size,
+            TextureFormat::T8,
+            &vec![0; (size * size) as usize],
+        );
+        let palette: Vec<Color32> = (0..=255u32)
+            .map(|a| Color32::from_rgba(0xffffff00 | a))
+            .collect();
+        texture.set_palette(&palette);
+        let line_height = source.line_height();
+        let mut cache = Self {
+            source,
+            font: BitmapFont::new(texture, line_height),
+            size: size as i32,
+            cached: BTreeSet::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+        };
+        cache.insert(BitmapFont::FALLBACK);
+        cache
+    }
+
+    pub fn source(&self) -> &R {
+        &self.source
+    }
+
+    /// Get the font made of the cached glyphs
+    ///
+    /// Only glyphs added with [`GlyphCache::prepare`] are available, the others are drawn
+    /// as [`BitmapFont::FALLBACK`]
+    pub fn font(&self) -> &BitmapFont {
+        &self.font
+    }
+
+    /// Remove all glyphs from the atlas
+    pub fn clear(&mut self) {
+        self.font.clear_glyphs();
+        self.cached.clear();
+        (self.shelf_x, self.shelf_y, self.shelf_height) = (0, 0, 0);
+        self.insert(BitmapFont::FALLBACK);
+    }
+
+    /// Rasterize the characters of `text` that aren't cached yet
+    ///
+    /// Prepare every string drawn in a frame before drawing any of them, as the atlas is cleared
+    /// when it runs out of space. With [pipelining](crate::PspGfx::set_pipelined), the GE may still be
+    /// reading the atlas for the previous frame, see [`PspGfx::sync`](crate::PspGfx::sync)
+    pub fn prepare(&mut self, text: &str) {
+        for c in text.chars() {
+            if !self.insert(c) {
+                self.clear();
+                for c in text.chars() {
+                    self.insert(c);
+                }
+                return;
+            }
+        }
+    }
+
+    /// Add a character to the atlas, returns `false` if it didn't fit
+    fn insert(&mut self, c: char) -> bool {
+        if c == '\n' || self.cached.contains(&c) {
+            return true;
+        }
+        let Some(raster) = self.source.rasterize_glyph(c) else {
+            self.cached.insert(c);
+            return true;
+        };
+        // Leave a pixel of padding so filtering doesn't pick up neighbouring glyphs
+        if self.shelf_x + raster.width + 1 > self.size {
+            self.shelf_x = 0;
+            self.shelf_y += self.shelf_height + 1;
+            self.shelf_height = 0;
+        }
+        if raster.width > self.size || self.shelf_y + raster.height > self.size {
+            return false;
+        }
+        let rect = Rect::new(self.shelf_x, self.shelf_y, raster.width, raster.height);
+        if !rect.is_empty() {
+            self.font.texture_mut().write(rect, &raster.coverage);
+        }
+        self.font.set_glyph(
+            c,
+            Glyph {
+                rect,
+                x_offset: raster.x_offset,
+                y_offset: raster.y_offset,
+                x_advance: raster.x_advance,
+            },
+        );
+        self.cached.insert(c);
+        self.shelf_x += raster.width + 1;
+        self.shelf_height = self.shelf_height.max(raster.height);
+        true
+    }
+}
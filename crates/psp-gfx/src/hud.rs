@@ -0,0 +1,134 @@
+//! On-screen performance HUD drawn with the debug font, for profiling on hardware without a serial console
+
+use alloc::{format, vec::Vec};
+use psp::sys::GuPrimitive;
+
+use crate::{
+    Frame,
+    color::Color32,
+    debug_text::DEBUG_FONT_SIZE,
+    profiler::{FRAME_BUDGET, push_quad},
+    state::RenderState,
+};
+
+/// Number of frames shown in the frame time graph, one pixel column each
+const HISTORY: usize = 96;
+
+/// Height of a full frame budget in the frame time graph, in pixels
+const GRAPH_BUDGET_HEIGHT: u32 = 12;
+
+/// The graph shows frame times up to this many frame budgets
+const GRAPH_MAX_FRAMES: u32 = 3;
+
+/// Overlay showing the frame rate, a frame time graph, draw calls, vertices and VRAM usage
+///
+/// Call [`PerfHud::draw`] once per frame, even while hidden, to keep the graph recording
+pub struct PerfHud {
+    visible: bool,
+    frame_times: [u32; HISTORY],
+    next: usize,
+}
+
+impl PerfHud {
+    pub const fn new() -> Self {
+        Self {
+            visible: true,
+            frame_times: [0; HISTORY],
+            next: 0,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Show the HUD if it's hidden, hide it otherwise, e.g. when a button is pressed
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Record the last frame time and draw the HUD with its top-left corner at the position
+    ///
+    /// The statistics come from [`PspGfx::frame_stats`](crate::PspGfx::frame_stats) and
+    /// [`PspGfx::vram_usage`](crate::PspGfx::vram_usage). The HUD is drawn over everything else,
+    /// the render state is restored afterwards
+    pub fn draw(&mut self, frame: &Frame, x: i32, y: i32) {
+        let stats = frame.gfx.frame_stats();
+        self.frame_times[self.next] = stats.frame_time;
+        self.next = (self.next + 1) % HISTORY;
+        if !self.visible {
+            return;
+        }
+
+        let vram = frame.gfx.vram_usage();
+        let ms = |time: u32| time as f32 / 1000.;
+        let text = format!(
+            "FPS {:5.1} {:5.1} ms\nCPU {:5.1} GE {:5.1} ms\nDraws {} Verts {}\nVRAM {}/{} KB",
+            stats.fps(),
+            ms(stats.frame_time),
+            ms(stats.cpu_time),
+            ms(stats.ge_time),
+            stats.draw_calls,
+            stats.vertices,
+            vram.used / 1024,
+            vram.total / 1024,
+        );
+        let line_height = DEBUG_FONT_SIZE + 2;
+        let lines = text.lines().count() as i32;
+        let text_width = text.lines().map(str::len).max().unwrap_or(0) as i32 * DEBUG_FONT_SIZE;
+        let graph_height = (GRAPH_BUDGET_HEIGHT * GRAPH_MAX_FRAMES) as i32;
+        let graph_top = y + lines * line_height;
+        let graph_bottom = graph_top + graph_height;
+
+        let mut vertices = Vec::with_capacity(HISTORY * 2 + 6);
+        push_quad(
+            &mut vertices,
+            ((x - 2).max(0), (y - 2).max(0)),
+            (x + (HISTORY as i32).max(text_width) + 2, graph_bottom + 2),
+            Color32::from_rgba(0x000000a0),
+        );
+        // Oldest frame on the left
+        for column in 0..HISTORY {
+            let time = self.frame_times[(self.next + column) % HISTORY];
+            let height = (time as u64 * GRAPH_BUDGET_HEIGHT as u64 / FRAME_BUDGET as u64)
+                .min(graph_height as u64) as i32;
+            let color = match time {
+                0..=FRAME_BUDGET => Color32::GREEN,
+                _ if time <= FRAME_BUDGET * 2 => Color32::YELLOW,
+                _ => Color32::RED,
+            };
+            let left = x + column as i32;
+            push_quad(
+                &mut vertices,
+                (left, graph_bottom - height),
+                (left + 1, graph_bottom),
+                color,
+            );
+        }
+        let budget = graph_bottom - GRAPH_BUDGET_HEIGHT as i32;
+        push_quad(
+            &mut vertices,
+            (x, budget),
+            (x + HISTORY as i32, budget + 1),
+            Color32::WHITE,
+        );
+
+        let saved = frame.render_state();
+        RenderState::diff_apply(&saved, &RenderState::OVERLAY);
+        frame.draw_array(GuPrimitive::Sprites, &frame.get_memory(&vertices));
+        RenderState::restore(&saved);
+        for (row, line) in text.lines().enumerate() {
+            frame.debug_text(x, y + row as i32 * line_height, line);
+        }
+    }
+}
+
+impl Default for PerfHud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
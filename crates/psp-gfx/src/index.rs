@@ -1,3 +1,4 @@
+use alloc::{vec, vec::Vec};
 use psp::sys::VertexType;
 
 /// Marker trait implemented on types that can be used as indices for indexed rendering
@@ -17,3 +18,146 @@ unsafe impl IndexItem for u16 {
         VertexType::INDEX_16BIT
     }
 }
+
+/// Number of entries in the simulated post-transform vertex cache used by [`optimize_vertex_cache`]
+pub const VERTEX_CACHE_SIZE: usize = 16;
+
+fn vertex_cache_score(cache_position: Option<usize>, active_triangles: usize) -> f32 {
+    if active_triangles == 0 {
+        return -1.;
+    }
+    let cache_score = match cache_position {
+        // The most recent triangle's vertices get a fixed score so they aren't reused immediately
+        Some(position) if position < 3 => 0.75,
+        Some(position) => {
+            let scaler = 1. / (VERTEX_CACHE_SIZE - 3) as f32;
+            libm::powf(1. - (position - 3) as f32 * scaler, 1.5)
+        }
+        None => 0.,
+    };
+    // Boost vertices with few remaining triangles to get rid of lone triangles early
+    cache_score + 2. / libm::sqrtf(active_triangles as f32)
+}
+
+/// Reorder the triangles of an indexed triangle list for better vertex cache locality
+///
+/// Uses Tom Forsyth's linear-speed vertex cache optimization algorithm.
+/// Only the order of triangles changes, the winding of each triangle is preserved.
+///
+/// This is fast enough to run on-device at load time, but ideally should be done offline.
+///
+/// # Panics
+///
+/// Panics if an index isn't below `vertex_count`
+pub fn optimize_vertex_cache<I: Copy + Into<usize>>(indices: &mut [I], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+    assert!(
+        indices.iter().all(|&index| index.into() < vertex_count),
+        "an index is out of bounds for {vertex_count} vertices"
+    );
+
+    // Build the vertex -> triangle adjacency
+    let mut active_triangles = vec![0usize; vertex_count];
+    for &index in &indices[..triangle_count * 3] {
+        active_triangles[index.into()] += 1;
+    }
+    let mut adjacency_offset = Vec::with_capacity(vertex_count + 1);
+    adjacency_offset.push(0);
+    for &count in &active_triangles {
+        adjacency_offset.push(adjacency_offset.last().unwrap() + count);
+    }
+    let mut adjacency = vec![0usize; triangle_count * 3];
+    let mut fill = adjacency_offset.clone();
+    for (index_idx, &index) in indices[..triangle_count * 3].iter().enumerate() {
+        let vertex = index.into();
+        adjacency[fill[vertex]] = index_idx / 3;
+        fill[vertex] += 1;
+    }
+
+    let mut vertex_score: Vec<f32> = active_triangles
+        .iter()
+        .map(|&active| vertex_cache_score(None, active))
+        .collect();
+    let triangle_vertices = |triangle: usize| {
+        [
+            indices[triangle * 3].into(),
+            indices[triangle * 3 + 1].into(),
+            indices[triangle * 3 + 2].into(),
+        ]
+    };
+    let mut triangle_score: Vec<f32> = (0..triangle_count)
+        .map(|triangle| {
+            triangle_vertices(triangle)
+                .iter()
+                .map(|&v| vertex_score[v])
+                .sum()
+        })
+        .collect();
+    let mut triangle_added = vec![false; triangle_count];
+    let mut cache: Vec<usize> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(triangle_count);
+
+    let mut best = None;
+    while order.len() < triangle_count {
+        // Fall back to a full scan if no candidate was found in the cache
+        let triangle = best.take().unwrap_or_else(|| {
+            (0..triangle_count)
+                .filter(|&t| !triangle_added[t])
+                .max_by(|&a, &b| triangle_score[a].total_cmp(&triangle_score[b]))
+                .unwrap()
+        });
+        triangle_added[triangle] = true;
+        order.push(triangle);
+
+        // Update the adjacency and the simulated LRU cache
+        let vertices = triangle_vertices(triangle);
+        for &vertex in &vertices {
+            active_triangles[vertex] -= 1;
+            let start = adjacency_offset[vertex];
+            let end = start + active_triangles[vertex] + 1;
+            if let Some(pos) = adjacency[start..end].iter().position(|&t| t == triangle) {
+                adjacency.swap(start + pos, end - 1);
+            }
+            if let Some(pos) = cache.iter().position(|&v| v == vertex) {
+                cache.remove(pos);
+            }
+        }
+        for &vertex in vertices.iter().rev() {
+            cache.insert(0, vertex);
+        }
+
+        // Recompute scores of everything touched by the cache
+        for (position, &vertex) in cache.iter().enumerate() {
+            let position = (position < VERTEX_CACHE_SIZE).then_some(position);
+            let new_score = vertex_cache_score(position, active_triangles[vertex]);
+            let delta = new_score - vertex_score[vertex];
+            vertex_score[vertex] = new_score;
+            let start = adjacency_offset[vertex];
+            for &t in &adjacency[start..start + active_triangles[vertex]] {
+                triangle_score[t] += delta;
+            }
+        }
+        cache.truncate(VERTEX_CACHE_SIZE);
+
+        // Pick the next best triangle among the cached vertices, once every score is up to date
+        let mut best_score = -1.;
+        for &vertex in &cache {
+            let start = adjacency_offset[vertex];
+            for &t in &adjacency[start..start + active_triangles[vertex]] {
+                if triangle_score[t] > best_score {
+                    best_score = triangle_score[t];
+                    best = Some(t);
+                }
+            }
+        }
+    }
+
+    let reordered: Vec<I> = order
+        .iter()
+        .flat_map(|&t| [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]])
+        .collect();
+    indices[..triangle_count * 3].copy_from_slice(&reordered);
+}
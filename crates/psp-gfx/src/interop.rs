@@ -0,0 +1,61 @@
+//! Conversions between the crate's math types and external math libraries
+
+use crate::math::{Mat4, Vec3};
+
+#[cfg(feature = "glam")]
+mod glam_impl {
+    use super::*;
+
+    impl From<glam::Mat4> for Mat4 {
+        fn from(m: glam::Mat4) -> Self {
+            Mat4::from_cols(m.to_cols_array_2d())
+        }
+    }
+
+    impl From<Mat4> for glam::Mat4 {
+        fn from(m: Mat4) -> Self {
+            glam::Mat4::from_cols_array_2d(&m.cols)
+        }
+    }
+
+    impl From<glam::Vec3> for Vec3 {
+        fn from(v: glam::Vec3) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vec3> for glam::Vec3 {
+        fn from(v: Vec3) -> Self {
+            glam::Vec3::new(v.x, v.y, v.z)
+        }
+    }
+}
+
+#[cfg(feature = "vek")]
+mod vek_impl {
+    use super::*;
+
+    impl From<vek::Mat4<f32>> for Mat4 {
+        fn from(m: vek::Mat4<f32>) -> Self {
+            Mat4::from_cols(m.into_col_arrays())
+        }
+    }
+
+    impl From<Mat4> for vek::Mat4<f32> {
+        fn from(m: Mat4) -> Self {
+            vek::Mat4::from_col_arrays(m.cols)
+        }
+    }
+
+    impl From<vek::Vec3<f32>> for Vec3 {
+        fn from(v: vek::Vec3<f32>) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vec3> for vek::Vec3<f32> {
+        fn from(v: Vec3) -> Self {
+            vek::Vec3::new(v.x, v.y, v.z)
+        }
+    }
+}
@@ -1,10 +1,14 @@
 #![no_std]
+#![cfg_attr(
+    all(feature = "vfpu", target_os = "psp"),
+    feature(asm_experimental_arch)
+)]
 #![allow(static_mut_refs)]
 #![allow(clippy::missing_safety_doc)]
 
 extern crate alloc;
 
-use core::mem::ManuallyDrop;
+use core::{ffi::c_void, mem::ManuallyDrop};
 use psp::{
     Align16, BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH,
     sys::{
@@ -16,25 +20,123 @@ use psp::{
 
 #[cfg(feature = "gfx_ext")]
 pub mod gfx_ext;
+#[cfg(any(feature = "glam", feature = "vek"))]
+mod interop;
 
+pub mod bake;
+pub mod blit;
+pub mod blur;
 pub mod buffer;
+pub mod camera;
 pub mod color;
+pub mod command;
+pub mod curve;
+pub mod debug_text;
+pub mod decode;
+pub mod depth_view;
+pub mod display_list;
+pub mod font;
+pub mod frustum;
+pub mod glyph_cache;
+pub mod hud;
 pub mod index;
+pub mod light;
+pub mod low_res;
+pub mod material;
+pub mod math;
+pub mod matrix;
+pub mod mesh;
+pub mod nine_slice;
+pub mod parallax;
+pub mod patch;
+pub mod post_effects;
+pub mod post_process;
+pub mod profiler;
 pub mod rect;
+pub mod reflection;
+pub mod render_target;
+pub mod scene;
+pub mod screenshot;
+pub mod shadow;
+pub mod shapes;
+pub mod signal;
+pub mod split_screen;
+pub mod sprite;
+pub mod state;
+pub mod system_font;
+pub mod terrain;
+pub mod text_layout;
+pub mod texture;
+pub mod tilemap;
+pub mod transform;
+pub mod transition;
+pub mod ui;
 pub mod vertex;
+pub mod viewport;
+pub mod vram;
 
 use buffer::{Buffer, TransientBuffer};
 use color::Color32;
 use index::IndexItem;
+use matrix::MatrixKind;
 use rect::Rect;
 use vertex::Vertex;
+use viewport::{DepthRange, FULL_SCREEN, VIRTUAL_CENTER};
 
-pub static mut BUFFER: Align16<[u32; 0x40000]> = Align16([0; 0x40000]);
+/// Display list buffers, frames alternate between them
+pub static mut BUFFERS: [Align16<[u32; 0x40000]>; 2] = [Align16([0; 0x40000]); 2];
+
+/// Size of each display list buffer in bytes
+pub const LIST_BUFFER_SIZE: usize = core::mem::size_of::<[u32; 0x40000]>();
+
+/// Size of the buffer the current display list is built in, in bytes
+static mut LIST_CAPACITY: usize = LIST_BUFFER_SIZE;
+
+/// Room kept free after every checked write, for the commands ending the list and the state changes before it
+const LIST_RESERVE: usize = 256;
+
+/// Worst case size of a draw call, with all four matrices uploaded first
+pub(crate) const DRAW_COMMANDS_SIZE: usize = 256;
+
+/// Set the size of the buffer the display list is built in, returning the previous one
+pub(crate) fn replace_list_capacity(capacity: usize) -> usize {
+    unsafe { core::mem::replace(&mut LIST_CAPACITY, capacity) }
+}
+
+/// Make sure `bytes` more fit into the display list being built, before they're written
+///
+/// # Panics
+///
+/// Panics if the list would overflow its buffer
+pub(crate) fn reserve_list(bytes: usize) {
+    let used = unsafe { sys::sceGuCheckList() } as usize;
+    let capacity = unsafe { LIST_CAPACITY };
+    assert!(
+        used + bytes + LIST_RESERVE <= capacity,
+        "display list overflow, {bytes} more bytes don't fit into a {capacity} byte buffer with {used} bytes used"
+    );
+}
+
+/// Display list consumption of finished frames, in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListUsage {
+    /// Size of the list of the last finished frame
+    pub last_frame: usize,
+    /// Largest list of all frames since the peak was last reset
+    pub peak: usize,
+}
+
+static mut SCISSOR: Rect = FULL_SCREEN;
 
 pub struct PspGfx {
     pub(crate) fbp0: *mut u8,
     pub(crate) fbp1: *mut u8,
     pub(crate) zbp: *mut u8,
+    frame: usize,
+    pending: bool,
+    pipelined: bool,
+    list_usage: ListUsage,
+    profiler: profiler::Profiler,
 }
 
 impl PspGfx {
@@ -49,13 +151,20 @@ impl PspGfx {
         let zbp = allocator
             .alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444)
             .as_mut_ptr_from_zero();
+        // The psp allocator can't free, render targets get the rest of VRAM from our own
+        vram::init(zbp as u32 + BUF_WIDTH * SCREEN_HEIGHT * 2, unsafe {
+            sys::sceGeEdramGetSize()
+        });
 
         unsafe {
             sys::sceGuInit();
-            sys::sceGumLoadIdentity();
+            for kind in MatrixKind::ALL {
+                sys::sceGumMatrixMode(kind.into());
+                sys::sceGumLoadIdentity();
+            }
             sys::sceGuStart(
                 psp::sys::GuContextType::Direct,
-                BUFFER.0.as_mut_ptr() as *mut _,
+                BUFFERS[0].0.as_mut_ptr() as *mut _,
             );
             sys::sceGuDrawBuffer(DisplayPixelFormat::Psm8888, fbp0 as _, BUF_WIDTH as i32);
             sys::sceGuDispBuffer(
@@ -65,54 +174,204 @@ impl PspGfx {
                 BUF_WIDTH as i32,
             );
             sys::sceGuDepthBuffer(zbp as _, BUF_WIDTH as i32);
-            sys::sceGuOffset(2048 - (SCREEN_WIDTH / 2), 2048 - (SCREEN_HEIGHT / 2));
-            sys::sceGuViewport(2048, 2048, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
-            sys::sceGuDepthRange(65535, 0);
+            sys::sceGuOffset(
+                VIRTUAL_CENTER as u32 - (SCREEN_WIDTH / 2),
+                VIRTUAL_CENTER as u32 - (SCREEN_HEIGHT / 2),
+            );
+            sys::sceGuViewport(
+                VIRTUAL_CENTER,
+                VIRTUAL_CENTER,
+                SCREEN_WIDTH as i32,
+                SCREEN_HEIGHT as i32,
+            );
+            sys::sceGuDepthRange(DepthRange::FULL.near as i32, DepthRange::FULL.far as i32);
             sys::sceGuScissor(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
             sys::sceGuEnable(GuState::ScissorTest);
+            state::RenderState::reset();
+            // Always installed, the profiler timestamps finished frames
+            sys::sceGuSetCallback(sys::GuCallbackId::Finish, Some(signal::on_finish));
             sys::sceGuFinish();
             sys::sceGuSync(sys::GuSyncMode::Finish, sys::GuSyncBehavior::Wait);
             sys::sceDisplayWaitVblankStart();
             sys::sceGuDisplay(true);
         }
 
-        Self { fbp0, fbp1, zbp }
+        Self {
+            fbp0,
+            fbp1,
+            zbp,
+            frame: 0,
+            pending: false,
+            pipelined: false,
+            list_usage: ListUsage::default(),
+            profiler: profiler::Profiler::default(),
+        }
     }
 
+    /// Start building the display list of the next frame
+    ///
+    /// The list is only sent to the GE once the frame is finished, and the frame is displayed
+    /// as soon as the GE is done with it, unless pipelining is enabled with [`PspGfx::set_pipelined`]
     pub fn start_frame<'a>(&'a mut self) -> Frame<'a> {
+        self.profiler.frame_started();
+        let draw_buffer = self.draw_buffer();
+        replace_list_capacity(LIST_BUFFER_SIZE);
         unsafe {
             sys::sceGuStart(
-                psp::sys::GuContextType::Direct,
-                BUFFER.0.as_mut_ptr() as *mut _,
+                psp::sys::GuContextType::Send,
+                BUFFERS[self.frame % 2].0.as_mut_ptr() as *mut _,
+            );
+            // Only direct lists set the draw buffer automatically
+            sys::sceGuDrawBufferList(
+                DisplayPixelFormat::Psm8888,
+                draw_buffer as _,
+                BUF_WIDTH as i32,
             );
         }
-        Frame { _gfx: self }
+        let frame = Frame { gfx: self };
+        frame.begin_low_res();
+        frame
+    }
+
+    /// Get the VRAM offset of the buffer the current frame is drawn into
+    pub(crate) fn draw_buffer(&self) -> *mut u8 {
+        [self.fbp0, self.fbp1][self.frame % 2]
+    }
+
+    /// Get the display list sizes of the finished frames
+    ///
+    /// Compare against [`LIST_BUFFER_SIZE`] to see how much headroom is left,
+    /// draws panic once a frame would overflow its buffer
+    pub fn list_usage(&self) -> ListUsage {
+        self.list_usage
+    }
+
+    /// Get the display list of the last finished frame, e.g. for [`decode`](decode::decode)
+    pub fn last_frame_list(&self) -> &[u32] {
+        match self.frame.checked_sub(1) {
+            Some(frame) => unsafe { &BUFFERS[frame % 2].0[..self.list_usage.last_frame / 4] },
+            None => &[],
+        }
+    }
+
+    /// Forget the peak display list size, e.g. after a loading screen
+    pub fn reset_list_peak(&mut self) {
+        self.list_usage.peak = self.list_usage.last_frame;
+    }
+
+    /// Pause the execution of display lists on the GE
+    ///
+    /// The GE stops after the command it's currently processing.
+    /// If `reset_queues` is set, all queued lists are discarded as well.
+    ///
+    /// This can only recover from a list that never finishes with [pipelining](PspGfx::set_pipelined)
+    /// enabled, before the next frame is finished and waits for it. Without pipelining,
+    /// finishing a frame already waits for the GE to draw it and never returns
+    pub fn break_execution(&mut self, reset_queues: bool) {
+        unsafe {
+            sys::sceGuBreak(reset_queues as i32);
+        }
+        if reset_queues {
+            self.pending = false;
+        }
+    }
+
+    /// Resume the execution paused with [`PspGfx::break_execution`]
+    pub fn continue_execution(&mut self) {
+        unsafe {
+            sys::sceGuContinue();
+        }
+    }
+
+    /// Let the CPU build the next frame while the GE is still drawing the last finished one
+    ///
+    /// A finished frame is then only displayed when the frame after it is finished.
+    /// Pipelining is disabled by default, finishing a frame waits for the GE to draw it
+    ///
+    /// # Safety
+    ///
+    /// While enabled, everything a frame borrows (textures, buffers, display lists, render targets...)
+    /// is still read by the GE after the frame is finished. It must stay alive and unmodified
+    /// until the next frame is finished or [`PspGfx::sync`] is called
+    pub unsafe fn set_pipelined(&mut self, pipelined: bool) {
+        self.pipelined = pipelined;
+    }
+
+    pub fn is_pipelined(&self) -> bool {
+        self.pipelined
+    }
+
+    /// Wait until the GE is done with the last finished frame
+    pub fn sync(&mut self) {
+        if self.pending {
+            unsafe {
+                sys::sceGuSync(sys::GuSyncMode::Send, sys::GuSyncBehavior::Wait);
+            }
+        }
+    }
+
+    /// Wait for the GE to finish the frame that was sent last and display it
+    fn present(&mut self) {
+        unsafe {
+            sys::sceGuSync(sys::GuSyncMode::Send, sys::GuSyncBehavior::Wait);
+            sys::sceDisplayWaitVblankStart();
+            sys::sceGuSwapBuffers();
+        }
+        self.profiler.frame_swapped();
+        self.pending = false;
     }
 }
 
 pub struct Frame<'gfx> {
-    _gfx: &'gfx mut PspGfx,
+    gfx: &'gfx mut PspGfx,
 }
 
 impl<'gfx> Frame<'gfx> {
-    fn finish_non_consuming(&self) {
+    fn finish_non_consuming(&mut self) {
+        self.resolve_low_res();
+        let gfx = &mut *self.gfx;
+        // The id is passed to the finish handler
+        // Overflows are caught by `reserve_list` before anything is written past the buffer
+        let size = unsafe { sys::sceGuFinishId(gfx.frame as u32 & 0xffff) } as usize;
+        gfx.list_usage.last_frame = size;
+        gfx.list_usage.peak = gfx.list_usage.peak.max(size);
+        gfx.profiler.frame_built();
+        // Show the previous frame once it's done, the GE only starts on this one afterwards
+        if gfx.pending {
+            gfx.present();
+        }
         unsafe {
-            sys::sceGuFinish();
-            sys::sceGuSync(sys::GuSyncMode::Finish, sys::GuSyncBehavior::Wait);
-            sys::sceDisplayWaitVblankStart();
-            sys::sceGuSwapBuffers();
+            sys::sceGuSendList(
+                sys::GuQueueMode::Tail,
+                BUFFERS[gfx.frame % 2].0.as_ptr() as *const _,
+                core::ptr::null_mut(),
+            );
+        }
+        gfx.profiler.list_sent();
+        gfx.frame += 1;
+        gfx.pending = true;
+        // Without pipelining, the resources borrowed by the frame are free to go once this returns
+        if !gfx.pipelined {
+            gfx.present();
         }
     }
 
-    /// Finish rendering
+    /// Finish rendering and submit the frame to the GE
     ///
     /// Note that you don't have to call this as the `Frame` is terminated automatically when it's dropped
-    pub fn finish(self) {
+    pub fn finish(mut self) {
         self.finish_non_consuming();
         // XXX: this could *potentially* leak
         let _ = ManuallyDrop::new(self);
     }
 
+    /// Get the number of bytes used so far by the display list being built
+    ///
+    /// While recording a [`DisplayList`](display_list::DisplayList), the size of the recorded list is returned instead
+    pub fn list_size(&self) -> usize {
+        unsafe { sys::sceGuCheckList() as usize }
+    }
+
     /// Clear the color buffer with the specified color
     pub fn clear_color(&self, color: Color32) {
         unsafe {
@@ -129,6 +388,14 @@ impl<'gfx> Frame<'gfx> {
         }
     }
 
+    /// Clear the stencil buffer using the specified value
+    pub fn clear_stencil(&self, stencil: u8) {
+        unsafe {
+            sys::sceGuClearStencil(stencil as u32);
+            sys::sceGuClear(sys::ClearBuffer::STENCIL_BUFFER_BIT);
+        }
+    }
+
     /// Clear both color and depth buffers using the specified data
     pub fn clear_color_depth(&self, color: Color32, depth: u32) {
         unsafe {
@@ -145,10 +412,15 @@ impl<'gfx> Frame<'gfx> {
         texture_effect: TextureEffect,
         texture_color_component: TextureColorComponent,
     ) {
-        // XXX: this affects context outside of current frame
-        unsafe {
-            sys::sceGuTexFunc(texture_effect, texture_color_component);
-        }
+        state::upload_texture_function(state::TextureFunction::new(
+            texture_effect,
+            texture_color_component,
+        ));
+    }
+
+    /// Get the current texture function
+    pub fn texture_function(&self) -> state::TextureFunction {
+        self.render_state().texture_function
     }
 
     pub fn set_shading_model(&self, shading_model: ShadingModel) {
@@ -164,10 +436,46 @@ impl<'gfx> Frame<'gfx> {
         }
     }
 
+    /// Limit drawing to a region of the screen (in pixels)
     pub fn set_scissor(&self, scissor: Rect) {
         unsafe {
-            sys::sceGuScissor(scissor.x, scissor.y, scissor.w, scissor.h);
+            SCISSOR = scissor;
+            if scissor.is_empty() {
+                // The end is stored inclusive and masked, so a zero sized region would wrap around
+                // to the whole 1024x1024 space. A start past the end rejects every pixel instead
+                sys::sceGuScissor(1, 1, 1, 1);
+                return;
+            }
+            // Despite the parameter names, sceGuScissor takes the right and bottom edges
+            sys::sceGuScissor(
+                scissor.x,
+                scissor.y,
+                scissor.x + scissor.w,
+                scissor.y + scissor.h,
+            );
+        }
+    }
+
+    /// Get the current scissor region
+    pub fn scissor(&self) -> Rect {
+        unsafe { SCISSOR }
+    }
+
+    /// Limit drawing to the part of `rect` inside the current scissor region and the screen
+    /// (or render target, see [`Frame::render_to`])
+    ///
+    /// Returns `false` without changing the scissor region if the resulting region is empty,
+    /// in which case drawing can be skipped entirely
+    pub fn intersect_scissor(&self, rect: Rect) -> bool {
+        let scissor = self
+            .scissor()
+            .intersect(&rect)
+            .intersect(&render_target::bounds());
+        if scissor.is_empty() {
+            return false;
         }
+        self.set_scissor(scissor);
+        true
     }
 
     /// Get memory from sceGuGetMemory as a [`TransientBuffer`]
@@ -180,21 +488,47 @@ impl<'gfx> Frame<'gfx> {
         unsafe { TransientBuffer::get_memory_static(data) }
     }
 
+    /// Get `len` items of memory from sceGuGetMemory, filled in place with `f(index)`
+    ///
+    /// Avoids building the data in a separate allocation first, e.g. for generated geometry
+    pub fn get_memory_with<'frame, T: Clone + Copy>(
+        &'frame self,
+        len: usize,
+        f: impl FnMut(usize) -> T,
+    ) -> TransientBuffer<'frame, T> {
+        unsafe { TransientBuffer::get_memory_with_static(len, f) }
+    }
+
     pub fn draw_array<V: Buffer>(&self, primitive: GuPrimitive, vertex_buf: &V)
     where
         V::Item: Vertex,
     {
         unsafe {
-            sys::sceGuDrawArray(
+            draw_raw(
                 primitive,
                 V::Item::vtype(),
-                vertex_buf.len() as i32,
+                vertex_buf.len(),
                 core::ptr::null(),
                 vertex_buf.as_ptr(),
             );
         }
     }
 
+    /// Draw axis-aligned quads using the two-vertex sprite primitive
+    ///
+    /// Each pair of vertices describes the top-left and bottom-right corners of a quad.
+    /// See [`SpriteVertex::quad`](vertex::SpriteVertex::quad) for building the vertex pairs
+    pub fn draw_sprites<V: Buffer>(&self, vertex_buf: &V)
+    where
+        V::Item: Vertex,
+    {
+        debug_assert!(
+            vertex_buf.len().is_multiple_of(2),
+            "sprites require vertex pairs"
+        );
+        self.draw_array(GuPrimitive::Sprites, vertex_buf);
+    }
+
     pub fn draw_array_indexed<V: Buffer, I: Buffer>(
         &self,
         primitive: GuPrimitive,
@@ -206,10 +540,10 @@ impl<'gfx> Frame<'gfx> {
     {
         // XXX: are indices pointing oob ub?
         unsafe {
-            sys::sceGuDrawArray(
+            draw_raw(
                 primitive,
                 V::Item::vtype() | I::Item::vtype(),
-                index_buf.len() as i32,
+                index_buf.len(),
                 index_buf.as_ptr(),
                 vertex_buf.as_ptr(),
             );
@@ -217,6 +551,39 @@ impl<'gfx> Frame<'gfx> {
     }
 }
 
+/// Flush the matrices and submit a draw call
+///
+/// `indices` and `vertices` have to point to data matching `vtype` that stays alive until the GE is done with it
+pub(crate) unsafe fn draw_raw(
+    primitive: GuPrimitive,
+    vtype: sys::VertexType,
+    count: usize,
+    indices: *const c_void,
+    vertices: *const c_void,
+) {
+    debug_validate_vertex_type(&vtype);
+    reserve_list(DRAW_COMMANDS_SIZE);
+    profiler::count_draw(count);
+    unsafe {
+        sys::sceGumUpdateMatrix();
+        sys::sceGuDrawArray(primitive, vtype, count as i32, indices, vertices);
+    }
+}
+
+/// Catch vertex formats that can't be drawn correctly with the current GE state (debug builds only)
+fn debug_validate_vertex_type(vtype: &sys::VertexType) {
+    if cfg!(debug_assertions) {
+        // 2D vertices bypass lighting
+        let lit = !vtype.contains(sys::VertexType::TRANSFORM_2D)
+            && unsafe { sys::sceGuGetStatus(GuState::Lighting) };
+        // NORMAL_32BITF has both bits of the normal format field set
+        debug_assert!(
+            !lit || vtype.intersects(sys::VertexType::NORMAL_32BITF),
+            "hardware lighting is enabled, but the vertex layout has no normals"
+        );
+    }
+}
+
 impl<'a> Drop for Frame<'a> {
     fn drop(&mut self) {
         self.finish_non_consuming();
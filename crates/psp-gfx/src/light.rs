@@ -0,0 +1,435 @@
+use core::marker::PhantomData;
+
+use psp::sys::{self, GuState, LightComponent, LightMode, LightType};
+
+use crate::{Frame, color::Color32, material::Material, math::Vec3, state};
+
+/// Number of hardware light slots on the GE
+pub const MAX_LIGHTS: usize = 4;
+
+const LIGHT_STATES: [GuState; MAX_LIGHTS] = [
+    GuState::Light0,
+    GuState::Light1,
+    GuState::Light2,
+    GuState::Light3,
+];
+
+/// Shape of a [`Light`]
+///
+/// Positions and directions are in world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Light infinitely far away (e.g. the sun), `direction` is the direction the light travels in
+    Directional { direction: Vec3 },
+    /// Light emitted in all directions from `position`
+    Point { position: Vec3 },
+    /// Cone of light emitted from `position` along `direction`
+    Spot { position: Vec3, direction: Vec3 },
+}
+
+/// Lighting terms computed by a [`Light`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightComponents {
+    /// Diffuse only, the light never produces specular highlights
+    Diffuse,
+    /// Diffuse and specular, required for highlights from the material specular color
+    #[default]
+    DiffuseAndSpecular,
+    /// Diffuse raised to the material specular power, without specular highlights
+    ///
+    /// Gives a sharper light/shadow transition than plain diffuse
+    PoweredDiffuse,
+}
+
+impl From<LightComponents> for LightComponent {
+    fn from(components: LightComponents) -> Self {
+        match components {
+            LightComponents::Diffuse => LightComponent::DIFFUSE,
+            LightComponents::DiffuseAndSpecular => {
+                LightComponent::DIFFUSE | LightComponent::SPECULAR
+            }
+            LightComponents::PoweredDiffuse => LightComponent::UNKNOWN_LIGHT_COMPONENT,
+        }
+    }
+}
+
+/// Distance falloff of point and spot lights
+///
+/// The light intensity at distance `d` is scaled by `1 / (constant + linear * d + quadratic * d²)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// No falloff, the light has the same intensity at any distance
+    pub const NONE: Self = Self::new(1., 0., 0.);
+
+    pub const fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Self {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    /// Falloff reaching roughly a quarter of the full intensity at `radius`
+    /// and fading out smoothly past it
+    pub fn from_radius(radius: f32) -> Self {
+        let radius = radius.max(f32::EPSILON);
+        Self::new(1., 2. / radius, 1. / (radius * radius))
+    }
+
+    /// Physically based inverse square falloff, reaching half intensity at `distance`
+    pub fn inverse_square(distance: f32) -> Self {
+        let distance = distance.max(f32::EPSILON);
+        Self::new(1., 0., 1. / (distance * distance))
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Shape of the cone of a spot light
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotCone {
+    exponent: f32,
+    cutoff: f32,
+}
+
+impl SpotCone {
+    /// Highest exponent accepted by [`SpotCone::new`]
+    pub const MAX_EXPONENT: f32 = 128.;
+
+    /// Cone with a 45° half-angle and a soft edge
+    pub const DEFAULT: Self = Self {
+        exponent: 1.,
+        cutoff: core::f32::consts::FRAC_PI_4,
+    };
+
+    /// Create a new spot light cone
+    ///
+    /// - `exponent`: how quickly the light fades out towards the edge of the cone, 0 for a hard edge
+    /// - `cutoff`: half-angle of the cone in radians, light outside of it is discarded
+    ///
+    /// # Panics
+    ///
+    /// Panics if `exponent` is not within `0..=MAX_EXPONENT` or `cutoff` is not within `0..=π/2`
+    pub fn new(exponent: f32, cutoff: f32) -> Self {
+        assert!(
+            (0. ..=Self::MAX_EXPONENT).contains(&exponent),
+            "spot light exponent must be within 0..={}",
+            Self::MAX_EXPONENT
+        );
+        assert!(
+            (0. ..=core::f32::consts::FRAC_PI_2).contains(&cutoff),
+            "spot light cutoff must be within 0..=π/2 radians"
+        );
+        Self { exponent, cutoff }
+    }
+
+    pub fn exponent(&self) -> f32 {
+        self.exponent
+    }
+
+    /// Half-angle of the cone in radians
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+}
+
+impl Default for SpotCone {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Hardware light, uploaded to one of the GE light slots with [`LightSlot::set`]
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub components: LightComponents,
+    pub diffuse: Color32,
+    pub specular: Color32,
+    pub ambient: Color32,
+    /// Only used by point and spot lights
+    pub attenuation: Attenuation,
+    /// Only used by spot lights
+    pub spot: SpotCone,
+}
+
+impl Light {
+    /// Create a white light of the specified kind
+    pub const fn new(kind: LightKind) -> Self {
+        Self {
+            kind,
+            components: LightComponents::DiffuseAndSpecular,
+            diffuse: Color32::WHITE,
+            specular: Color32::WHITE,
+            ambient: Color32::BLACK,
+            attenuation: Attenuation::NONE,
+            spot: SpotCone::DEFAULT,
+        }
+    }
+
+    pub const fn directional(direction: Vec3) -> Self {
+        Self::new(LightKind::Directional { direction })
+    }
+
+    pub const fn point(position: Vec3) -> Self {
+        Self::new(LightKind::Point { position })
+    }
+
+    pub const fn spot(position: Vec3, direction: Vec3) -> Self {
+        Self::new(LightKind::Spot {
+            position,
+            direction,
+        })
+    }
+
+    pub const fn with_components(self, components: LightComponents) -> Self {
+        Self { components, ..self }
+    }
+
+    /// Set both the diffuse and specular colors
+    pub const fn with_color(self, color: Color32) -> Self {
+        Self {
+            diffuse: color,
+            specular: color,
+            ..self
+        }
+    }
+
+    pub const fn with_diffuse(self, diffuse: Color32) -> Self {
+        Self { diffuse, ..self }
+    }
+
+    pub const fn with_specular(self, specular: Color32) -> Self {
+        Self { specular, ..self }
+    }
+
+    /// Set the color this light adds to the ambient term of lit surfaces
+    pub const fn with_ambient(self, ambient: Color32) -> Self {
+        Self { ambient, ..self }
+    }
+
+    pub const fn with_attenuation(self, attenuation: Attenuation) -> Self {
+        Self {
+            attenuation,
+            ..self
+        }
+    }
+
+    pub const fn with_spot_cone(self, spot: SpotCone) -> Self {
+        Self { spot, ..self }
+    }
+
+    /// Set the spot light exponent, see [`SpotCone::new`]
+    pub fn with_spot_exponent(self, exponent: f32) -> Self {
+        self.with_spot_cone(SpotCone::new(exponent, self.spot.cutoff))
+    }
+
+    /// Set the spot light cone half-angle in radians, see [`SpotCone::new`]
+    pub fn with_spot_cutoff(self, cutoff: f32) -> Self {
+        self.with_spot_cone(SpotCone::new(self.spot.exponent, cutoff))
+    }
+}
+
+/// Complete lighting setup, applied with [`Frame::set_lighting_setup`]
+#[derive(Clone, Copy, Debug)]
+pub struct Lighting {
+    /// Lights assigned to the hardware slots, empty slots are disabled
+    pub lights: [Option<Light>; MAX_LIGHTS],
+    pub ambient: Color32,
+    pub material: Material,
+    pub separate_specular: bool,
+}
+
+impl Lighting {
+    /// Setup without any lights, only the ambient light
+    pub const fn new(ambient: Color32) -> Self {
+        Self {
+            lights: [None; MAX_LIGHTS],
+            ambient,
+            material: Material::DEFAULT,
+            separate_specular: false,
+        }
+    }
+
+    /// A single directional light (e.g. the sun) over an ambient base
+    pub const fn simple_directional(direction: Vec3, color: Color32, ambient: Color32) -> Self {
+        Self::new(ambient).with_light(0, Light::directional(direction).with_color(color))
+    }
+
+    /// A single point light fading out around `radius` over an ambient base
+    pub fn simple_point(position: Vec3, color: Color32, radius: f32, ambient: Color32) -> Self {
+        let light = Light::point(position)
+            .with_color(color)
+            .with_attenuation(Attenuation::from_radius(radius));
+        Self::new(ambient).with_light(0, light)
+    }
+
+    /// Key light with a dimmer fill light coming from the opposite side, avoids flat unlit backsides
+    pub fn key_fill(direction: Vec3, color: Color32, fill: Color32, ambient: Color32) -> Self {
+        Self::new(ambient)
+            .with_light(0, Light::directional(direction).with_color(color))
+            .with_light(1, Light::directional(-direction).with_color(fill))
+    }
+
+    /// Assign a light to a hardware slot
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [`MAX_LIGHTS`]
+    pub const fn with_light(mut self, index: usize, light: Light) -> Self {
+        self.lights[index] = Some(light);
+        self
+    }
+
+    pub const fn with_material(self, material: Material) -> Self {
+        Self { material, ..self }
+    }
+
+    pub const fn with_separate_specular(self, separate_specular: bool) -> Self {
+        Self {
+            separate_specular,
+            ..self
+        }
+    }
+}
+
+/// Handle to one of the [`MAX_LIGHTS`] hardware light slots
+#[derive(Clone, Copy)]
+pub struct LightSlot<'frame> {
+    index: usize,
+    _frame: PhantomData<&'frame ()>,
+}
+
+impl<'frame> LightSlot<'frame> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Upload the light parameters to this slot
+    ///
+    /// This does not enable the slot, see [`LightSlot::enable`]
+    pub fn set(&self, light: &Light) {
+        let index = self.index as i32;
+        let (kind, position) = match light.kind {
+            // The GE expects the direction towards the light
+            LightKind::Directional { direction } => (LightType::Directional, -direction),
+            LightKind::Point { position } => (LightType::Pointlight, position),
+            LightKind::Spot { position, .. } => (LightType::Spotlight, position),
+        };
+        unsafe {
+            sys::sceGuLight(index, kind, light.components.into(), &position.into());
+            sys::sceGuLightColor(index, LightComponent::DIFFUSE, light.diffuse.as_abgr());
+            sys::sceGuLightColor(index, LightComponent::SPECULAR, light.specular.as_abgr());
+            sys::sceGuLightColor(index, LightComponent::AMBIENT, light.ambient.as_abgr());
+            if !matches!(light.kind, LightKind::Directional { .. }) {
+                let Attenuation {
+                    constant,
+                    linear,
+                    quadratic,
+                } = light.attenuation;
+                sys::sceGuLightAtt(index, constant, linear, quadratic);
+            }
+            if let LightKind::Spot { direction, .. } = light.kind {
+                // The GE compares the cosine of the angle to the cutoff
+                sys::sceGuLightSpot(
+                    index,
+                    &direction.into(),
+                    light.spot.exponent,
+                    libm::cosf(light.spot.cutoff),
+                );
+            }
+        }
+    }
+
+    pub fn enable(&self) {
+        self.set_enabled(true);
+    }
+
+    pub fn disable(&self) {
+        self.set_enabled(false);
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        unsafe {
+            sys::sceGuSetStatus(LIGHT_STATES[self.index], enabled as i32);
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        unsafe { sys::sceGuGetStatus(LIGHT_STATES[self.index]) }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Get a handle to a hardware light slot
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [`MAX_LIGHTS`]
+    pub fn light(&self, index: usize) -> LightSlot<'_> {
+        assert!(index < MAX_LIGHTS, "the GE only has {MAX_LIGHTS} lights");
+        LightSlot {
+            index,
+            _frame: PhantomData,
+        }
+    }
+
+    /// Enable or disable hardware lighting as a whole
+    ///
+    /// Individual lights also have to be enabled through [`Frame::light`].
+    /// While lighting is enabled, 3D vertex layouts must have normals,
+    /// drawing layouts without them panics in debug builds
+    pub fn set_lighting(&self, enabled: bool) {
+        state::upload_lighting(enabled);
+    }
+
+    /// Set the global ambient light color
+    ///
+    /// Ambient light reaches every lit surface (scaled by the material ambient color),
+    /// so it keeps geometry outside the range of the other lights from turning pitch black
+    pub fn set_ambient(&self, color: Color32) {
+        unsafe {
+            sys::sceGuAmbient(color.as_abgr());
+        }
+    }
+
+    /// Compute specular lighting in a separate color, added after texturing
+    ///
+    /// With a single color the specular highlights are modulated by the texture like the rest of the lighting,
+    /// which makes them barely visible on dark textures
+    pub fn set_separate_specular(&self, enabled: bool) {
+        let mode = if enabled {
+            LightMode::SeparateSpecularColor
+        } else {
+            LightMode::SingleColor
+        };
+        unsafe {
+            sys::sceGuLightMode(mode);
+        }
+    }
+
+    /// Enable lighting and apply a complete [`Lighting`] setup in one call
+    pub fn set_lighting_setup(&self, lighting: &Lighting) {
+        for (index, light) in lighting.lights.iter().enumerate() {
+            let slot = self.light(index);
+            if let Some(light) = light {
+                slot.set(light);
+            }
+            slot.set_enabled(light.is_some());
+        }
+        self.set_ambient(lighting.ambient);
+        self.set_material(&lighting.material);
+        self.set_separate_specular(lighting.separate_specular);
+        self.set_lighting(true);
+    }
+}
@@ -0,0 +1,80 @@
+//! Rendering at a reduced resolution, scaled up to the screen once the scene is drawn
+//!
+//! Fill rate is often what limits the frame rate of 3D scenes, and drawing fewer pixels
+//! is the cheapest way around it
+
+use crate::{
+    Frame, PspGfx, post_process::PostProcessState, render_target::RenderTarget,
+    texture::TextureFilter, viewport::FULL_SCREEN,
+};
+
+static mut TARGET: Option<RenderTarget> = None;
+/// Whether the current frame is still drawn into the low resolution target
+static mut ACTIVE: bool = false;
+
+impl PspGfx {
+    /// Draw every frame at `width` by `height` until [`Frame::resolve_low_res`] is called,
+    /// or at full resolution with `None`
+    ///
+    /// The reduced resolution should keep the aspect ratio of the screen, e.g. 320x181 or 240x136.
+    /// Waits for the GE to finish the last frame before replacing the render target.
+    /// Returns `false` if there's not enough VRAM left, in which case frames are drawn at full resolution
+    pub fn set_low_res(&mut self, size: Option<(u32, u32)>) -> bool {
+        self.sync();
+        unsafe {
+            TARGET = None;
+            TARGET = match size {
+                Some((width, height)) => match RenderTarget::new(self, width, height, true) {
+                    Some(target) => Some(target),
+                    None => return false,
+                },
+                None => None,
+            };
+        }
+        true
+    }
+
+    /// Get the resolution frames are drawn at before being resolved, `None` for full resolution
+    pub fn low_res(&self) -> Option<(u32, u32)> {
+        unsafe {
+            TARGET
+                .as_ref()
+                .map(|target| (target.width(), target.height()))
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Start drawing into the low resolution target, if there is one
+    pub(crate) fn begin_low_res(&self) {
+        let Some(target) = (unsafe { TARGET.as_ref() }) else {
+            return;
+        };
+        unsafe {
+            ACTIVE = true;
+        }
+        self.bind_target(Some(target.target()));
+        self.set_viewport(target.rect());
+        self.set_scissor(target.rect());
+    }
+
+    /// Scale what has been drawn at the resolution set with [`PspGfx::set_low_res`] up to the screen,
+    /// with linear filtering
+    ///
+    /// Everything drawn afterwards, e.g. the HUD, is drawn at full resolution. The viewport and scissor
+    /// are reset to the whole screen. Frames are resolved automatically when they're finished,
+    /// and this does nothing when drawing at full resolution or if the frame was already resolved
+    pub fn resolve_low_res(&self) {
+        let Some(target) = (unsafe { TARGET.as_ref() }) else {
+            return;
+        };
+        if !unsafe { core::mem::take(&mut ACTIVE) } {
+            return;
+        }
+        self.bind_target(None);
+        self.set_viewport(FULL_SCREEN);
+        self.set_scissor(FULL_SCREEN);
+        let state = PostProcessState::COPY.with_filter(TextureFilter::Linear);
+        self.post_process(target, &state);
+    }
+}
@@ -0,0 +1,212 @@
+use alloc::vec::Vec;
+use psp::sys::{self, LightComponent};
+
+use crate::{Frame, color::Color32};
+
+/// Selection of the ambient, diffuse and specular material components
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaterialComponents {
+    pub ambient: bool,
+    pub diffuse: bool,
+    pub specular: bool,
+}
+
+impl MaterialComponents {
+    pub const NONE: Self = Self::new(false, false, false);
+    pub const AMBIENT_AND_DIFFUSE: Self = Self::new(true, true, false);
+    pub const ALL: Self = Self::new(true, true, true);
+
+    pub const fn new(ambient: bool, diffuse: bool, specular: bool) -> Self {
+        Self {
+            ambient,
+            diffuse,
+            specular,
+        }
+    }
+}
+
+impl From<MaterialComponents> for LightComponent {
+    fn from(components: MaterialComponents) -> Self {
+        let mut flags = LightComponent::empty();
+        flags.set(LightComponent::AMBIENT, components.ambient);
+        flags.set(LightComponent::DIFFUSE, components.diffuse);
+        flags.set(LightComponent::SPECULAR, components.specular);
+        flags
+    }
+}
+
+/// Surface properties used by hardware lighting
+///
+/// The alpha of `ambient` is used as the alpha of lit vertices
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub ambient: Color32,
+    pub diffuse: Color32,
+    pub specular: Color32,
+    /// Color emitted by the surface regardless of lighting
+    pub emissive: Color32,
+    /// Sharpness of the specular highlight, higher values give smaller highlights
+    pub specular_power: f32,
+    /// Components taken from the vertex colors instead of the material, for vertex layouts with a color
+    pub vertex_color: MaterialComponents,
+}
+
+impl Material {
+    /// White, fully diffuse material without specular highlights
+    pub const DEFAULT: Self = Self {
+        ambient: Color32::WHITE,
+        diffuse: Color32::WHITE,
+        specular: Color32::BLACK,
+        emissive: Color32::BLACK,
+        specular_power: 1.,
+        vertex_color: MaterialComponents::NONE,
+    };
+
+    /// Fullbright material, drawn in `color` regardless of the lighting
+    ///
+    /// Used by [`Frame::set_unlit`]
+    pub const fn unlit(color: Color32) -> Self {
+        Self {
+            ambient: color,
+            diffuse: Color32::BLACK,
+            specular: Color32::BLACK,
+            emissive: color,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Create a material with the same ambient and diffuse color
+    pub const fn new(color: Color32) -> Self {
+        Self {
+            ambient: color,
+            diffuse: color,
+            ..Self::DEFAULT
+        }
+    }
+
+    pub const fn with_ambient(self, ambient: Color32) -> Self {
+        Self { ambient, ..self }
+    }
+
+    pub const fn with_diffuse(self, diffuse: Color32) -> Self {
+        Self { diffuse, ..self }
+    }
+
+    /// Set the specular color and power
+    pub const fn with_specular(self, specular: Color32, specular_power: f32) -> Self {
+        Self {
+            specular,
+            specular_power,
+            ..self
+        }
+    }
+
+    pub const fn with_emissive(self, emissive: Color32) -> Self {
+        Self { emissive, ..self }
+    }
+
+    /// Take the specified components from the vertex colors
+    pub const fn with_vertex_color(self, vertex_color: MaterialComponents) -> Self {
+        Self {
+            vertex_color,
+            ..self
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Use the material for the following draw calls
+    pub fn set_material(&self, material: &Material) {
+        unsafe {
+            sys::sceGuModelColor(
+                material.emissive.as_abgr(),
+                material.ambient.as_abgr(),
+                material.diffuse.as_abgr(),
+                material.specular.as_abgr(),
+            );
+            // Also sets the material alpha, which sceGuModelColor doesn't
+            sys::sceGuAmbientColor(material.ambient.as_abgr());
+            sys::sceGuSpecular(material.specular_power);
+            sys::sceGuColorMaterial(material.vertex_color.into());
+        }
+    }
+
+    /// Switch to unlit rendering, for UI and fullbright effects
+    ///
+    /// Disables hardware lighting and uses [`Material::unlit`], so vertices without colors are drawn in `color`
+    /// no matter what the previous 3D pass configured
+    pub fn set_unlit(&self, color: Color32) {
+        self.set_lighting(false);
+        self.set_material(&Material::unlit(color));
+    }
+}
+
+/// Queue of draws that are sorted by texture and material before being submitted
+///
+/// Material state is only uploaded when it changes between consecutive draws,
+/// and `bind_texture` is only called when the texture key changes.
+/// The queue keeps its allocation, so it can be reused every frame
+pub struct MaterialBatch<'a, T> {
+    draws: Vec<(u32, &'a Material, T)>,
+}
+
+impl<'a, T> MaterialBatch<'a, T> {
+    pub const fn new() -> Self {
+        Self { draws: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Queue a draw
+    ///
+    /// - `texture`: user-defined key identifying the texture the draw uses
+    /// - `item`: data passed back to the draw callback, e.g. the mesh and its model matrix
+    pub fn push(&mut self, texture: u32, material: &'a Material, item: T) {
+        self.draws.push((texture, material, item));
+    }
+
+    /// Sort the queued draws and submit them, leaving the queue empty
+    ///
+    /// Draws sharing the same texture and material keep their relative order
+    pub fn submit(
+        &mut self,
+        frame: &Frame,
+        mut bind_texture: impl FnMut(u32),
+        mut draw: impl FnMut(T),
+    ) {
+        self.draws
+            .sort_by_key(|(texture, material, _)| (*texture, *material as *const Material));
+        let mut texture = None;
+        let mut material: Option<&Material> = None;
+        for (draw_texture, draw_material, item) in self.draws.drain(..) {
+            if texture != Some(draw_texture) {
+                bind_texture(draw_texture);
+                texture = Some(draw_texture);
+            }
+            // Grouped by identity like the sort, equal materials at different addresses are set again
+            if !material.is_some_and(|material| core::ptr::eq(material, draw_material)) {
+                frame.set_material(draw_material);
+                material = Some(draw_material);
+            }
+            draw(item);
+        }
+    }
+}
+
+impl<'a, T> Default for MaterialBatch<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,502 @@
+use core::ops::{Add, Mul, Neg, Sub};
+
+use psp::sys::{ScePspFMatrix4, ScePspFVector3, ScePspFVector4};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Self = Self::new(0., 0., 0.);
+    pub const ONE: Self = Self::new(1., 1., 1.);
+    pub const X: Self = Self::new(1., 0., 0.);
+    pub const Y: Self = Self::new(0., 1., 0.);
+    pub const Z: Self = Self::new(0., 0., 1.);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Create a vector with all components set to `v`
+    pub const fn splat(v: f32) -> Self {
+        Self::new(v, v, v)
+    }
+
+    /// Component-wise minimum
+    pub fn min(self, other: Self) -> Self {
+        Self::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    /// Component-wise maximum
+    pub fn max(self, other: Self) -> Self {
+        Self::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        libm::sqrtf(self.dot(self))
+    }
+
+    /// Get the unit vector pointing in the same direction
+    ///
+    /// Zero-length vectors are returned unchanged
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len <= f32::EPSILON {
+            return self;
+        }
+        self * (1. / len)
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Self;
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl From<Vec3> for ScePspFVector3 {
+    fn from(v: Vec3) -> Self {
+        ScePspFVector3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Compute the smallest box containing all of the points
+    ///
+    /// Returns `None` if the iterator is empty
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, point| {
+            Self::new(aabb.min.min(point), aabb.max.max(point))
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half of the size of the box along each axis
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Get all 8 corners of the box
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+}
+
+/// 4x4 column-major matrix, laid out the same way as the GE expects it
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C, align(16))]
+pub struct Mat4 {
+    pub cols: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self::from_cols([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    pub const fn from_cols(cols: [[f32; 4]; 4]) -> Self {
+        Self { cols }
+    }
+
+    pub fn from_translation(v: Vec3) -> Self {
+        Self::from_cols([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [v.x, v.y, v.z, 1.],
+        ])
+    }
+
+    pub fn from_scale(v: Vec3) -> Self {
+        Self::from_cols([
+            [v.x, 0., 0., 0.],
+            [0., v.y, 0., 0.],
+            [0., 0., v.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Rotation around the X axis, `angle` is in radians
+    pub fn from_rotation_x(angle: f32) -> Self {
+        let (s, c) = libm::sincosf(angle);
+        Self::from_cols([
+            [1., 0., 0., 0.],
+            [0., c, s, 0.],
+            [0., -s, c, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Rotation around the Y axis, `angle` is in radians
+    pub fn from_rotation_y(angle: f32) -> Self {
+        let (s, c) = libm::sincosf(angle);
+        Self::from_cols([
+            [c, 0., -s, 0.],
+            [0., 1., 0., 0.],
+            [s, 0., c, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Rotation around the Z axis, `angle` is in radians
+    pub fn from_rotation_z(angle: f32) -> Self {
+        let (s, c) = libm::sincosf(angle);
+        Self::from_cols([
+            [c, s, 0., 0.],
+            [-s, c, 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Perspective projection matrix
+    ///
+    /// - `fov_y`: vertical field of view in radians
+    /// - `aspect`: width / height ratio of the viewport
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let f = 1. / libm::tanf(fov_y * 0.5);
+        let depth = near - far;
+        Self::from_cols([
+            [f / aspect, 0., 0., 0.],
+            [0., f, 0., 0.],
+            [0., 0., (far + near) / depth, -1.],
+            [0., 0., (2. * far * near) / depth, 0.],
+        ])
+    }
+
+    /// Orthographic projection matrix
+    pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let (w, h, d) = (right - left, top - bottom, far - near);
+        Self::from_cols([
+            [2. / w, 0., 0., 0.],
+            [0., 2. / h, 0., 0.],
+            [0., 0., -2. / d, 0.],
+            [
+                -(right + left) / w,
+                -(top + bottom) / h,
+                -(far + near) / d,
+                1.,
+            ],
+        ])
+    }
+
+    /// Perspective projection matrix defined by the near plane rectangle
+    pub fn frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let (w, h, d) = (right - left, top - bottom, far - near);
+        Self::from_cols([
+            [2. * near / w, 0., 0., 0.],
+            [0., 2. * near / h, 0., 0.],
+            [
+                (right + left) / w,
+                (top + bottom) / h,
+                -(far + near) / d,
+                -1.,
+            ],
+            [0., 0., -2. * far * near / d, 0.],
+        ])
+    }
+
+    /// View matrix for a camera at `eye` looking at `target`
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let f = (target - eye).normalize();
+        let s = f.cross(up).normalize();
+        let u = s.cross(f);
+        Self::from_cols([
+            [s.x, u.x, -f.x, 0.],
+            [s.y, u.y, -f.y, 0.],
+            [s.z, u.z, -f.z, 0.],
+            [-s.dot(eye), -u.dot(eye), f.dot(eye), 1.],
+        ])
+    }
+
+    /// Get a row of the matrix
+    pub fn row(&self, i: usize) -> [f32; 4] {
+        self.cols.map(|col| col[i])
+    }
+
+    /// Transform a point (w = 1), without perspective division
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let [x, y, z, _] = self.transform_vec4([p.x, p.y, p.z, 1.]);
+        Vec3::new(x, y, z)
+    }
+
+    /// Transform a direction (w = 0), ignoring the translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let [x, y, z, _] = self.transform_vec4([v.x, v.y, v.z, 0.]);
+        Vec3::new(x, y, z)
+    }
+
+    /// Multiply a homogeneous vector by the matrix
+    #[cfg(not(feature = "vfpu"))]
+    pub fn transform_vec4(&self, v: [f32; 4]) -> [f32; 4] {
+        core::array::from_fn(|row| (0..4).map(|k| self.cols[k][row] * v[k]).sum())
+    }
+
+    /// Multiply a homogeneous vector by the matrix
+    #[cfg(feature = "vfpu")]
+    pub fn transform_vec4(&self, v: [f32; 4]) -> [f32; 4] {
+        // The vector as the first column of an aligned matrix
+        let column = Mat4::from_cols([v, [0.; 4], [0.; 4], [0.; 4]]);
+        vfpu::mat4_mul(self, &column).cols[0]
+    }
+
+    /// Transform a point (w = 1) and apply the perspective division
+    ///
+    /// Returns `None` if the point ends up at or behind the projection plane (w <= 0)
+    pub fn project_point(&self, p: Vec3) -> Option<Vec3> {
+        let [x, y, z, w] = self.transform_vec4([p.x, p.y, p.z, 1.]);
+        (w > f32::EPSILON).then(|| Vec3::new(x / w, y / w, z / w))
+    }
+
+    /// Compute the inverse of the matrix, `None` if it's not invertible
+    pub fn inverse(&self) -> Option<Self> {
+        let m: [f32; 16] = bytemuck::cast(self.cols);
+        let mut inv = [0f32; 16];
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        let inv_det = 1. / det;
+        Some(Self::from_cols(bytemuck::cast(inv.map(|x| x * inv_det))))
+    }
+
+    pub fn transpose(&self) -> Self {
+        let c = &self.cols;
+        Self::from_cols(core::array::from_fn(|i| {
+            [c[0][i], c[1][i], c[2][i], c[3][i]]
+        }))
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    #[cfg(feature = "vfpu")]
+    fn mul(self, rhs: Self) -> Self {
+        vfpu::mat4_mul(&self, &rhs)
+    }
+
+    #[cfg(not(feature = "vfpu"))]
+    fn mul(self, rhs: Self) -> Self {
+        Self::from_cols(core::array::from_fn(|col| {
+            core::array::from_fn(|row| (0..4).map(|k| self.cols[k][row] * rhs.cols[col][k]).sum())
+        }))
+    }
+}
+
+impl From<Mat4> for ScePspFMatrix4 {
+    fn from(m: Mat4) -> Self {
+        let [x, y, z, w] = m.cols.map(|[x, y, z, w]| ScePspFVector4 { x, y, z, w });
+        ScePspFMatrix4 { x, y, z, w }
+    }
+}
+
+impl From<ScePspFMatrix4> for Mat4 {
+    fn from(m: ScePspFMatrix4) -> Self {
+        Self::from_cols([m.x, m.y, m.z, m.w].map(|v| [v.x, v.y, v.z, v.w]))
+    }
+}
+
+#[cfg(feature = "vfpu")]
+mod vfpu {
+    //! VFPU implementations of the hot math operations
+    //!
+    //! Matrix products and everything built on [`Mat4::transform_vec4`] go through here.
+    //! The [`Vec3`](super::Vec3) operations and [`Mat4::inverse`] stay scalar, loading their few
+    //! values into VFPU registers would cost more than it saves
+    //!
+    //! Only the `M000`-`M200` scratch matrices are used, `M300` holds the current GUM matrix
+
+    use super::Mat4;
+
+    pub(super) fn mat4_mul(lhs: &Mat4, rhs: &Mat4) -> Mat4 {
+        let mut out = Mat4::IDENTITY;
+        let lhs = lhs as *const Mat4;
+        let rhs = rhs as *const Mat4;
+        let out_ptr = &mut out as *mut Mat4;
+        unsafe {
+            psp::vfpu_asm!(
+                "lv.q C000,  0({lhs})",
+                "lv.q C010, 16({lhs})",
+                "lv.q C020, 32({lhs})",
+                "lv.q C030, 48({lhs})",
+                "lv.q C100,  0({rhs})",
+                "lv.q C110, 16({rhs})",
+                "lv.q C120, 32({rhs})",
+                "lv.q C130, 48({rhs})",
+                "vmmul.q M200, M000, M100",
+                "sv.q C200,  0({out})",
+                "sv.q C210, 16({out})",
+                "sv.q C220, 32({out})",
+                "sv.q C230, 48({out})",
+                lhs = in(reg) lhs,
+                rhs = in(reg) rhs,
+                out = in(reg) out_ptr,
+                options(nostack),
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,424 @@
+use core::marker::PhantomData;
+
+use psp::{
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+    sys::{self, MatrixMode, ScePspFMatrix4, TextureMapMode, TextureProjectionMapMode},
+};
+
+use crate::{
+    Frame,
+    math::Mat4,
+    state::{CullMode, DepthState, RenderState},
+};
+
+/// Maximum depth of each GUM matrix stack
+pub const MATRIX_STACK_DEPTH: usize = 32;
+
+/// Number of bone matrices supported by the GE for hardware skinning
+pub const MAX_BONES: usize = 8;
+
+static mut STACK_DEPTH: [usize; 4] = [0; 4];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatrixKind {
+    Projection,
+    View,
+    Model,
+    Texture,
+}
+
+impl MatrixKind {
+    pub const ALL: [Self; 4] = [Self::Projection, Self::View, Self::Model, Self::Texture];
+}
+
+impl From<MatrixKind> for MatrixMode {
+    fn from(kind: MatrixKind) -> Self {
+        match kind {
+            MatrixKind::Projection => MatrixMode::Projection,
+            MatrixKind::View => MatrixMode::View,
+            MatrixKind::Model => MatrixMode::Model,
+            MatrixKind::Texture => MatrixMode::Texture,
+        }
+    }
+}
+
+/// Source of texture coordinates
+#[derive(Clone, Copy, Debug)]
+pub enum TextureMapping {
+    /// Use the UVs from the vertices as-is (default)
+    Coords,
+    /// Transform the projection source by the [`MatrixKind::Texture`] matrix
+    ///
+    /// Use [`TextureProjectionMapMode::Uv`] to transform (scroll, rotate, scale) the vertex UVs,
+    /// or [`TextureProjectionMapMode::Position`] for projective texturing
+    Matrix(TextureProjectionMapMode),
+    /// Environment mapping, using the directions of the specified lights as U and V vectors
+    EnvironmentMap { u_light: u32, v_light: u32 },
+}
+
+/// Handle to one of the GUM matrix stacks
+///
+/// Changes are uploaded to the GE lazily, right before the next draw call
+#[derive(Clone, Copy)]
+pub struct MatrixStack<'frame> {
+    kind: MatrixKind,
+    _frame: PhantomData<&'frame ()>,
+}
+
+impl<'frame> MatrixStack<'frame> {
+    pub fn kind(&self) -> MatrixKind {
+        self.kind
+    }
+
+    fn select(&self) {
+        unsafe {
+            sys::sceGumMatrixMode(self.kind.into());
+        }
+    }
+
+    /// Replace the current matrix with the identity matrix
+    pub fn load_identity(&self) {
+        self.select();
+        unsafe {
+            sys::sceGumLoadIdentity();
+        }
+    }
+
+    /// Replace the current matrix
+    pub fn load(&self, matrix: &Mat4) {
+        self.select();
+        unsafe {
+            sys::sceGumLoadMatrix(&(*matrix).into());
+        }
+    }
+
+    /// Replace the current matrix with any matrix convertible into [`Mat4`]
+    ///
+    /// Useful with the `glam` and `vek` features, which take care of the memory layout:
+    /// ```ignore
+    /// frame.matrix(MatrixKind::Model).set_matrix(&glam::Mat4::from_rotation_y(angle));
+    /// ```
+    pub fn set_matrix<M: Copy + Into<Mat4>>(&self, matrix: &M) {
+        self.load(&(*matrix).into());
+    }
+
+    /// Multiply the current matrix by `matrix` (`current = current * matrix`)
+    pub fn mult(&self, matrix: &Mat4) {
+        self.select();
+        unsafe {
+            sys::sceGumMultMatrix(&(*matrix).into());
+        }
+    }
+
+    /// Get the current matrix
+    pub fn get(&self) -> Mat4 {
+        self.select();
+        let mut matrix: ScePspFMatrix4 = Mat4::IDENTITY.into();
+        unsafe {
+            sys::sceGumStoreMatrix(&mut matrix);
+        }
+        matrix.into()
+    }
+
+    /// Get the current depth of the stack
+    pub fn depth(&self) -> usize {
+        unsafe { STACK_DEPTH[self.kind as usize] }
+    }
+
+    /// Save the current matrix, restoring it when the returned guard is dropped
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack is already [`MATRIX_STACK_DEPTH`] levels deep
+    pub fn push(&self) -> MatrixPushGuard<'frame> {
+        let depth = unsafe { &mut STACK_DEPTH[self.kind as usize] };
+        assert!(*depth + 1 < MATRIX_STACK_DEPTH, "matrix stack overflow");
+        *depth += 1;
+        self.select();
+        unsafe {
+            sys::sceGumPushMatrix();
+        }
+        MatrixPushGuard { stack: *self }
+    }
+}
+
+/// Restores the saved matrix when dropped
+///
+/// Returned by [`MatrixStack::push`]
+#[must_use = "the matrix is restored as soon as the guard is dropped"]
+pub struct MatrixPushGuard<'frame> {
+    stack: MatrixStack<'frame>,
+}
+
+impl<'frame> core::ops::Deref for MatrixPushGuard<'frame> {
+    type Target = MatrixStack<'frame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stack
+    }
+}
+
+impl<'frame> Drop for MatrixPushGuard<'frame> {
+    fn drop(&mut self) {
+        self.stack.select();
+        unsafe {
+            sys::sceGumPopMatrix();
+            STACK_DEPTH[self.stack.kind as usize] -= 1;
+        }
+    }
+}
+
+/// Restores the 3D rendering state when dropped
+///
+/// Returned by [`Frame::begin_2d`]
+#[must_use = "3D state is restored as soon as the guard is dropped"]
+pub struct Mode2dGuard<'frame> {
+    state: RenderState,
+    _projection: MatrixPushGuard<'frame>,
+    _view: MatrixPushGuard<'frame>,
+    _model: MatrixPushGuard<'frame>,
+}
+
+impl<'frame> Drop for Mode2dGuard<'frame> {
+    fn drop(&mut self) {
+        RenderState::restore(&self.state);
+    }
+}
+
+/// Model, view and projection matrices uploaded straight to the GE
+///
+/// Only the matrices that changed since the last [`Frame::upload_mvp`] are sent again,
+/// so drawing many objects under a static camera only costs one model matrix upload per object.
+/// Like [`Frame::load_matrix`], this bypasses the GUM matrix stacks
+#[derive(Clone, Copy, Debug)]
+pub struct Mvp {
+    projection: Mat4,
+    view: Mat4,
+    model: Mat4,
+    dirty: [bool; 3],
+}
+
+impl Mvp {
+    /// Create a new set of matrices, all of them are uploaded on the first [`Frame::upload_mvp`]
+    pub const fn new(projection: Mat4, view: Mat4, model: Mat4) -> Self {
+        Self {
+            projection,
+            view,
+            model,
+            dirty: [true; 3],
+        }
+    }
+
+    pub fn projection(&self) -> &Mat4 {
+        &self.projection
+    }
+
+    pub fn view(&self) -> &Mat4 {
+        &self.view
+    }
+
+    pub fn model(&self) -> &Mat4 {
+        &self.model
+    }
+
+    fn set(&mut self, kind: MatrixKind, matrix: Mat4) {
+        let slot = match kind {
+            MatrixKind::Projection => &mut self.projection,
+            MatrixKind::View => &mut self.view,
+            MatrixKind::Model => &mut self.model,
+            MatrixKind::Texture => unreachable!(),
+        };
+        if *slot != matrix {
+            *slot = matrix;
+            self.dirty[kind as usize] = true;
+        }
+    }
+
+    /// Set the projection matrix, marking it dirty if it changed
+    pub fn set_projection(&mut self, matrix: Mat4) {
+        self.set(MatrixKind::Projection, matrix);
+    }
+
+    /// Set the view matrix, marking it dirty if it changed
+    pub fn set_view(&mut self, matrix: Mat4) {
+        self.set(MatrixKind::View, matrix);
+    }
+
+    /// Set the model matrix, marking it dirty if it changed
+    pub fn set_model(&mut self, matrix: Mat4) {
+        self.set(MatrixKind::Model, matrix);
+    }
+
+    /// Check if any matrix has to be uploaded
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.contains(&true)
+    }
+
+    /// Force all matrices to be uploaded again
+    ///
+    /// Required after the GE matrices were changed behind the struct's back,
+    /// e.g. through [`MatrixStack`] or at the start of a new frame
+    pub fn invalidate(&mut self) {
+        self.dirty = [true; 3];
+    }
+}
+
+impl Default for Mvp {
+    fn default() -> Self {
+        Self::new(Mat4::IDENTITY, Mat4::IDENTITY, Mat4::IDENTITY)
+    }
+}
+
+/// Mark every matrix as changed, so all of them are uploaded before the next draw
+///
+/// Used when commands outside of the matrix stacks replaced the matrices on the GE
+pub(crate) fn invalidate_matrices() {
+    for kind in MatrixKind::ALL {
+        let stack = MatrixStack {
+            kind,
+            _frame: PhantomData,
+        };
+        stack.load(&stack.get());
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Upload the matrices of `mvp` that changed since the last call
+    pub fn upload_mvp(&self, mvp: &mut Mvp) {
+        if !mvp.is_dirty() {
+            return;
+        }
+        unsafe {
+            sys::sceGumUpdateMatrix();
+        }
+        let matrices = [mvp.projection, mvp.view, mvp.model];
+        for (kind, matrix) in MatrixKind::ALL.into_iter().zip(matrices) {
+            if core::mem::take(&mut mvp.dirty[kind as usize]) {
+                unsafe {
+                    sys::sceGuSetMatrix(kind.into(), &matrix.into());
+                }
+            }
+        }
+    }
+
+    /// Get a handle to the matrix stack of the specified kind
+    pub fn matrix(&self, kind: MatrixKind) -> MatrixStack<'_> {
+        MatrixStack {
+            kind,
+            _frame: PhantomData,
+        }
+    }
+
+    /// Switch to pixel-space 2D rendering for 3D-transformed vertices
+    ///
+    /// Loads an orthographic projection mapping vertex coordinates to screen pixels (origin at the top left),
+    /// identity view and model matrices, and disables depth testing and face culling.
+    /// Everything is restored when the returned guard is dropped
+    pub fn begin_2d(&self) -> Mode2dGuard<'_> {
+        let state = self.render_state();
+        let guard = Mode2dGuard {
+            state,
+            _projection: self.matrix(MatrixKind::Projection).push(),
+            _view: self.matrix(MatrixKind::View).push(),
+            _model: self.matrix(MatrixKind::Model).push(),
+        };
+        self.matrix(MatrixKind::Projection).load(&Mat4::ortho(
+            0.,
+            SCREEN_WIDTH as f32,
+            SCREEN_HEIGHT as f32,
+            0.,
+            -1.,
+            1.,
+        ));
+        self.matrix(MatrixKind::View).load_identity();
+        self.matrix(MatrixKind::Model).load_identity();
+        self.set_depth(DepthState {
+            test: None,
+            ..state.depth
+        });
+        self.set_cull_mode(CullMode::None);
+        guard
+    }
+
+    /// Get a handle to the texture matrix stack
+    ///
+    /// The texture matrix only has an effect with [`TextureMapping::Matrix`]
+    pub fn texture_matrix(&self) -> MatrixStack<'_> {
+        self.matrix(MatrixKind::Texture)
+    }
+
+    /// Set the source of texture coordinates
+    pub fn set_texture_mapping(&self, mapping: TextureMapping) {
+        unsafe {
+            match mapping {
+                TextureMapping::Coords => {
+                    sys::sceGuTexMapMode(TextureMapMode::TextureCoords, 0, 0);
+                }
+                TextureMapping::Matrix(projection) => {
+                    sys::sceGuTexProjMapMode(projection);
+                    sys::sceGuTexMapMode(TextureMapMode::TextureMatrix, 0, 0);
+                }
+                TextureMapping::EnvironmentMap { u_light, v_light } => {
+                    sys::sceGuTexMapMode(TextureMapMode::EnvironmentMap, u_light, v_light);
+                }
+            }
+        }
+    }
+
+    /// Upload a matrix directly to the GE, bypassing the GUM matrix stacks
+    ///
+    /// Pending GUM changes are flushed first so they can't overwrite this matrix on the next draw.
+    /// The GUM stack of the same kind is left untouched, so later changes made through
+    /// [`MatrixStack`] replace this matrix again.
+    pub fn load_matrix(&self, kind: MatrixKind, matrix: &Mat4) {
+        unsafe {
+            sys::sceGumUpdateMatrix();
+            sys::sceGuSetMatrix(kind.into(), &(*matrix).into());
+        }
+    }
+
+    /// Upload the bone matrix palette used for hardware skinning
+    ///
+    /// Bone `i` is weighted by the `i`-th weight of each vertex, see the `weight: WEIGHT_32BITF[N]` form of
+    /// [`define_vertex_layout`](crate::define_vertex_layout).
+    /// Only the affine (4x3) part of the matrices is used
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`MAX_BONES`] matrices are passed
+    pub fn set_bone_matrices(&self, matrices: &[Mat4]) {
+        assert!(
+            matrices.len() <= MAX_BONES,
+            "the GE supports at most {MAX_BONES} bone matrices"
+        );
+        for (index, matrix) in matrices.iter().enumerate() {
+            unsafe {
+                sys::sceGuBoneMatrix(index as u32, &(*matrix).into());
+            }
+        }
+    }
+
+    /// Load a perspective projection matrix
+    ///
+    /// See [`Mat4::perspective`]
+    pub fn set_perspective(&self, fov_y: f32, aspect: f32, near: f32, far: f32) {
+        self.matrix(MatrixKind::Projection)
+            .load(&Mat4::perspective(fov_y, aspect, near, far));
+    }
+
+    /// Load an orthographic projection matrix
+    ///
+    /// See [`Mat4::ortho`]
+    pub fn set_ortho(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.matrix(MatrixKind::Projection)
+            .load(&Mat4::ortho(left, right, bottom, top, near, far));
+    }
+
+    /// Load a perspective projection matrix defined by the near plane rectangle
+    ///
+    /// See [`Mat4::frustum`]
+    pub fn set_frustum(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.matrix(MatrixKind::Projection)
+            .load(&Mat4::frustum(left, right, bottom, top, near, far));
+    }
+}
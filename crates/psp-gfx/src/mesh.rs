@@ -0,0 +1,133 @@
+use alloc::vec::Vec;
+use psp::sys::{self, GuPrimitive};
+
+use crate::{
+    Frame, bake,
+    buffer::{Buffer, OwnedBuffer},
+    index::{self, IndexItem},
+    light::Lighting,
+    math::{Aabb, Mat4},
+    vertex::Vertex,
+};
+
+crate::define_vertex_layout! {
+    BoundsVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_3D,
+    }
+}
+
+/// Geometry stored in RAM that can be drawn over multiple frames
+pub struct Mesh<V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy = u16> {
+    primitive: GuPrimitive,
+    vertices: OwnedBuffer<V>,
+    indices: Option<OwnedBuffer<I>>,
+    bounds: Option<(Aabb, OwnedBuffer<BoundsVertex>)>,
+}
+
+impl<V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy> Mesh<V, I> {
+    pub fn new(primitive: GuPrimitive, vertices: Vec<V>) -> Self {
+        Self {
+            primitive,
+            vertices: OwnedBuffer::new(vertices),
+            indices: None,
+            bounds: None,
+        }
+    }
+
+    pub fn new_indexed(primitive: GuPrimitive, vertices: Vec<V>, indices: Vec<I>) -> Self {
+        Self {
+            indices: Some(OwnedBuffer::new(indices)),
+            ..Self::new(primitive, vertices)
+        }
+    }
+
+    pub fn primitive(&self) -> GuPrimitive {
+        self.primitive
+    }
+
+    pub fn vertices(&self) -> &OwnedBuffer<V> {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> Option<&OwnedBuffer<I>> {
+        self.indices.as_ref()
+    }
+
+    /// Get the bounding box used for the GE object test, if any
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds.as_ref().map(|(aabb, _)| *aabb)
+    }
+
+    /// Set the bounding box used for the GE object test
+    ///
+    /// Meshes with bounds are skipped by the GE if none of the box corners pass the transform test.
+    /// Pass `None` to always draw the mesh
+    pub fn set_bounds(&mut self, bounds: Option<Aabb>) {
+        self.bounds = bounds.map(|aabb| {
+            let corners = aabb
+                .corners()
+                .map(|corner| BoundsVertex::from_position(corner.x, corner.y, corner.z));
+            (aabb, OwnedBuffer::new(Vec::from(corners)))
+        });
+    }
+
+    /// Compute the bounding box from the vertex positions and use it for the GE object test
+    pub fn compute_bounds(&mut self) {
+        let aabb = Aabb::from_points(self.vertices.as_slice().iter().map(Vertex::position));
+        self.set_bounds(aabb);
+    }
+
+    /// Bake the lighting into the vertex colors, see [`bake::bake_vertex_colors`]
+    pub fn bake_lighting(&mut self, model: &Mat4, lighting: &Lighting) {
+        self.vertices
+            .modify(|vertices| bake::bake_vertex_colors(vertices, model, lighting));
+    }
+}
+
+impl<V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy + Into<usize>> Mesh<V, I> {
+    /// Reorder the triangles for better vertex cache locality
+    ///
+    /// Only has an effect on indexed [`GuPrimitive::Triangles`] meshes, see [`index::optimize_vertex_cache`]
+    pub fn optimize_vertex_cache(&mut self) {
+        if !matches!(self.primitive, GuPrimitive::Triangles) {
+            return;
+        }
+        let vertex_count = self.vertices.len();
+        if let Some(indices) = &mut self.indices {
+            indices.modify(|indices| index::optimize_vertex_cache(indices, vertex_count));
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw a [`Mesh`]
+    ///
+    /// If the mesh has bounds set, the GE skips it entirely when its bounding box is off-screen
+    pub fn draw_mesh<V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy + Default>(
+        &self,
+        mesh: &Mesh<V, I>,
+    ) {
+        if let Some((_, bounds)) = &mesh.bounds {
+            unsafe {
+                // The bounds are tested with the current matrices, upload pending changes first
+                sys::sceGumUpdateMatrix();
+                sys::sceGuBeginObject(
+                    BoundsVertex::vtype().bits(),
+                    bounds.len() as i32,
+                    core::ptr::null(),
+                    bounds.as_ptr(),
+                );
+            }
+        }
+        match &mesh.indices {
+            Some(indices) => self.draw_array_indexed(mesh.primitive, &mesh.vertices, indices),
+            None => self.draw_array(mesh.primitive, &mesh.vertices),
+        }
+        if mesh.bounds.is_some() {
+            unsafe {
+                sys::sceGuEndObject();
+            }
+        }
+    }
+}
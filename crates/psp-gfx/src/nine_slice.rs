@@ -0,0 +1,116 @@
+use crate::{
+    Frame,
+    color::Color32,
+    rect::Rect,
+    sprite::{Sprite, SpriteBatch},
+    texture::Texture,
+};
+
+/// Texture region split into a 3x3 grid, for scalable UI panels
+///
+/// The corners are drawn at their original size, the edges are stretched along one axis
+/// and the center is stretched to fill the rest of the panel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NineSlice {
+    /// Texture region of the whole panel, in texels
+    pub uv: Rect,
+    /// Border margins, in texels
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl NineSlice {
+    pub const fn new(uv: Rect, left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Self {
+            uv,
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// Create a nine-slice with the same margin on every side
+    pub const fn uniform(uv: Rect, border: i32) -> Self {
+        Self::new(uv, border, border, border, border)
+    }
+
+    /// Queue the panel covering `dst` into a sprite batch
+    ///
+    /// If `dst` is smaller than the margins, the margins are shrunk proportionally
+    pub fn draw<'gfx>(
+        &self,
+        batch: &mut SpriteBatch<'_, 'gfx>,
+        texture: &'gfx Texture,
+        dst: Rect,
+        color: Color32,
+    ) {
+        let fit = |size: i32, start: i32, end: i32| {
+            if start + end <= size || start + end == 0 {
+                (start, end)
+            } else {
+                let start = size * start / (start + end);
+                (start, size - start)
+            }
+        };
+        let (left, right) = fit(dst.w, self.left, self.right);
+        let (top, bottom) = fit(dst.h, self.top, self.bottom);
+        let uv = self.uv;
+        let columns = [
+            (dst.x, left, uv.x, self.left),
+            (
+                dst.x + left,
+                dst.w - left - right,
+                uv.x + self.left,
+                uv.w - self.left - self.right,
+            ),
+            (
+                dst.x + dst.w - right,
+                right,
+                uv.x + uv.w - self.right,
+                self.right,
+            ),
+        ];
+        let rows = [
+            (dst.y, top, uv.y, self.top),
+            (
+                dst.y + top,
+                dst.h - top - bottom,
+                uv.y + self.top,
+                uv.h - self.top - self.bottom,
+            ),
+            (
+                dst.y + dst.h - bottom,
+                bottom,
+                uv.y + uv.h - self.bottom,
+                self.bottom,
+            ),
+        ];
+        for (y, h, v, uv_h) in rows {
+            for (x, w, u, uv_w) in columns {
+                if w <= 0 || h <= 0 || uv_w <= 0 || uv_h <= 0 {
+                    continue;
+                }
+                let sprite = Sprite::new(x as f32, y as f32, Rect::new(u, v, uv_w, uv_h))
+                    .with_size(w as f32, h as f32)
+                    .with_color(color);
+                batch.draw(texture, &sprite);
+            }
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw a nine-slice panel covering `dst`, see [`NineSlice::draw`]
+    pub fn draw_nine_slice(
+        &self,
+        texture: &'gfx Texture,
+        slice: &NineSlice,
+        dst: Rect,
+        color: Color32,
+    ) {
+        slice.draw(&mut self.sprite_batch(), texture, dst, color);
+    }
+}
@@ -0,0 +1,113 @@
+//! Scrolling backgrounds made of layers moving at different speeds
+
+use alloc::vec::Vec;
+use psp::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::{Frame, rect::Rect, texture::Texture, viewport::FULL_SCREEN};
+
+/// Single image of a [`Parallax`] background
+#[derive(Clone, Copy)]
+pub struct ParallaxLayer<'a> {
+    pub texture: &'a Texture,
+    /// Region of the texture that's drawn, in texels
+    pub src: Rect,
+    /// How far the layer moves relative to the camera, `(0, 0)` for a fixed layer and `(1, 1)`
+    /// to move along with the foreground
+    pub factor: (f32, f32),
+    /// Extra scroll applied on top of the camera, e.g. for drifting clouds
+    pub offset: (f32, f32),
+    /// Whether the image repeats along each axis
+    pub repeat: (bool, bool),
+}
+
+impl<'a> ParallaxLayer<'a> {
+    /// Create a layer from a whole texture, repeating horizontally
+    pub fn new(texture: &'a Texture, factor_x: f32, factor_y: f32) -> Self {
+        Self {
+            texture,
+            src: Rect::new(0, 0, texture.width() as i32, texture.height() as i32),
+            factor: (factor_x, factor_y),
+            offset: (0., 0.),
+            repeat: (true, false),
+        }
+    }
+
+    pub const fn with_src(self, src: Rect) -> Self {
+        Self { src, ..self }
+    }
+
+    pub const fn with_offset(self, x: f32, y: f32) -> Self {
+        Self {
+            offset: (x, y),
+            ..self
+        }
+    }
+
+    pub const fn with_repeat(self, x: bool, y: bool) -> Self {
+        Self {
+            repeat: (x, y),
+            ..self
+        }
+    }
+
+    /// Get the screen positions the image is drawn at along one axis
+    fn positions(scroll: f32, size: i32, screen: u32, repeat: bool) -> impl Iterator<Item = i32> {
+        let scroll = libm::floorf(scroll) as i32;
+        let (start, count) = if repeat && size > 0 {
+            let start = -scroll.rem_euclid(size);
+            (start, (screen as i32 - start).div_euclid(size) + 1)
+        } else {
+            (-scroll, 1)
+        };
+        (0..count).map(move |i| start + i * size)
+    }
+
+    /// Draw the layer for the camera position, with pixel-exact blits
+    pub fn draw(&self, frame: &Frame<'a>, camera_x: f32, camera_y: f32) {
+        let scroll_x = camera_x * self.factor.0 + self.offset.0;
+        let scroll_y = camera_y * self.factor.1 + self.offset.1;
+        let (w, h) = (self.src.w, self.src.h);
+        let mut regions = Vec::new();
+        for y in Self::positions(scroll_y, h, SCREEN_HEIGHT, self.repeat.1) {
+            for x in Self::positions(scroll_x, w, SCREEN_WIDTH, self.repeat.0) {
+                let dst = Rect::new(x, y, w, h);
+                if !dst.intersect(&FULL_SCREEN).is_empty() {
+                    regions.push((self.src, dst));
+                }
+            }
+        }
+        frame.blit_many(self.texture, regions);
+    }
+}
+
+/// Background made of [`ParallaxLayer`]s, drawn back to front
+#[derive(Clone, Default)]
+pub struct Parallax<'a> {
+    layers: Vec<ParallaxLayer<'a>>,
+}
+
+impl<'a> Parallax<'a> {
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Add a layer in front of the existing ones
+    pub fn push(&mut self, layer: ParallaxLayer<'a>) {
+        self.layers.push(layer);
+    }
+
+    pub fn layers(&self) -> &[ParallaxLayer<'a>] {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [ParallaxLayer<'a>] {
+        &mut self.layers
+    }
+
+    /// Draw all layers for the camera position, in pixels
+    pub fn draw(&self, frame: &Frame<'a>, camera_x: f32, camera_y: f32) {
+        for layer in &self.layers {
+            layer.draw(frame, camera_x, camera_y);
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! Bezier and spline patches, tessellated by the GE
+
+use alloc::{vec, vec::Vec};
+use psp::sys::{self, GuPrimitive};
+
+use crate::{
+    Frame,
+    buffer::Buffer,
+    debug_validate_vertex_type,
+    math::{Aabb, Vec3},
+    matrix::MatrixKind,
+    mesh::Mesh,
+    state,
+    vertex::Vertex,
+};
+
+/// Patch subdivision set up by [`PspGfx::init`](crate::PspGfx::init)
+pub const DEFAULT_DIVIDE: (u32, u32) = (16, 16);
+
+/// Primitive the GE builds tessellated patches from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatchPrimitive {
+    /// Filled surfaces, the primitive set up by [`PspGfx::init`](crate::PspGfx::init)
+    #[default]
+    Triangles,
+    /// Wireframe made of the edges of the tessellated grid
+    Lines,
+    /// Only the vertices of the tessellated grid
+    Points,
+}
+
+impl From<PatchPrimitive> for sys::PatchPrimitive {
+    fn from(primitive: PatchPrimitive) -> Self {
+        match primitive {
+            PatchPrimitive::Triangles => Self::TriangleStrip,
+            PatchPrimitive::Lines => Self::LineStrip,
+            PatchPrimitive::Points => Self::Points,
+        }
+    }
+}
+
+/// How the ends of a spline along one direction behave
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SplineEnds {
+    /// The curve doesn't pass through the first control point
+    pub open_start: bool,
+    /// The curve doesn't pass through the last control point
+    pub open_end: bool,
+}
+
+impl SplineEnds {
+    /// Curve passing through the first and last control points
+    pub const CLOSED: Self = Self::new(false, false);
+    pub const OPEN: Self = Self::new(true, true);
+
+    pub const fn new(open_start: bool, open_end: bool) -> Self {
+        Self {
+            open_start,
+            open_end,
+        }
+    }
+
+    fn bits(self) -> i32 {
+        self.open_start as i32 | (self.open_end as i32) << 1
+    }
+}
+
+/// Picks the patch subdivision from the size of a patch on screen, see [`Frame::set_patch_lod`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PatchLod {
+    /// Targeted length of a tessellated segment on screen, in pixels
+    pub pixels_per_segment: f32,
+    /// Subdivision used for distant patches
+    pub min_divide: u32,
+    /// Subdivision used for close patches
+    pub max_divide: u32,
+    /// Most vertices a single draw may be tessellated into, the subdivision is lowered to stay below it
+    pub max_vertices: u32,
+}
+
+impl PatchLod {
+    pub const DEFAULT: Self = Self {
+        pixels_per_segment: 12.,
+        min_divide: 1,
+        max_divide: 16,
+        max_vertices: 1024,
+    };
+
+    pub const fn new() -> Self {
+        Self::DEFAULT
+    }
+
+    pub const fn with_pixels_per_segment(self, pixels_per_segment: f32) -> Self {
+        Self {
+            pixels_per_segment,
+            ..self
+        }
+    }
+
+    pub const fn with_divide_range(self, min_divide: u32, max_divide: u32) -> Self {
+        Self {
+            min_divide,
+            max_divide,
+            ..self
+        }
+    }
+
+    pub const fn with_max_vertices(self, max_vertices: u32) -> Self {
+        Self {
+            max_vertices,
+            ..self
+        }
+    }
+
+    /// Get the subdivision along U and V for a grid of `spans` patches that's `screen_size` pixels wide
+    ///
+    /// Bezier grids have `(count - 1) / 3` spans along a direction, splines `count - 3`
+    pub fn divide(&self, screen_size: f32, spans: (usize, usize)) -> (u32, u32) {
+        let (min, max) = (self.min_divide.max(1), self.max_divide.max(1));
+        let per_span = |spans: usize| {
+            let segments = screen_size / (spans.max(1) as f32 * self.pixels_per_segment);
+            (libm::ceilf(segments.min(max as f32)) as u32).clamp(min.min(max), max)
+        };
+        let (mut u, mut v) = (per_span(spans.0), per_span(spans.1));
+        let vertices = |u: u32, v: u32| (spans.0 as u32 * u + 1) * (spans.1 as u32 * v + 1);
+        while vertices(u, v) > self.max_vertices && (u > 1 || v > 1) {
+            if u >= v {
+                u -= 1;
+            } else {
+                v -= 1;
+            }
+        }
+        (u, v)
+    }
+}
+
+impl Default for PatchLod {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Set the primitive the following patches are drawn with, e.g. [`PatchPrimitive::Lines`] to debug tessellation
+    pub fn set_patch_primitive(&self, primitive: PatchPrimitive) {
+        state::upload_patch_primitive(primitive);
+    }
+
+    /// Get the current patch primitive
+    pub fn patch_primitive(&self) -> PatchPrimitive {
+        self.render_state().patch_primitive
+    }
+
+    /// Set how many segments each bezier patch or spline span is divided into, along U and V
+    ///
+    /// More segments give smoother surfaces at the cost of more vertices
+    pub fn set_patch_divide(&self, u: u32, v: u32) {
+        state::upload_patch_divide((u, v));
+    }
+
+    /// Get the current patch subdivision along U and V
+    pub fn patch_divide(&self) -> (u32, u32) {
+        self.render_state().patch_divide
+    }
+
+    /// Get the approximate size on screen of a box drawn with the current matrices, in pixels
+    ///
+    /// The box is treated as its bounding sphere, boxes around or behind the camera are infinitely large.
+    /// Boxes are in model space, pass the control points to [`Aabb::from_points`] for a patch
+    pub fn projected_size(&self, bounds: &Aabb) -> f32 {
+        let model_view = self.matrix(MatrixKind::View).get() * self.matrix(MatrixKind::Model).get();
+        let scale = [Vec3::X, Vec3::Y, Vec3::Z]
+            .map(|axis| model_view.transform_vector(axis).length())
+            .into_iter()
+            .fold(0., f32::max);
+        let radius = bounds.extents().length() * scale;
+        let center = model_view.transform_point(bounds.center());
+        let projection = self.matrix(MatrixKind::Projection).get();
+        let [.., w] = projection.transform_vec4([center.x, center.y, center.z, 1.]);
+        // Orthographic projections keep w at 1, whatever the distance
+        let perspective = projection.row(3) != [0., 0., 0., 1.];
+        if perspective && w <= radius {
+            return f32::INFINITY;
+        }
+        radius * 2. * projection.row(1)[1] / w * self.viewport().h as f32 / 2.
+    }
+
+    /// Set the patch subdivision for a grid of `spans` patches within `bounds`, from its size on screen
+    ///
+    /// Returns the subdivision along U and V, see [`PatchLod::divide`]
+    pub fn set_patch_lod(
+        &self,
+        lod: &PatchLod,
+        bounds: &Aabb,
+        spans: (usize, usize),
+    ) -> (u32, u32) {
+        let (u, v) = lod.divide(self.projected_size(bounds), spans);
+        self.set_patch_divide(u, v);
+        (u, v)
+    }
+
+    /// Flip the normals the GE generates for lit patches
+    ///
+    /// With lighting enabled, the GE derives the normals from the tessellated surface, pointing along
+    /// the cross product of the U and V directions. Flip them if lit patches look inside out because
+    /// their control points are laid out the other way round, or use [`tessellate_bezier`]
+    /// and [`tessellate_spline`] to compute the normals on the CPU instead
+    pub fn set_patch_normals_flipped(&self, flipped: bool) {
+        state::upload_patch_normals_flipped(flipped);
+    }
+
+    /// Check if the normals of lit patches are flipped
+    pub fn patch_normals_flipped(&self) -> bool {
+        self.render_state().patch_normals_flipped
+    }
+
+    /// Draw bezier patches from a `u_count` by `v_count` grid of control points, row by row
+    ///
+    /// Every patch is made of 4x4 control points, neighbouring patches share their edges
+    ///
+    /// # Panics
+    ///
+    /// Panics if the counts aren't `3n + 1`, or if the buffer doesn't hold exactly the grid
+    pub fn draw_bezier<V: Buffer>(&self, u_count: usize, v_count: usize, vertex_buf: &V)
+    where
+        V::Item: Vertex,
+    {
+        assert!(
+            u_count >= 4
+                && v_count >= 4
+                && (u_count - 1).is_multiple_of(3)
+                && (v_count - 1).is_multiple_of(3),
+            "bezier patches need 3n + 1 control points along each direction, got {u_count}x{v_count}"
+        );
+        assert_eq!(
+            vertex_buf.len(),
+            u_count * v_count,
+            "the vertex buffer doesn't match the control point grid"
+        );
+        let vtype = V::Item::vtype();
+        debug_validate_vertex_type(&vtype);
+        crate::reserve_list(crate::DRAW_COMMANDS_SIZE);
+        crate::profiler::count_draw(u_count * v_count);
+        unsafe {
+            sys::sceGumUpdateMatrix();
+            sys::sceGuDrawBezier(
+                vtype,
+                u_count as i32,
+                v_count as i32,
+                core::ptr::null(),
+                vertex_buf.as_ptr(),
+            );
+        }
+    }
+
+    /// Draw a B-spline surface from a `u_count` by `v_count` grid of control points, row by row
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 4 control points along a direction,
+    /// or if the buffer doesn't hold exactly the grid
+    pub fn draw_spline<V: Buffer>(
+        &self,
+        u_count: usize,
+        v_count: usize,
+        u_ends: SplineEnds,
+        v_ends: SplineEnds,
+        vertex_buf: &V,
+    ) where
+        V::Item: Vertex,
+    {
+        assert!(
+            u_count >= 4 && v_count >= 4,
+            "splines need at least 4 control points along each direction, got {u_count}x{v_count}"
+        );
+        assert_eq!(
+            vertex_buf.len(),
+            u_count * v_count,
+            "the vertex buffer doesn't match the control point grid"
+        );
+        let vtype = V::Item::vtype();
+        debug_validate_vertex_type(&vtype);
+        crate::reserve_list(crate::DRAW_COMMANDS_SIZE);
+        crate::profiler::count_draw(u_count * v_count);
+        unsafe {
+            sys::sceGumUpdateMatrix();
+            sys::sceGuDrawSpline(
+                vtype,
+                u_count as i32,
+                v_count as i32,
+                u_ends.bits(),
+                v_ends.bits(),
+                core::ptr::null(),
+                vertex_buf.as_ptr(),
+            );
+        }
+    }
+}
+
+/// Weights of every control point along one direction, and their derivatives, at a position from 0 to 1
+type Basis = (Vec<f32>, Vec<f32>);
+
+fn bezier_basis(count: usize, s: f32) -> Basis {
+    let segments = (count - 1) / 3;
+    let scaled = s.clamp(0., 1.) * segments as f32;
+    let segment = (scaled as usize).min(segments - 1);
+    let (t, u) = (scaled - segment as f32, 1. - (scaled - segment as f32));
+    let (mut weights, mut derivatives) = (vec![0.; count], vec![0.; count]);
+    let start = segment * 3;
+    weights[start..start + 4].copy_from_slice(&[
+        u * u * u,
+        3. * u * u * t,
+        3. * u * t * t,
+        t * t * t,
+    ]);
+    derivatives[start..start + 4].copy_from_slice(&[
+        -3. * u * u,
+        3. * u * u - 6. * u * t,
+        6. * u * t - 3. * t * t,
+        3. * t * t,
+    ]);
+    (weights, derivatives)
+}
+
+/// Cubic B-spline basis, closed ends repeat their knot so the curve passes through the end point
+fn spline_basis(count: usize, ends: SplineEnds, s: f32) -> Basis {
+    let end = (count - 3) as f32;
+    let knots: Vec<f32> = (0..count + 4)
+        .map(|k| {
+            let knot = k as f32 - 3.;
+            match (ends.open_start, ends.open_end) {
+                (false, _) if knot < 0. => 0.,
+                (_, false) if knot > end => end,
+                _ => knot,
+            }
+        })
+        .collect();
+    let x = s.clamp(0., 1.) * end;
+    // Last non-empty knot span starting at or before `x`, so the end of the curve is included
+    let span = (3..count)
+        .rev()
+        .find(|&i| knots[i] <= x && knots[i] < knots[i + 1])
+        .unwrap_or(3);
+    let ratio = |a: f32, b: f32| if b > 0. { a / b } else { 0. };
+    let mut basis = vec![0.; count + 3];
+    basis[span] = 1.;
+    let mut lower = basis.clone();
+    for degree in 1..=3 {
+        lower.clone_from(&basis);
+        for i in 0..count + 3 - degree {
+            basis[i] = ratio(x - knots[i], knots[i + degree] - knots[i]) * lower[i]
+                + ratio(
+                    knots[i + degree + 1] - x,
+                    knots[i + degree + 1] - knots[i + 1],
+                ) * lower[i + 1];
+        }
+    }
+    let derivatives = (0..count)
+        .map(|i| {
+            3. * (ratio(lower[i], knots[i + 3] - knots[i])
+                - ratio(lower[i + 1], knots[i + 4] - knots[i + 1]))
+        })
+        .collect();
+    basis.truncate(count);
+    (basis, derivatives)
+}
+
+/// Evaluate a surface at every grid point of the tessellation, with normals along `∂u × ∂v`
+fn tessellate<V: Vertex + Clone + Copy>(
+    u_count: usize,
+    points: &[Vec3],
+    divide: (u32, u32),
+    u_basis: impl Fn(f32) -> Basis,
+    v_basis: impl Fn(f32) -> Basis,
+) -> Mesh<V> {
+    let (columns, rows) = (divide.0.max(1) as usize, divide.1.max(1) as usize);
+    assert!(
+        (columns + 1) * (rows + 1) <= u16::MAX as usize + 1,
+        "a {columns}x{rows} tessellation has too many vertices for 16-bit indices"
+    );
+    let u_bases: Vec<Basis> = (0..=columns)
+        .map(|column| u_basis(column as f32 / columns as f32))
+        .collect();
+    // Position and derivatives along U and V of every sample, row by row
+    let mut samples = Vec::with_capacity((columns + 1) * (rows + 1));
+    for row in 0..=rows {
+        let (v_weights, v_derivatives) = v_basis(row as f32 / rows as f32);
+        for (u_weights, u_derivatives) in &u_bases {
+            let (mut position, mut du, mut dv) = (Vec3::ZERO, Vec3::ZERO, Vec3::ZERO);
+            for (j, (&wv, &dwv)) in v_weights.iter().zip(&v_derivatives).enumerate() {
+                for (i, (&wu, &dwu)) in u_weights.iter().zip(u_derivatives).enumerate() {
+                    if (wv == 0. && dwv == 0.) || (wu == 0. && dwu == 0.) {
+                        continue;
+                    }
+                    let point = points[j * u_count + i];
+                    position = position + point * (wu * wv);
+                    du = du + point * (dwu * wv);
+                    dv = dv + point * (wu * dwv);
+                }
+            }
+            samples.push((position, du, dv));
+        }
+    }
+    let sample = |column: usize, row: usize| samples[row * (columns + 1) + column];
+    let neighbour = |index: usize, last: usize| if index < last { index + 1 } else { index - 1 };
+    let collapsed = |derivative: Vec3, other: Vec3| derivative.length() <= other.length() * 1e-4;
+    let mut vertices = Vec::with_capacity(samples.len());
+    let mut previous = Vec3::ZERO;
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let (position, mut du, mut dv) = sample(column, row);
+            // Collapsed edges (e.g. the poles of a sphere) have no derivative along them,
+            // take it from the next sample across the edge instead
+            if collapsed(du, dv) {
+                du = sample(column, neighbour(row, rows)).1;
+            } else if collapsed(dv, du) {
+                dv = sample(neighbour(column, columns), row).2;
+            }
+            let normal = du.cross(dv).normalize();
+            // Still degenerate, e.g. at a cusp, reuse the last normal
+            let normal = if normal.length() > 0.5 {
+                normal
+            } else {
+                previous
+            };
+            previous = normal;
+            let (s, t) = (column as f32 / columns as f32, row as f32 / rows as f32);
+            vertices.push(V::from_attributes(position, normal, [s, t]));
+        }
+    }
+    let stride = columns as u16 + 1;
+    let mut indices = Vec::with_capacity(columns * rows * 6);
+    for row in 0..rows as u16 {
+        for column in 0..columns as u16 {
+            let a = row * stride + column;
+            let (b, c, d) = (a + 1, a + stride + 1, a + stride);
+            indices.extend([a, b, c, a, c, d]);
+        }
+    }
+    let mut mesh = Mesh::new_indexed(GuPrimitive::Triangles, vertices, indices);
+    mesh.compute_bounds();
+    mesh
+}
+
+/// Tessellate bezier patches on the CPU into a mesh with normals, see [`Frame::draw_bezier`]
+///
+/// The whole grid is divided into `divide` quads along U and V. Normals point along the cross product
+/// of the U and V directions, and texture coordinates go from 0 to 1 over the whole grid
+///
+/// # Panics
+///
+/// Panics if the counts aren't `3n + 1`, or if `points` doesn't hold exactly the grid
+pub fn tessellate_bezier<V: Vertex + Clone + Copy>(
+    u_count: usize,
+    v_count: usize,
+    points: &[Vec3],
+    divide: (u32, u32),
+) -> Mesh<V> {
+    assert!(
+        u_count >= 4
+            && v_count >= 4
+            && (u_count - 1).is_multiple_of(3)
+            && (v_count - 1).is_multiple_of(3),
+        "bezier patches need 3n + 1 control points along each direction, got {u_count}x{v_count}"
+    );
+    assert_eq!(
+        points.len(),
+        u_count * v_count,
+        "the points don't match the control point grid"
+    );
+    tessellate(
+        u_count,
+        points,
+        divide,
+        |s| bezier_basis(u_count, s),
+        |t| bezier_basis(v_count, t),
+    )
+}
+
+/// Tessellate a B-spline surface on the CPU into a mesh with normals, see [`Frame::draw_spline`]
+///
+/// The whole grid is divided into `divide` quads along U and V. Normals point along the cross product
+/// of the U and V directions, and texture coordinates go from 0 to 1 over the whole grid
+///
+/// # Panics
+///
+/// Panics if there are fewer than 4 control points along a direction,
+/// or if `points` doesn't hold exactly the grid
+pub fn tessellate_spline<V: Vertex + Clone + Copy>(
+    u_count: usize,
+    v_count: usize,
+    u_ends: SplineEnds,
+    v_ends: SplineEnds,
+    points: &[Vec3],
+    divide: (u32, u32),
+) -> Mesh<V> {
+    assert!(
+        u_count >= 4 && v_count >= 4,
+        "splines need at least 4 control points along each direction, got {u_count}x{v_count}"
+    );
+    assert_eq!(
+        points.len(),
+        u_count * v_count,
+        "the points don't match the control point grid"
+    );
+    tessellate(
+        u_count,
+        points,
+        divide,
+        |s| spline_basis(u_count, u_ends, s),
+        |t| spline_basis(v_count, v_ends, t),
+    )
+}
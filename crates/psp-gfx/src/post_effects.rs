@@ -0,0 +1,92 @@
+//! Ready-made retro post effects, e.g. for emulator frontends
+//!
+//! Effects are combined with [`Frame::apply_post_effects`], which draws a render target holding
+//! the scene to the screen
+
+use alloc::vec::Vec;
+use psp::sys::{GuPrimitive, TextureColorComponent, TextureEffect};
+
+use crate::{
+    Frame,
+    color::Color32,
+    post_process::PostProcessState,
+    render_target::{self, RenderTarget},
+    state::{BlendMode, RenderState},
+};
+
+crate::define_vertex_layout! {
+    LineVertex {
+        vertex: VERTEX_16BIT,
+        transform: TRANSFORM_2D,
+        color: COLOR_8888,
+    }
+}
+
+/// Step of [`Frame::apply_post_effects`]
+#[derive(Clone, Copy)]
+pub enum PostEffect<'a> {
+    /// Scale the image down into a smaller render target, giving blocky pixels once scaled back up
+    Pixelate(&'a RenderTarget),
+    /// Multiply the image by a color
+    Tint(Color32),
+    /// Darken every `spacing`-th row by `darkness` (from 0 to 255), like the lines of a CRT
+    Scanlines { darkness: u8, spacing: u32 },
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Darken every `spacing`-th row of the screen (or the render target being drawn into)
+    pub fn scanlines(&self, darkness: u8, spacing: u32) {
+        let bounds = render_target::bounds();
+        let spacing = spacing.max(1) as usize;
+        let color = Color32::from_rgba(darkness as u32);
+        let mut vertices = Vec::new();
+        for y in (bounds.y..bounds.y + bounds.h).step_by(spacing) {
+            vertices.extend([
+                LineVertex::from_position2_color(bounds.x as u16, y as u16, color),
+                LineVertex::from_position2_color((bounds.x + bounds.w) as u16, y as u16 + 1, color),
+            ]);
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        let saved = self.render_state();
+        let pass = RenderState {
+            blend: Some(BlendMode::ALPHA),
+            ..RenderState::DEFAULT
+        };
+        RenderState::diff_apply(&saved, &pass);
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+        RenderState::restore(&saved);
+    }
+
+    /// Draw `scene` to the screen (or the render target being drawn into) through a chain of effects
+    ///
+    /// [`PostEffect::Pixelate`] steps are applied in order, each scaling the result of the previous one.
+    /// The result is then drawn with the last [`PostEffect::Tint`], and the scanlines are drawn over it
+    pub fn apply_post_effects(&self, scene: &'gfx RenderTarget, effects: &[PostEffect<'gfx>]) {
+        let mut source = scene;
+        let mut state = PostProcessState::COPY;
+        for effect in effects {
+            match *effect {
+                PostEffect::Pixelate(target) => {
+                    self.render_to(target, |frame| {
+                        frame.post_process(source, &PostProcessState::COPY);
+                    });
+                    source = target;
+                }
+                PostEffect::Tint(color) => {
+                    state = PostProcessState::COPY
+                        .with_effect(TextureEffect::Modulate, TextureColorComponent::Rgb)
+                        .with_color(color);
+                }
+                PostEffect::Scanlines { .. } => {}
+            }
+        }
+        self.post_process(source, &state);
+        for effect in effects {
+            if let PostEffect::Scanlines { darkness, spacing } = *effect {
+                self.scanlines(darkness, spacing);
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Full-screen passes drawing a render target over the screen or another render target
+
+use alloc::vec::Vec;
+use psp::sys::{self, GuPrimitive, TextureColorComponent, TextureEffect};
+
+use crate::{
+    Frame,
+    blit::push_strips,
+    color::Color32,
+    rect::Rect,
+    render_target::{self, RenderTarget},
+    state::{BlendMode, RenderState, TextureFunction},
+    texture::TextureFilter,
+};
+
+/// How a [`Frame::post_process`] pass combines a render target with what's already drawn
+#[derive(Clone, Copy, Debug)]
+pub struct PostProcessState {
+    /// Blending with the destination, `None` to overwrite it
+    pub blend: Option<BlendMode>,
+    pub effect: TextureEffect,
+    pub component: TextureColorComponent,
+    /// Color the texture is combined with by `effect`
+    pub color: Color32,
+    /// Color mixed in by [`TextureEffect::Blend`]
+    pub env_color: Color32,
+    pub filter: TextureFilter,
+}
+
+impl PostProcessState {
+    /// Copy the render target as is
+    pub const COPY: Self = Self {
+        blend: None,
+        effect: TextureEffect::Replace,
+        component: TextureColorComponent::Rgb,
+        color: Color32::WHITE,
+        env_color: Color32::BLACK,
+        filter: TextureFilter::Nearest,
+    };
+
+    /// Add the render target to the destination, scaled by the alpha of `color`
+    pub const ADDITIVE: Self = Self {
+        blend: Some(BlendMode::ADDITIVE),
+        effect: TextureEffect::Modulate,
+        component: TextureColorComponent::Rgb,
+        ..Self::COPY
+    };
+
+    pub const fn with_blend(self, blend: Option<BlendMode>) -> Self {
+        Self { blend, ..self }
+    }
+
+    pub const fn with_effect(
+        self,
+        effect: TextureEffect,
+        component: TextureColorComponent,
+    ) -> Self {
+        Self {
+            effect,
+            component,
+            ..self
+        }
+    }
+
+    pub const fn with_color(self, color: Color32) -> Self {
+        Self { color, ..self }
+    }
+
+    pub const fn with_env_color(self, env_color: Color32) -> Self {
+        Self { env_color, ..self }
+    }
+
+    pub const fn with_filter(self, filter: TextureFilter) -> Self {
+        Self { filter, ..self }
+    }
+}
+
+impl Default for PostProcessState {
+    fn default() -> Self {
+        Self::COPY
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw a render target stretched over the whole screen, or the whole render target
+    /// being drawn into with [`Frame::render_to`]
+    ///
+    /// The pass ignores the scissor, depth and the rest of the render state,
+    /// which is restored afterwards along with the texture filter.
+    /// See [`Frame::blit`] for how the quad is split and inset
+    pub fn post_process(&self, target: &'gfx RenderTarget, state: &PostProcessState) {
+        self.post_process_region(target, target.rect(), state);
+    }
+
+    /// Variant of [`Frame::post_process`] stretching the `src` region of the render target (in texels)
+    pub fn post_process_region(
+        &self,
+        target: &'gfx RenderTarget,
+        src: Rect,
+        state: &PostProcessState,
+    ) {
+        let dst = render_target::bounds();
+        let mut vertices = Vec::new();
+        push_strips(&mut vertices, src, dst);
+        let saved = self.render_state();
+        let scissor = self.scissor();
+        // The wrap mode is left to the caller, e.g. blurs clamp the offset copies
+        let pass = RenderState {
+            blend: state.blend,
+            texturing: true,
+            texture_function: TextureFunction::new(state.effect, state.component),
+            texture_filter: state.filter,
+            texture_wrap: saved.texture_wrap,
+            ..RenderState::DEFAULT
+        };
+        RenderState::diff_apply(&saved, &pass);
+        self.set_scissor(dst);
+        self.bind_texture(target.texture());
+        self.set_color(state.color);
+        unsafe {
+            sys::sceGuTexEnvColor(state.env_color.as_abgr());
+        }
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+        RenderState::restore(&saved);
+        self.set_scissor(scissor);
+    }
+}
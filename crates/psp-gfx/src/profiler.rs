@@ -0,0 +1,204 @@
+//! Frame timing, showing whether the CPU or the GE is holding the frame rate back
+
+use alloc::{format, vec::Vec};
+use psp::sys::{self, GuPrimitive};
+
+use crate::{Frame, PspGfx, color::Color32, debug_text::DEBUG_FONT_SIZE, state::RenderState};
+
+/// Time between two vblanks, in microseconds
+pub const FRAME_BUDGET: u32 = 16_683;
+
+/// Width of a bar of [`Frame::draw_frame_stats`] for a full frame budget, in pixels
+const BAR_BUDGET_WIDTH: i32 = 64;
+
+/// Time the GE finished the last frame, written from the finish interrupt
+static mut GE_FINISHED: u32 = 0;
+static mut DRAW_CALLS: u32 = 0;
+static mut VERTICES: u32 = 0;
+
+crate::define_vertex_layout! {
+    pub(crate) BarVertex {
+        vertex: VERTEX_16BIT,
+        transform: TRANSFORM_2D,
+        color: COLOR_8888,
+    }
+}
+
+/// Append a sprite filling the rectangle from (`x0`, `y0`) to (`x1`, `y1`), in pixels
+pub(crate) fn push_quad(
+    vertices: &mut Vec<BarVertex>,
+    (x0, y0): (i32, i32),
+    (x1, y1): (i32, i32),
+    color: Color32,
+) {
+    vertices.extend([
+        BarVertex::from_position2_color(x0 as u16, y0 as u16, color),
+        BarVertex::from_position2_color(x1 as u16, y1 as u16, color),
+    ]);
+}
+
+fn now() -> u32 {
+    unsafe { sys::sceKernelGetSystemTimeLow() }
+}
+
+/// Record the time the GE finished a frame, called from the finish interrupt
+pub(crate) fn ge_finished() {
+    unsafe {
+        core::ptr::write_volatile(&raw mut GE_FINISHED, now());
+    }
+}
+
+/// Count a draw call of the frame being built
+pub(crate) fn count_draw(vertices: usize) {
+    unsafe {
+        DRAW_CALLS += 1;
+        VERTICES += vertices as u32;
+    }
+}
+
+/// Timing of the last frames, see [`PspGfx::frame_stats`]
+///
+/// Times are in microseconds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Time the CPU spent building the last finished frame
+    pub cpu_time: u32,
+    /// Time the GE spent drawing the frame being displayed
+    pub ge_time: u32,
+    /// Time between the last two displayed frames
+    pub frame_time: u32,
+    /// Vblanks missed between the last two displayed frames, 0 while running at 60 FPS
+    pub vblank_misses: u32,
+    /// Vblanks missed since the statistics were last reset
+    pub total_vblank_misses: u32,
+    /// Draw calls of the last finished frame
+    ///
+    /// Draws recorded into display lists are counted when they're recorded, not when the lists are called
+    pub draw_calls: u32,
+    /// Vertices sent by the draw calls of the last finished frame, control points for patches
+    pub vertices: u32,
+}
+
+impl FrameStats {
+    /// Get the frame rate from the time between the last two displayed frames
+    pub fn fps(&self) -> f32 {
+        if self.frame_time == 0 {
+            return 0.;
+        }
+        1_000_000. / self.frame_time as f32
+    }
+}
+
+/// Timestamps taken at the different stages of a frame
+#[derive(Default)]
+pub(crate) struct Profiler {
+    stats: FrameStats,
+    cpu_start: u32,
+    ge_start: u32,
+    /// Time and vblank count of the last buffer swap
+    last_swap: Option<(u32, u32)>,
+}
+
+impl Profiler {
+    pub(crate) fn frame_started(&mut self) {
+        self.cpu_start = now();
+    }
+
+    pub(crate) fn frame_built(&mut self) {
+        self.stats.cpu_time = now().wrapping_sub(self.cpu_start);
+        unsafe {
+            self.stats.draw_calls = core::mem::take(&mut DRAW_CALLS);
+            self.stats.vertices = core::mem::take(&mut VERTICES);
+        }
+    }
+
+    /// Called once the frame sent last is done on the GE and gets displayed
+    pub(crate) fn frame_swapped(&mut self) {
+        let ge_end = unsafe { core::ptr::read_volatile(&raw const GE_FINISHED) };
+        self.stats.ge_time = ge_end.wrapping_sub(self.ge_start);
+        let (time, vcount) = (now(), unsafe { sys::sceDisplayGetVcount() });
+        if let Some((last_time, last_vcount)) = self.last_swap {
+            self.stats.frame_time = time.wrapping_sub(last_time);
+            self.stats.vblank_misses = vcount.wrapping_sub(last_vcount).saturating_sub(1);
+            self.stats.total_vblank_misses += self.stats.vblank_misses;
+        }
+        self.last_swap = Some((time, vcount));
+    }
+
+    /// Called once a frame is sent to the GE, which is idle at that point and starts on it right away
+    pub(crate) fn list_sent(&mut self) {
+        self.ge_start = now();
+    }
+}
+
+impl PspGfx {
+    /// Get the timing of the last frames
+    ///
+    /// With [pipelining](PspGfx::set_pipelined), the GE time lags one frame behind the CPU time
+    pub fn frame_stats(&self) -> FrameStats {
+        self.profiler.stats
+    }
+
+    /// Forget the missed vblanks counted so far, e.g. after a loading screen
+    pub fn reset_frame_stats(&mut self) {
+        self.profiler.stats.total_vblank_misses = 0;
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw the CPU and GE times of [`PspGfx::frame_stats`] as bars with the top-left corner at the position
+    ///
+    /// The tick on each bar marks a full frame, bars turn red past it.
+    /// The overlay is drawn over everything else, the render state is restored afterwards
+    pub fn draw_frame_stats(&self, x: i32, y: i32) {
+        let stats = self.gfx.profiler.stats;
+        let line_height = DEBUG_FONT_SIZE + 2;
+        let bar_x = x + DEBUG_FONT_SIZE * 13;
+        let mut vertices = Vec::new();
+        for (row, time) in [stats.cpu_time, stats.ge_time].into_iter().enumerate() {
+            let top = y + row as i32 * line_height;
+            let width = (time as u64 * BAR_BUDGET_WIDTH as u64 / FRAME_BUDGET as u64)
+                .min(BAR_BUDGET_WIDTH as u64 * 2) as i32;
+            let color = if time > FRAME_BUDGET {
+                Color32::RED
+            } else {
+                Color32::GREEN
+            };
+            push_quad(
+                &mut vertices,
+                (bar_x, top),
+                (bar_x + BAR_BUDGET_WIDTH * 2, top + DEBUG_FONT_SIZE),
+                Color32::from_rgba(0x00000080),
+            );
+            push_quad(
+                &mut vertices,
+                (bar_x, top),
+                (bar_x + width, top + DEBUG_FONT_SIZE),
+                color,
+            );
+            let tick = bar_x + BAR_BUDGET_WIDTH;
+            push_quad(
+                &mut vertices,
+                (tick, top),
+                (tick + 1, top + DEBUG_FONT_SIZE),
+                Color32::WHITE,
+            );
+        }
+        let saved = self.render_state();
+        RenderState::diff_apply(&saved, &RenderState::OVERLAY);
+        self.draw_array(GuPrimitive::Sprites, &self.get_memory(&vertices));
+        RenderState::restore(&saved);
+
+        let ms = |time: u32| time as f32 / 1000.;
+        let text = format!(
+            "CPU {:5.1} ms\nGE  {:5.1} ms\nFPS {:5.1} ({} missed)",
+            ms(stats.cpu_time),
+            ms(stats.ge_time),
+            stats.fps(),
+            stats.total_vblank_misses,
+        );
+        for (row, line) in text.lines().enumerate() {
+            self.debug_text(x, y + row as i32 * line_height, line);
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use crate::{color::Color32, vertex::SpriteVertex};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -6,22 +9,28 @@ pub struct Rect {
 }
 
 impl Rect {
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+    pub const fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
         Self { x, y, w, h }
     }
 
-    // pub fn to_sprites_vertices(&self) -> [Vertex; 2] {
-    //     [
-    //         Vertex {
-    //             x: self.x as i16,
-    //             y: self.y as i16,
-    //             ..Default::default()
-    //         },
-    //         Vertex {
-    //             x: (self.x + self.w) as i16,
-    //             y: (self.y + self.h) as i16,
-    //             ..Default::default()
-    //         },
-    //     ]
-    // }
+    /// Check if the rectangle has no area
+    pub const fn is_empty(&self) -> bool {
+        self.w <= 0 || self.h <= 0
+    }
+
+    /// Get the overlapping part of two rectangles, empty if they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.w).min(other.x + other.w);
+        let bottom = (self.y + self.h).min(other.y + other.h);
+        Rect::new(x, y, (right - x).max(0), (bottom - y).max(0))
+    }
+
+    /// Build the sprite vertex pair covering this rectangle
+    ///
+    /// See [`SpriteVertex::quad`]
+    pub const fn to_sprite_vertices(&self, uv: Rect, color: Color32) -> [SpriteVertex; 2] {
+        SpriteVertex::quad(*self, uv, color)
+    }
 }
@@ -0,0 +1,162 @@
+//! Planar reflections for water and mirrors: the scene is drawn mirrored into a render target,
+//! which is then projected onto the reflective surface
+
+use psp::sys::{TextureColorComponent, TextureEffect, TextureProjectionMapMode};
+
+use crate::{
+    Frame, PspGfx,
+    color::Color32,
+    frustum::Plane,
+    math::Mat4,
+    matrix::{MatrixKind, TextureMapping},
+    render_target::RenderTarget,
+    state::CullMode,
+    texture::{TextureFilter, TextureWrap},
+};
+
+/// Reflection of the scene in a plane
+///
+/// Every frame, draw the reflected scene with [`PlanarReflection::render`],
+/// then the reflective surface with [`PlanarReflection::draw_surface`]
+pub struct PlanarReflection {
+    target: RenderTarget,
+    plane: Plane,
+}
+
+impl PlanarReflection {
+    /// Allocate a `width` by `height` render target with its own depth buffer for the reflection
+    ///
+    /// The size should have the aspect ratio of the screen, e.g. 256x145.
+    /// Returns `None` if there's not enough VRAM left
+    pub fn new(gfx: &PspGfx, width: u32, height: u32, plane: Plane) -> Option<Self> {
+        Some(Self {
+            target: RenderTarget::new(gfx, width, height, true)?,
+            plane,
+        })
+    }
+
+    pub fn plane(&self) -> &Plane {
+        &self.plane
+    }
+
+    /// Move the reflective surface, e.g. for a water level that rises
+    pub fn set_plane(&mut self, plane: Plane) {
+        self.plane = plane;
+    }
+
+    /// Get the render target holding the reflected scene
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    /// Get the matrix mirroring world space positions across the plane
+    pub fn reflection_matrix(&self) -> Mat4 {
+        let Plane { normal: n, d } = self.plane;
+        Mat4::from_cols([
+            [1. - 2. * n.x * n.x, -2. * n.x * n.y, -2. * n.x * n.z, 0.],
+            [-2. * n.x * n.y, 1. - 2. * n.y * n.y, -2. * n.y * n.z, 0.],
+            [-2. * n.x * n.z, -2. * n.y * n.z, 1. - 2. * n.z * n.z, 0.],
+            [-2. * d * n.x, -2. * d * n.y, -2. * d * n.z, 1.],
+        ])
+    }
+
+    /// Replace the near plane of `projection` with the reflection plane (given in view space),
+    /// so geometry behind the plane is clipped
+    ///
+    /// The GE has no user clip planes, this is the oblique near plane trick instead
+    fn clip_projection(projection: &Mat4, plane: [f32; 4]) -> Mat4 {
+        let Some(inverse) = projection.inverse() else {
+            return *projection;
+        };
+        let corner = inverse.transform_vec4([
+            libm::copysignf(1., plane[0]),
+            libm::copysignf(1., plane[1]),
+            1.,
+            1.,
+        ]);
+        let dot: f32 = (0..4).map(|i| plane[i] * corner[i]).sum();
+        if dot.abs() < f32::EPSILON {
+            return *projection;
+        }
+        let rows: [[f32; 4]; 4] = core::array::from_fn(|i| projection.row(i));
+        let near: [f32; 4] = core::array::from_fn(|i| plane[i] * 2. / dot - rows[3][i]);
+        Mat4::from_cols(core::array::from_fn(|col| {
+            [rows[0][col], rows[1][col], near[col], rows[3][col]]
+        }))
+    }
+
+    /// Draw the scene mirrored across the plane into the render target
+    ///
+    /// The frame's current projection and view matrices are used as the camera. The render target is
+    /// cleared to `clear`, and geometry on the far side of the plane is clipped away.
+    /// `draw` only has to submit the scene, the matrices and cull mode are restored afterwards
+    pub fn render<'gfx>(
+        &'gfx self,
+        frame: &Frame<'gfx>,
+        clear: Color32,
+        draw: impl FnOnce(&Frame<'gfx>),
+    ) {
+        let projection = frame.matrix(MatrixKind::Projection).push();
+        let view = frame.matrix(MatrixKind::View).push();
+        let mirrored = view.get() * self.reflection_matrix();
+        let plane = [
+            self.plane.normal.x,
+            self.plane.normal.y,
+            self.plane.normal.z,
+            self.plane.d,
+        ];
+        let view_plane = match mirrored.inverse() {
+            Some(inverse) => inverse.transpose().transform_vec4(plane),
+            None => plane,
+        };
+        projection.load(&Self::clip_projection(&projection.get(), view_plane));
+        view.load(&mirrored);
+        // Mirroring flips the winding of every triangle
+        let cull_mode = frame.render_state().cull_mode;
+        frame.set_cull_mode(match cull_mode {
+            CullMode::None => CullMode::None,
+            CullMode::Clockwise => CullMode::CounterClockwise,
+            CullMode::CounterClockwise => CullMode::Clockwise,
+        });
+        frame.render_to(&self.target, |frame| {
+            frame.clear_color_depth(clear, 0);
+            draw(frame);
+        });
+        frame.set_cull_mode(cull_mode);
+    }
+
+    /// Get the texture matrix mapping the reflection onto a surface drawn with the `model` matrix,
+    /// seen through the `view_projection` matrix of the camera
+    pub fn texture_matrix(&self, view_projection: &Mat4, model: &Mat4) -> Mat4 {
+        self.target.clip_to_uv() * *view_projection * *model
+    }
+
+    /// Select the model matrix of the next surface drawn in [`PlanarReflection::draw_surface`]
+    ///
+    /// The texture projection works on the vertex positions before the model matrix is applied,
+    /// so it has to follow the model matrix of every surface
+    pub fn set_model(&self, frame: &Frame, model: &Mat4) {
+        let texture_matrix = self.texture_matrix(&frame.view_projection(), model);
+        frame.texture_matrix().load(&texture_matrix);
+    }
+
+    /// Draw the reflective surface with the reflection projected onto it
+    ///
+    /// The reflection is modulated by the vertex or material color, e.g. to tint water or fade
+    /// it out with alpha blending. `draw` has to call [`PlanarReflection::set_model`] before every surface.
+    /// The texture matrix, mapping, filter and wrap modes are restored afterwards
+    pub fn draw_surface<'gfx>(&'gfx self, frame: &Frame<'gfx>, draw: impl FnOnce(&Frame<'gfx>)) {
+        let (filter, wrap) = (frame.texture_filter(), frame.texture_wrap());
+        frame.bind_texture(self.target.texture());
+        frame.set_texture_function(TextureEffect::Modulate, TextureColorComponent::Rgba);
+        frame.set_texture_filter(TextureFilter::Linear);
+        frame.set_texture_wrap(TextureWrap::Clamp, TextureWrap::Clamp);
+        frame.set_texture_mapping(TextureMapping::Matrix(TextureProjectionMapMode::Position));
+        let texture_matrix = frame.texture_matrix().push();
+        draw(frame);
+        drop(texture_matrix);
+        frame.set_texture_mapping(TextureMapping::Coords);
+        frame.set_texture_wrap(wrap.0, wrap.1);
+        frame.set_texture_filter(filter);
+    }
+}
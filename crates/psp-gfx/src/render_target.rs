@@ -0,0 +1,253 @@
+//! Offscreen buffers in VRAM that can be drawn into and then sampled as textures
+
+use core::ffi::c_void;
+use psp::{
+    BUF_WIDTH, SCREEN_HEIGHT,
+    sys::{self, DisplayPixelFormat},
+};
+
+use crate::{
+    Frame, PspGfx,
+    math::Mat4,
+    rect::Rect,
+    texture::{MAX_TEXTURE_SIZE, Texture, TextureFormat},
+    viewport::FULL_SCREEN,
+    vram,
+};
+
+/// Draw and depth buffer the GE is currently drawing into
+#[derive(Clone, Copy)]
+pub(crate) struct Target {
+    color: u32,
+    format: DisplayPixelFormat,
+    /// Own depth buffer, the screen's is used otherwise
+    depth: Option<u32>,
+    buffer_width: u32,
+    bounds: Rect,
+}
+
+/// Render target bound with [`Frame::render_to`], `None` while drawing to the screen
+static mut TARGET: Option<Target> = None;
+
+/// Get the region that can be drawn into, in pixels
+pub(crate) fn bounds() -> Rect {
+    unsafe { TARGET.map_or(FULL_SCREEN, |target| target.bounds) }
+}
+
+/// Buffer in VRAM that can be drawn into with [`Frame::render_to`] and used as a texture afterwards
+///
+/// The VRAM is freed when the render target is dropped, while the GE may still be using it
+/// for the previous frame, see [`PspGfx::sync`]
+pub struct RenderTarget {
+    texture: Texture,
+    format: DisplayPixelFormat,
+    width: u32,
+    height: u32,
+    color: u32,
+    depth: Option<u32>,
+}
+
+impl RenderTarget {
+    /// Allocate a `width` by `height` render target in the 32-bit format of the screen
+    ///
+    /// - `depth`: whether to allocate a depth buffer for the render target. Without one,
+    ///   the screen's depth buffer is shared, see [`RenderTarget::fits_screen_depth`]
+    ///
+    /// The texture is padded to powers of two, with rows at least 64 pixels long.
+    /// Returns `None` if there's not enough VRAM left, see [`PspGfx::vram_usage`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is zero or larger than [`MAX_TEXTURE_SIZE`],
+    /// or if there's no `depth` and the render target doesn't fit the screen's depth buffer
+    pub fn new(gfx: &PspGfx, width: u32, height: u32, depth: bool) -> Option<Self> {
+        Self::with_format(gfx, width, height, depth, DisplayPixelFormat::Psm8888)
+    }
+
+    /// Allocate a render target in a specific pixel format
+    ///
+    /// 16-bit render targets take half the VRAM and are roughly twice as fast to draw into and sample,
+    /// at the cost of precision. Only [`Psm8888`](DisplayPixelFormat::Psm8888) and
+    /// [`Psm4444`](DisplayPixelFormat::Psm4444) keep a useful alpha channel, see
+    /// [`BlendMode::supports_format`](crate::state::BlendMode::supports_format)
+    /// for blending into them.
+    /// See [`RenderTarget::new`] for the other parameters
+    pub fn with_format(
+        _gfx: &PspGfx,
+        width: u32,
+        height: u32,
+        depth: bool,
+        format: DisplayPixelFormat,
+    ) -> Option<Self> {
+        for size in [width, height] {
+            assert!(
+                size > 0 && size <= MAX_TEXTURE_SIZE,
+                "render target sizes have to be between 1 and {MAX_TEXTURE_SIZE}, got {size}"
+            );
+        }
+        let texture_format = match format {
+            DisplayPixelFormat::Psm5650 => TextureFormat::Psm5650,
+            DisplayPixelFormat::Psm5551 => TextureFormat::Psm5551,
+            DisplayPixelFormat::Psm4444 => TextureFormat::Psm4444,
+            DisplayPixelFormat::Psm8888 => TextureFormat::Psm8888,
+        };
+        assert!(
+            depth || Self::fits_screen_depth(width, height),
+            "{width}x{height} render targets don't fit the screen's depth buffer, they need their own"
+        );
+        // Draw buffers need rows of a multiple of 64 pixels, textures need powers of two
+        let buffer_width = Self::buffer_width(width);
+        let color = vram::alloc(buffer_width * height * texture_format.bytes_per_pixel() as u32)?;
+        let depth = match depth {
+            true => match vram::alloc(buffer_width * height * 2) {
+                Some(offset) => Some(offset),
+                None => {
+                    vram::free(color);
+                    return None;
+                }
+            },
+            false => None,
+        };
+        let texture = unsafe {
+            let address = sys::sceGeEdramGetAddr().add(color as usize);
+            Texture::from_vram(
+                buffer_width,
+                height.next_power_of_two(),
+                texture_format,
+                address,
+            )
+        };
+        Some(Self {
+            texture,
+            format,
+            width,
+            height,
+            color,
+            depth,
+        })
+    }
+
+    /// Check whether a render target of this size can share the screen's depth buffer,
+    /// which has rows of `BUF_WIDTH` pixels and is as high as the screen
+    pub const fn fits_screen_depth(width: u32, height: u32) -> bool {
+        Self::buffer_width(width) <= BUF_WIDTH && height <= SCREEN_HEIGHT
+    }
+
+    const fn buffer_width(width: u32) -> u32 {
+        let width = width.next_power_of_two();
+        if width < 64 { 64 } else { width }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> DisplayPixelFormat {
+        self.format
+    }
+
+    pub fn has_depth(&self) -> bool {
+        self.depth.is_some()
+    }
+
+    /// Get the texture reading the render target
+    ///
+    /// Only [`RenderTarget::rect`] holds what was drawn, the rest of the texture is padding.
+    /// Don't bind it while drawing into the render target
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Get the region of the texture that's drawn into, in texels
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    /// Get the matrix mapping clip space coordinates to UVs covering [`RenderTarget::rect`]
+    ///
+    /// Rows 0 and 1 hold the UVs and row 2 the clip space w the GE divides them by, as used by
+    /// the [`MatrixKind::Texture`](crate::matrix::MatrixKind::Texture) matrix with
+    /// [`TextureProjectionMapMode::Position`](sys::TextureProjectionMapMode::Position).
+    /// Multiply it by the view-projection matrix the render target was drawn with
+    pub fn clip_to_uv(&self) -> Mat4 {
+        let scale_u = self.width as f32 / self.texture.width() as f32;
+        let scale_v = self.height as f32 / self.texture.height() as f32;
+        Mat4::from_cols([
+            [0.5 * scale_u, 0., 0., 0.],
+            [0., -0.5 * scale_v, 0., 0.],
+            [0., 0., 0., 0.],
+            [0.5 * scale_u, 0.5 * scale_v, 1., 1.],
+        ])
+    }
+
+    pub(crate) fn target(&self) -> Target {
+        Target {
+            color: self.color,
+            format: self.format,
+            depth: self.depth,
+            buffer_width: self.texture.width(),
+            bounds: self.rect(),
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        vram::free(self.color);
+        if let Some(depth) = self.depth {
+            vram::free(depth);
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    pub(crate) fn bind_target(&self, target: Option<Target>) {
+        unsafe {
+            TARGET = target;
+            match target {
+                Some(target) => {
+                    sys::sceGuDrawBufferList(
+                        target.format,
+                        target.color as *mut c_void,
+                        target.buffer_width as i32,
+                    );
+                    match target.depth {
+                        Some(depth) => {
+                            sys::sceGuDepthBuffer(depth as *mut c_void, target.buffer_width as i32)
+                        }
+                        None => sys::sceGuDepthBuffer(self.gfx.zbp as _, BUF_WIDTH as i32),
+                    }
+                }
+                None => {
+                    sys::sceGuDrawBufferList(
+                        DisplayPixelFormat::Psm8888,
+                        self.gfx.draw_buffer() as _,
+                        BUF_WIDTH as i32,
+                    );
+                    sys::sceGuDepthBuffer(self.gfx.zbp as _, BUF_WIDTH as i32);
+                }
+            }
+        }
+    }
+
+    /// Draw into a render target instead of the screen
+    ///
+    /// The viewport and scissor cover the render target while `draw` runs, and pixel
+    /// coordinates are relative to its top-left corner. The previous draw buffer, viewport
+    /// and scissor are restored afterwards. Render passes can be nested
+    pub fn render_to(&self, target: &'gfx RenderTarget, draw: impl FnOnce(&Self)) {
+        let previous = unsafe { TARGET };
+        let (viewport, scissor) = (self.viewport(), self.scissor());
+        self.bind_target(Some(target.target()));
+        self.set_viewport(target.rect());
+        self.set_scissor(target.rect());
+        draw(self);
+        self.bind_target(previous);
+        self.set_viewport(viewport);
+        self.set_scissor(scissor);
+    }
+}
@@ -0,0 +1,121 @@
+//! Compilation of static scenes into a single display list
+
+use alloc::vec::Vec;
+
+use crate::{
+    Frame,
+    display_list::{DisplayList, ListRecorder},
+    index::IndexItem,
+    material::Material,
+    math::Mat4,
+    matrix::MatrixKind,
+    mesh::Mesh,
+    vertex::Vertex,
+};
+
+struct SceneItem<'a, V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy> {
+    texture: u32,
+    material: &'a Material,
+    mesh: &'a Mesh<V, I>,
+    transform: Mat4,
+}
+
+/// Builder baking meshes, materials and transforms into one [`DisplayList`] at load time
+///
+/// Drawing the whole scene then only costs a single [`Frame::call_list`] per frame.
+/// The projection and view matrices aren't part of the list, so the camera can move freely
+pub struct SceneCompiler<'a, V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy = u16> {
+    items: Vec<SceneItem<'a, V, I>>,
+}
+
+impl<'a, V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy + Default> SceneCompiler<'a, V, I> {
+    pub const fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Add a mesh to the scene
+    ///
+    /// - `texture`: user-defined key identifying the texture the mesh uses, see [`SceneCompiler::compile`]
+    /// - `transform`: model matrix of the mesh
+    pub fn push(
+        &mut self,
+        texture: u32,
+        material: &'a Material,
+        mesh: &'a Mesh<V, I>,
+        transform: Mat4,
+    ) {
+        self.items.push(SceneItem {
+            texture,
+            material,
+            mesh,
+            transform,
+        });
+    }
+
+    /// Record the scene into a display list
+    ///
+    /// - `capacity`: size of the list buffer in bytes
+    /// - `bind_texture`: called whenever the texture key changes, to record the texture setup
+    ///
+    /// Meshes are sorted by texture and material, so each texture and material is only set once.
+    /// Model matrices are only recorded when they differ from the previous mesh,
+    /// meshes sharing the same transform are drawn without any matrix uploads in between
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scene doesn't fit into `capacity`, see [`Frame::record_list`]
+    pub fn compile(
+        mut self,
+        frame: &Frame,
+        capacity: usize,
+        mut bind_texture: impl FnMut(&ListRecorder<'a, '_>, u32),
+    ) -> DisplayList<'a> {
+        self.items.sort_by_key(|item| {
+            (
+                item.texture,
+                item.material as *const Material,
+                item.mesh as *const Mesh<V, I>,
+            )
+        });
+        // The model matrix is changed while recording, keep the frame's one intact
+        let model = frame.matrix(MatrixKind::Model);
+        let _guard = model.push();
+        frame.record_list(capacity, |recorder| {
+            let mut texture = None;
+            let mut material: Option<&Material> = None;
+            let mut transform = None;
+            for item in &self.items {
+                if texture != Some(item.texture) {
+                    bind_texture(recorder, item.texture);
+                    texture = Some(item.texture);
+                }
+                // Grouped by identity like the sort, equal materials at different addresses are set again
+                if !material.is_some_and(|material| core::ptr::eq(material, item.material)) {
+                    recorder.set_material(item.material);
+                    material = Some(item.material);
+                }
+                if transform != Some(item.transform) {
+                    recorder.matrix(MatrixKind::Model).load(&item.transform);
+                    transform = Some(item.transform);
+                }
+                recorder.draw_mesh(item.mesh);
+            }
+        })
+    }
+}
+
+impl<'a, V: Vertex + Clone + Copy, I: IndexItem + Clone + Copy + Default> Default
+    for SceneCompiler<'a, V, I>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
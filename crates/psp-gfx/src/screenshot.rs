@@ -0,0 +1,141 @@
+//! Copies of the displayed frame, optionally encoded as BMP files
+
+#[cfg(feature = "bmp")]
+use alloc::vec::Vec;
+use core::{ffi::c_void, ptr};
+use psp::{
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+    sys::{self, DisplayPixelFormat, DisplaySetBufSync},
+};
+
+use crate::{PspGfx, color::Color32};
+
+/// Offset of the uncached mirror of memory, so VRAM is read as the GE wrote it
+const UNCACHED: usize = 0x4000_0000;
+
+/// Expand a color channel of `bits` bits to 8 bits
+fn expand(value: u32, bits: u32) -> u32 {
+    let value = value & ((1 << bits) - 1);
+    (value << (8 - bits)) | (value >> (2 * bits).saturating_sub(8))
+}
+
+/// Convert a pixel of a display buffer to a color
+fn decode_pixel(format: DisplayPixelFormat, pixel: u32) -> Color32 {
+    let (r, g, b, a) = match format {
+        DisplayPixelFormat::Psm8888 => return Color32::from_abgr(pixel),
+        DisplayPixelFormat::Psm5650 => (
+            expand(pixel, 5),
+            expand(pixel >> 5, 6),
+            expand(pixel >> 11, 5),
+            0xff,
+        ),
+        DisplayPixelFormat::Psm5551 => (
+            expand(pixel, 5),
+            expand(pixel >> 5, 5),
+            expand(pixel >> 10, 5),
+            (pixel >> 15 & 1) * 0xff,
+        ),
+        DisplayPixelFormat::Psm4444 => (
+            expand(pixel, 4),
+            expand(pixel >> 4, 4),
+            expand(pixel >> 8, 4),
+            expand(pixel >> 12, 4),
+        ),
+    };
+    Color32::from_abgr(a << 24 | b << 16 | g << 8 | r)
+}
+
+impl PspGfx {
+    /// Copy the frame currently shown on the display into `pixels`, row by row without padding
+    ///
+    /// The frame is converted from the pixel format of the display buffer. The alpha channel holds
+    /// whatever was written to the buffer, which is usually not meaningful.
+    /// Returns `false` if nothing has been displayed yet
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` holds fewer than `SCREEN_WIDTH * SCREEN_HEIGHT` colors
+    pub fn screenshot(&self, pixels: &mut [Color32]) -> bool {
+        let (width, height) = (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
+        assert!(
+            pixels.len() >= width * height,
+            "screenshots need room for {width}x{height} pixels, got {}",
+            pixels.len()
+        );
+        let mut top_addr: *mut c_void = ptr::null_mut();
+        let mut buffer_width = 0;
+        let mut format = DisplayPixelFormat::Psm8888;
+        unsafe {
+            sys::sceDisplayGetFrameBuf(
+                &mut top_addr,
+                &mut buffer_width,
+                &mut format,
+                DisplaySetBufSync::Immediate,
+            );
+        }
+        if top_addr.is_null() {
+            return false;
+        }
+        let base = (top_addr as usize | UNCACHED) as *const u8;
+        for (y, row) in pixels.chunks_exact_mut(width).take(height).enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                let index = y * buffer_width + x;
+                let pixel = unsafe {
+                    match format {
+                        DisplayPixelFormat::Psm8888 => {
+                            (base as *const u32).add(index).read_volatile()
+                        }
+                        _ => (base as *const u16).add(index).read_volatile() as u32,
+                    }
+                };
+                *color = decode_pixel(format, pixel);
+            }
+        }
+        true
+    }
+}
+
+/// Encode an image as a 24-bit BMP file, e.g. a [`PspGfx::screenshot`] to be saved to the memory stick
+///
+/// # Panics
+///
+/// Panics if the length of `pixels` doesn't match the size
+#[cfg(feature = "bmp")]
+pub fn encode_bmp(width: u32, height: u32, pixels: &[Color32]) -> Vec<u8> {
+    assert_eq!(
+        pixels.len(),
+        (width * height) as usize,
+        "pixel data doesn't match the image size"
+    );
+    const HEADER_SIZE: u32 = 14 + 40;
+    // Rows are padded to multiples of 4 bytes
+    let stride = (width * 3).next_multiple_of(4);
+    let image_size = stride * height;
+    let mut bmp = Vec::with_capacity((HEADER_SIZE + image_size) as usize);
+    // File header
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&(HEADER_SIZE + image_size).to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    // BITMAPINFOHEADER
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&(width as i32).to_le_bytes());
+    bmp.extend_from_slice(&(height as i32).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&24u16.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&image_size.to_le_bytes());
+    // 72 DPI
+    bmp.extend_from_slice(&2835i32.to_le_bytes());
+    bmp.extend_from_slice(&2835i32.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    // Rows are stored bottom to top, pixels as BGR
+    for row in pixels.chunks_exact(width.max(1) as usize).rev() {
+        for color in row {
+            bmp.extend_from_slice(&[color.b(), color.g(), color.r()]);
+        }
+        bmp.resize(bmp.len() + (stride - width * 3) as usize, 0);
+    }
+    bmp
+}
@@ -0,0 +1,192 @@
+//! Projected shadows: shadow casters are drawn into a render target from the light's point of view,
+//! which is then projected onto the receivers with texture projection mapping
+
+use psp::sys::{GuPrimitive, TextureColorComponent, TextureEffect, TextureProjectionMapMode};
+
+use crate::{
+    Frame, PspGfx,
+    color::Color32,
+    math::{Mat4, Vec3},
+    matrix::{MatrixKind, TextureMapping},
+    rect::Rect,
+    render_target::RenderTarget,
+    state::{BlendFactor, BlendMode, BlendOp, CompareFunc, DepthState, RenderState},
+    texture::{TextureFilter, TextureWrap},
+};
+
+crate::define_vertex_layout! {
+    ShadeVertex {
+        vertex: VERTEX_16BIT,
+        transform: TRANSFORM_2D,
+        color: COLOR_8888,
+    }
+}
+
+/// Blacks out the covered pixels, whatever the color of the casters
+const CASTER: BlendMode = BlendMode::new(BlendOp::Add, BlendFactor::ZERO, BlendFactor::ZERO);
+
+/// Shadow of a set of casters cast by a single light, projected onto receiver geometry
+///
+/// Every frame, draw the casters with [`ProjectedShadow::render_casters`] before drawing the scene,
+/// then draw the receivers a second time with [`ProjectedShadow::receive`]
+pub struct ProjectedShadow {
+    target: RenderTarget,
+    view: Mat4,
+    projection: Mat4,
+    /// Color the receivers are multiplied by in the shadow
+    pub color: Color32,
+}
+
+impl ProjectedShadow {
+    /// Allocate a `size` by `size` render target for the shadow
+    ///
+    /// Returns `None` if there's not enough VRAM left
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` isn't a power of two between 64 and [`MAX_TEXTURE_SIZE`](crate::texture::MAX_TEXTURE_SIZE)
+    pub fn new(gfx: &PspGfx, size: u32) -> Option<Self> {
+        assert!(
+            size.is_power_of_two() && size >= 64,
+            "shadow sizes have to be powers of two of at least 64, got {size}"
+        );
+        Some(Self {
+            // Casters are drawn without depth testing, but large targets can't share the screen's depth buffer
+            target: RenderTarget::new(
+                gfx,
+                size,
+                size,
+                !RenderTarget::fits_screen_depth(size, size),
+            )?,
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+            color: Color32::from_rgba(0x808080ff),
+        })
+    }
+
+    /// Get the render target holding the shadow, white where there's no shadow
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    /// Cast the shadow from a directional light at `eye` shining towards `target`,
+    /// covering casters up to `radius` units away from the line between them
+    pub fn set_directional_light(&mut self, eye: Vec3, target: Vec3, radius: f32) {
+        let distance = (target - eye).length();
+        self.set_light(
+            Mat4::look_at(eye, target, Self::up(eye, target)),
+            Mat4::ortho(-radius, radius, -radius, radius, 0., 2. * distance),
+        );
+    }
+
+    /// Cast the shadow from a spot light at `eye` shining towards `target`, with a cone of `fov` radians
+    pub fn set_spot_light(&mut self, eye: Vec3, target: Vec3, fov: f32, near: f32, far: f32) {
+        self.set_light(
+            Mat4::look_at(eye, target, Self::up(eye, target)),
+            Mat4::perspective(fov, 1., near, far),
+        );
+    }
+
+    /// Set the view and projection matrices of the light directly
+    pub fn set_light(&mut self, view: Mat4, projection: Mat4) {
+        self.view = view;
+        self.projection = projection;
+    }
+
+    /// Pick an up vector that isn't parallel to the light direction
+    fn up(eye: Vec3, target: Vec3) -> Vec3 {
+        let direction = (target - eye).normalize();
+        if libm::fabsf(direction.y) > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        }
+    }
+
+    /// Draw the shadow casters from the light's point of view
+    ///
+    /// `draw` only has to submit the geometry with its model matrices, the light's matrices and
+    /// the render state are set up and restored afterwards
+    pub fn render_casters<'gfx>(&'gfx self, frame: &Frame<'gfx>, draw: impl FnOnce(&Frame<'gfx>)) {
+        frame.render_to(&self.target, |frame| {
+            frame.clear_color(Color32::WHITE);
+            // Keep a white border so the clamped edges of the projection are unshadowed
+            let size = self.target.width() as i32;
+            frame.set_scissor(Rect::new(1, 1, size - 2, size - 2));
+            let saved = frame.render_state();
+            let pass = RenderState {
+                blend: Some(CASTER),
+                ..RenderState::DEFAULT
+            };
+            RenderState::diff_apply(&saved, &pass);
+            let projection = frame.matrix(MatrixKind::Projection).push();
+            let view = frame.matrix(MatrixKind::View).push();
+            projection.load(&self.projection);
+            view.load(&self.view);
+            draw(frame);
+            drop((projection, view));
+
+            // Lighten the shadow to its color
+            let shade = RenderState {
+                blend: Some(BlendMode::new(
+                    BlendOp::Max,
+                    BlendFactor::ONE,
+                    BlendFactor::ONE,
+                )),
+                ..RenderState::DEFAULT
+            };
+            RenderState::diff_apply(&frame.render_state(), &shade);
+            let vertices = [
+                ShadeVertex::from_position2_color(0, 0, self.color),
+                ShadeVertex::from_position2_color(size as u16, size as u16, self.color),
+            ];
+            frame.draw_array(GuPrimitive::Sprites, &frame.get_memory(&vertices));
+            RenderState::restore(&saved);
+        });
+    }
+
+    /// Get the texture matrix projecting the shadow onto geometry drawn with the `model` matrix
+    pub fn texture_matrix(&self, model: &Mat4) -> Mat4 {
+        self.target.clip_to_uv() * self.projection * self.view * *model
+    }
+
+    /// Select the model matrix of the next receiver drawn in [`ProjectedShadow::receive`]
+    ///
+    /// The texture projection works on the vertex positions before the model matrix is applied,
+    /// so it has to follow the model matrix of every receiver
+    pub fn set_model(&self, frame: &Frame, model: &Mat4) {
+        frame.texture_matrix().load(&self.texture_matrix(model));
+    }
+
+    /// Darken the receivers where they're in the shadow
+    ///
+    /// `draw` has to draw the receivers exactly like they were drawn before, as only the pixels
+    /// with equal depth are shaded, calling [`ProjectedShadow::set_model`] before each of them.
+    /// The render state, texture matrix and texture mapping are restored afterwards
+    pub fn receive<'gfx>(&'gfx self, frame: &Frame<'gfx>, draw: impl FnOnce(&Frame<'gfx>)) {
+        let saved = frame.render_state();
+        let (filter, wrap) = (frame.texture_filter(), frame.texture_wrap());
+        let pass = RenderState {
+            blend: Some(BlendMode::MULTIPLY),
+            depth: DepthState::new(Some(CompareFunc::Equal), false),
+            texturing: true,
+            lighting: false,
+            fog: None,
+            alpha_test: None,
+            ..saved
+        };
+        RenderState::diff_apply(&saved, &pass);
+        frame.bind_texture(self.target.texture());
+        frame.set_texture_function(TextureEffect::Replace, TextureColorComponent::Rgb);
+        frame.set_texture_filter(TextureFilter::Linear);
+        frame.set_texture_wrap(TextureWrap::Clamp, TextureWrap::Clamp);
+        frame.set_texture_mapping(TextureMapping::Matrix(TextureProjectionMapMode::Position));
+        let texture_matrix = frame.texture_matrix().push();
+        draw(frame);
+        drop(texture_matrix);
+        frame.set_texture_mapping(TextureMapping::Coords);
+        frame.set_texture_wrap(wrap.0, wrap.1);
+        frame.set_texture_filter(filter);
+        RenderState::restore(&saved);
+    }
+}
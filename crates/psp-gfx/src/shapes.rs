@@ -0,0 +1,180 @@
+//! Procedural meshes of basic shapes, for prototypes and examples without external assets
+//!
+//! Meshes are built in any vertex layout through [`Vertex::from_attributes`], centered on the origin,
+//! with outward normals, texture coordinates from 0 to 1, and counter-clockwise front faces
+
+use core::f32::consts::{PI, TAU};
+
+use alloc::vec::Vec;
+use psp::sys::GuPrimitive;
+
+use crate::{math::Vec3, mesh::Mesh, vertex::Vertex};
+
+/// Vertices and triangle indices of a shape being built
+struct Builder<V> {
+    vertices: Vec<V>,
+    indices: Vec<u16>,
+}
+
+impl<V: Vertex + Clone + Copy> Builder<V> {
+    fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u16 {
+        assert!(
+            self.vertices.len() <= u16::MAX as usize,
+            "the shape has too many vertices for 16-bit indices"
+        );
+        self.vertices.push(V::from_attributes(position, normal, uv));
+        (self.vertices.len() - 1) as u16
+    }
+
+    /// Add a `columns` by `rows` grid of quads, `point` maps texture coordinates to a position and normal
+    ///
+    /// Front faces point along `∂t × ∂s`, with `t` being the second texture coordinate
+    fn grid(&mut self, columns: u32, rows: u32, point: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+        let base = self.vertices.len() as u16;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (s, t) = (column as f32 / columns as f32, row as f32 / rows as f32);
+                let (position, normal) = point(s, t);
+                self.push(position, normal, [s, t]);
+            }
+        }
+        let stride = columns as u16 + 1;
+        for row in 0..rows as u16 {
+            for column in 0..columns as u16 {
+                let a = base + row * stride + column;
+                let (b, c, d) = (a + 1, a + stride + 1, a + stride);
+                self.indices.extend([a, d, c, a, c, b]);
+            }
+        }
+    }
+
+    fn build(self) -> Mesh<V> {
+        let mut mesh = Mesh::new_indexed(GuPrimitive::Triangles, self.vertices, self.indices);
+        mesh.compute_bounds();
+        mesh
+    }
+}
+
+/// Build a cube with edges of length `size`, with the whole texture on every face
+pub fn cube<V: Vertex + Clone + Copy>(size: f32) -> Mesh<V> {
+    let half = size / 2.;
+    // Normal, then the axes the texture runs along, with `u × v = normal`
+    let faces = [
+        ([1., 0., 0.], [0., 0., -1.], [0., 1., 0.]),
+        ([-1., 0., 0.], [0., 0., 1.], [0., 1., 0.]),
+        ([0., 1., 0.], [1., 0., 0.], [0., 0., -1.]),
+        ([0., -1., 0.], [1., 0., 0.], [0., 0., 1.]),
+        ([0., 0., 1.], [1., 0., 0.], [0., 1., 0.]),
+        ([0., 0., -1.], [-1., 0., 0.], [0., 1., 0.]),
+    ];
+    let mut builder = Builder::new();
+    for (normal, u, v) in faces {
+        let (normal, u, v) = (Vec3::from(normal), Vec3::from(u), Vec3::from(v));
+        let corner = |du: f32, dv: f32| (normal + u * du + v * dv) * half;
+        let base = builder.push(corner(-1., -1.), normal, [0., 1.]);
+        builder.push(corner(1., -1.), normal, [1., 1.]);
+        builder.push(corner(1., 1.), normal, [1., 0.]);
+        builder.push(corner(-1., 1.), normal, [0., 0.]);
+        builder
+            .indices
+            .extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    builder.build()
+}
+
+/// Build a flat `width` by `depth` rectangle facing +Y, split into `subdivisions` squared quads
+///
+/// The top of the texture is at -Z
+pub fn plane<V: Vertex + Clone + Copy>(width: f32, depth: f32, subdivisions: u32) -> Mesh<V> {
+    let subdivisions = subdivisions.max(1);
+    let mut builder = Builder::new();
+    builder.grid(subdivisions, subdivisions, |s, t| {
+        (
+            Vec3::new((s - 0.5) * width, 0., (t - 0.5) * depth),
+            Vec3::new(0., 1., 0.),
+        )
+    });
+    builder.build()
+}
+
+/// Build a sphere from `segments` slices around the Y axis and `rings` stacks from pole to pole
+///
+/// The texture is wrapped around the sphere with its top at the +Y pole
+pub fn uv_sphere<V: Vertex + Clone + Copy>(radius: f32, segments: u32, rings: u32) -> Mesh<V> {
+    let mut builder = Builder::new();
+    builder.grid(segments.max(3), rings.max(2), |s, t| {
+        let (longitude, latitude) = (s * TAU, t * PI);
+        let normal = Vec3::new(
+            libm::sinf(latitude) * libm::sinf(longitude),
+            libm::cosf(latitude),
+            libm::sinf(latitude) * libm::cosf(longitude),
+        );
+        (normal * radius, normal)
+    });
+    builder.build()
+}
+
+/// Build a capped cylinder along the Y axis from `segments` slices
+///
+/// The texture is wrapped around the side, and mapped onto the caps as a disc inscribed in it
+pub fn cylinder<V: Vertex + Clone + Copy>(radius: f32, height: f32, segments: u32) -> Mesh<V> {
+    let segments = segments.max(3);
+    let half = height / 2.;
+    let mut builder = Builder::new();
+    builder.grid(segments, 1, |s, t| {
+        let angle = s * TAU;
+        let normal = Vec3::new(libm::sinf(angle), 0., libm::cosf(angle));
+        (
+            normal * radius + Vec3::new(0., (0.5 - t) * height, 0.),
+            normal,
+        )
+    });
+    for (y, up) in [(half, 1.), (-half, -1.)] {
+        let normal = Vec3::new(0., up, 0.);
+        let center = builder.push(Vec3::new(0., y, 0.), normal, [0.5, 0.5]);
+        for segment in 0..=segments {
+            let angle = segment as f32 / segments as f32 * TAU;
+            let (sin, cos) = (libm::sinf(angle), libm::cosf(angle));
+            builder.push(
+                Vec3::new(sin * radius, y, cos * radius),
+                normal,
+                [0.5 + sin * 0.5, 0.5 - cos * up * 0.5],
+            );
+        }
+        for segment in 0..segments as u16 {
+            let (a, b) = (center + 1 + segment, center + 2 + segment);
+            if up > 0. {
+                builder.indices.extend([center, a, b]);
+            } else {
+                builder.indices.extend([center, b, a]);
+            }
+        }
+    }
+    builder.build()
+}
+
+/// Build a torus around the Y axis, `radius` being the distance from the center to the middle of the tube
+///
+/// The ring is made of `segments` slices, the tube of `sides` faces
+pub fn torus<V: Vertex + Clone + Copy>(
+    radius: f32,
+    tube_radius: f32,
+    segments: u32,
+    sides: u32,
+) -> Mesh<V> {
+    let mut builder = Builder::new();
+    builder.grid(segments.max(3), sides.max(3), |s, t| {
+        let (ring, tube) = (s * TAU, t * TAU);
+        let outward = Vec3::new(libm::sinf(ring), 0., libm::cosf(ring));
+        let normal = outward * libm::cosf(tube) - Vec3::new(0., libm::sinf(tube), 0.);
+        (outward * radius + normal * tube_radius, normal)
+    });
+    builder.build()
+}
@@ -0,0 +1,83 @@
+//! Signals inserted into the command stream, notifying the CPU when the GE reaches them,
+//! and notifications for finished frames
+
+use core::ffi::c_void;
+use psp::sys::{self, GeCommand, GuCallbackId};
+
+use crate::{Frame, PspGfx};
+
+static mut SIGNAL_HANDLER: Option<fn(u16)> = None;
+static mut FINISH_HANDLER: Option<fn(u16)> = None;
+
+extern "C" fn on_signal(id: i32, _arg: *mut c_void) {
+    if let Some(handler) = unsafe { SIGNAL_HANDLER } {
+        handler(id as u16);
+    }
+}
+
+pub(crate) extern "C" fn on_finish(id: i32, _arg: *mut c_void) {
+    crate::profiler::ge_finished();
+    if let Some(handler) = unsafe { FINISH_HANDLER } {
+        handler(id as u16);
+    }
+}
+
+/// What the GE does while the signal handler runs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SignalBehavior {
+    /// Stop executing the list until the handler returns
+    Suspend,
+    /// Keep executing the list
+    #[default]
+    Continue,
+}
+
+impl SignalBehavior {
+    fn bits(self) -> i32 {
+        match self {
+            Self::Suspend => 1,
+            Self::Continue => 2,
+        }
+    }
+}
+
+impl PspGfx {
+    /// Set the function called when the GE reaches a signal inserted with [`Frame::signal`]
+    ///
+    /// The handler receives the signal id. It's called from an interrupt handler,
+    /// so it must return quickly and can't block, allocate or call most kernel functions.
+    /// Reading the system time to profile GE work is fine
+    pub fn set_signal_handler(&mut self, handler: Option<fn(u16)>) {
+        unsafe {
+            SIGNAL_HANDLER = handler;
+            sys::sceGuSetCallback(GuCallbackId::Signal, Some(on_signal));
+        }
+    }
+
+    /// Set the function called as soon as the GE finished drawing a frame
+    ///
+    /// The handler receives the lower 16 bits of the frame number, counting finished frames from 0.
+    /// It's called before the frame is displayed, so post-frame CPU work like audio mixing
+    /// can start without waiting for the vblank.
+    /// The same restrictions as for [`PspGfx::set_signal_handler`] apply
+    pub fn set_finish_handler(&mut self, handler: Option<fn(u16)>) {
+        unsafe {
+            FINISH_HANDLER = handler;
+            sys::sceGuSetCallback(GuCallbackId::Finish, Some(on_finish));
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Insert a signal, calling the handler set with [`PspGfx::set_signal_handler`] once the GE gets here
+    ///
+    /// Everything submitted before the signal has been processed by the GE when the handler runs
+    pub fn signal(&self, id: u16, behavior: SignalBehavior) {
+        // sceGuSignal puts its arguments into the wrong fields of the command,
+        // the behavior goes into the upper 8 bits and the id into the lower 16
+        unsafe {
+            sys::sceGuSendCommandi(GeCommand::Signal, behavior.bits() << 16 | id as i32);
+            sys::sceGuSendCommandi(GeCommand::End, 0);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Drawing the scene several times per frame into separate regions, e.g. for split-screen multiplayer
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Frame, camera::Camera, matrix::MatrixKind, rect::Rect, render_target};
+
+/// Arrangement of the regions returned by [`SplitLayout::split`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitLayout {
+    /// Two regions side by side
+    Horizontal,
+    /// Two regions on top of each other
+    Vertical,
+    /// Four regions in a 2x2 grid, in reading order
+    Quad,
+}
+
+impl SplitLayout {
+    /// Split `area` into regions separated by `gap` pixels
+    pub fn split(self, area: Rect, gap: i32) -> Vec<Rect> {
+        let half_w = (area.w - gap) / 2;
+        let half_h = (area.h - gap) / 2;
+        let (right, bottom) = (area.x + area.w - half_w, area.y + area.h - half_h);
+        match self {
+            Self::Horizontal => vec![
+                Rect::new(area.x, area.y, half_w, area.h),
+                Rect::new(right, area.y, half_w, area.h),
+            ],
+            Self::Vertical => vec![
+                Rect::new(area.x, area.y, area.w, half_h),
+                Rect::new(area.x, bottom, area.w, half_h),
+            ],
+            Self::Quad => vec![
+                Rect::new(area.x, area.y, half_w, half_h),
+                Rect::new(right, area.y, half_w, half_h),
+                Rect::new(area.x, bottom, half_w, half_h),
+                Rect::new(right, bottom, half_w, half_h),
+            ],
+        }
+    }
+}
+
+/// Region of the frame seen through its own camera, see [`Frame::draw_views`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Region of the screen (or render target), in pixels
+    pub rect: Rect,
+    pub camera: Camera,
+    /// Vertical field of view, in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl View {
+    pub const fn new(rect: Rect, camera: Camera, fov_y: f32, near: f32, far: f32) -> Self {
+        Self {
+            rect,
+            camera,
+            fov_y,
+            near,
+            far,
+        }
+    }
+
+    /// Get the aspect ratio of the region
+    pub fn aspect(&self) -> f32 {
+        self.rect.w as f32 / self.rect.h.max(1) as f32
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw the scene once for every view
+    ///
+    /// Before `draw` is called with the index of a view, the viewport and scissor are set to its region,
+    /// and its camera and a perspective projection matching the aspect ratio of the region are loaded.
+    /// The viewport, scissor, projection and view matrices are restored afterwards.
+    /// Clear each region from `draw` if the views overlap anything drawn before
+    pub fn draw_views(&self, views: &[View], mut draw: impl FnMut(&Self, usize)) {
+        let (viewport, scissor) = (self.viewport(), self.scissor());
+        let projection = self.matrix(MatrixKind::Projection).push();
+        let view_matrix = self.matrix(MatrixKind::View).push();
+        for (index, view) in views.iter().enumerate() {
+            let region = view.rect.intersect(&render_target::bounds());
+            if region.is_empty() {
+                continue;
+            }
+            self.set_viewport(view.rect);
+            self.set_scissor(region);
+            self.set_perspective(view.fov_y, view.aspect(), view.near, view.far);
+            self.set_camera(&view.camera);
+            draw(self, index);
+        }
+        drop((projection, view_matrix));
+        self.set_viewport(viewport);
+        self.set_scissor(scissor);
+    }
+}
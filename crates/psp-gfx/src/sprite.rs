@@ -0,0 +1,308 @@
+//! Batched drawing of textured quads in screen space
+
+use alloc::vec::Vec;
+use psp::sys::{GuPrimitive, TextureColorComponent, TextureEffect};
+
+use crate::{Frame, color::Color32, rect::Rect, texture::Texture};
+
+crate::define_vertex_layout! {
+    BatchVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_2D,
+        texture: TEXTURE_32BITF,
+        color: COLOR_8888,
+    }
+}
+
+/// Textured quad drawn by a [`SpriteBatch`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    /// Screen position of the origin, in pixels
+    pub x: f32,
+    pub y: f32,
+    /// Size on screen, in pixels
+    pub width: f32,
+    pub height: f32,
+    /// Texture region, in texels
+    pub uv: Rect,
+    /// Color multiplied with the texture
+    pub color: Color32,
+    /// Clockwise rotation around the origin, in radians
+    pub rotation: f32,
+    /// Point the sprite is positioned and rotated around, relative to its size
+    ///
+    /// `(0, 0)` is the top-left corner and `(1, 1)` the bottom-right one
+    pub origin: (f32, f32),
+    /// Draw order within a [`SpriteBatch`], sprites on higher layers are drawn over lower ones
+    pub layer: i32,
+    /// Mirror the texture region horizontally and vertically
+    pub flip: (bool, bool),
+}
+
+impl Sprite {
+    /// Create an untinted sprite with its top-left corner at the position, sized like its texture region
+    pub const fn new(x: f32, y: f32, uv: Rect) -> Self {
+        Self {
+            x,
+            y,
+            width: uv.w as f32,
+            height: uv.h as f32,
+            uv,
+            color: Color32::WHITE,
+            rotation: 0.,
+            origin: (0., 0.),
+            layer: 0,
+            flip: (false, false),
+        }
+    }
+
+    /// Create a sprite covering `dst` on screen, with the origin in its center
+    pub const fn from_rects(dst: Rect, src: Rect) -> Self {
+        Self {
+            x: dst.x as f32 + dst.w as f32 / 2.,
+            y: dst.y as f32 + dst.h as f32 / 2.,
+            width: dst.w as f32,
+            height: dst.h as f32,
+            origin: (0.5, 0.5),
+            ..Self::new(0., 0., src)
+        }
+    }
+
+    pub const fn with_size(self, width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            ..self
+        }
+    }
+
+    pub const fn with_color(self, color: Color32) -> Self {
+        Self { color, ..self }
+    }
+
+    pub const fn with_rotation(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub const fn with_origin(self, x: f32, y: f32) -> Self {
+        Self {
+            origin: (x, y),
+            ..self
+        }
+    }
+
+    pub const fn with_layer(self, layer: i32) -> Self {
+        Self { layer, ..self }
+    }
+
+    pub const fn with_flip(self, x: bool, y: bool) -> Self {
+        Self {
+            flip: (x, y),
+            ..self
+        }
+    }
+}
+
+/// Accumulates sprites and draws them with as few draw calls as possible
+///
+/// Sprites are sorted by [`Sprite::layer`] when the batch is flushed, keeping the submission order
+/// within a layer. They're drawn in through mode, one draw call per run of sprites sharing the same texture.
+/// Rotated sprites are drawn as triangles, so switching between rotated and unrotated sprites
+/// also starts a new draw call, as does switching between clip rectangles.
+/// The batch is flushed when it's dropped
+pub struct SpriteBatch<'frame, 'gfx> {
+    frame: &'frame Frame<'gfx>,
+    queue: Vec<(&'gfx Texture, Sprite, Option<Rect>)>,
+    /// Clip rectangle of the sprites queued from now on
+    clip: Option<Rect>,
+    /// Clip rectangle of the vertices being accumulated
+    run_clip: Option<Rect>,
+    texture: Option<&'gfx Texture>,
+    bound: Option<&'gfx Texture>,
+    rotated: bool,
+    vertices: Vec<BatchVertex>,
+}
+
+impl<'frame, 'gfx> SpriteBatch<'frame, 'gfx> {
+    /// Queue a sprite
+    pub fn draw(&mut self, texture: &'gfx Texture, sprite: &Sprite) {
+        self.queue.push((texture, *sprite, self.clip));
+    }
+
+    /// Clip the sprites queued from now on to a region of the screen (in pixels), or stop clipping them
+    ///
+    /// The clip rectangle is applied with the scissor test when the batch is flushed,
+    /// on top of the scissor region that's active at that point
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+    pub fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    /// Draw the queued sprites, sorted by layer
+    pub fn flush(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        // Other code may have bound a texture or changed the texture function since the last flush
+        self.bound = None;
+        let function = self.frame.texture_function();
+        self.frame
+            .set_texture_function(TextureEffect::Modulate, TextureColorComponent::Rgba);
+        // Stable, so sprites on the same layer keep their order
+        self.queue.sort_by_key(|(_, sprite, _)| sprite.layer);
+        let scissor = self.frame.scissor();
+        let mut queue = core::mem::take(&mut self.queue);
+        for (texture, sprite, clip) in queue.drain(..) {
+            self.push_vertices(texture, &sprite, clip, scissor);
+        }
+        self.draw_vertices(scissor);
+        if self.frame.scissor() != scissor {
+            self.frame.set_scissor(scissor);
+        }
+        self.frame
+            .set_texture_function(function.effect, function.component);
+        // Keep the allocation for the next flush
+        self.queue = queue;
+    }
+
+    fn push_vertices(
+        &mut self,
+        texture: &'gfx Texture,
+        sprite: &Sprite,
+        clip: Option<Rect>,
+        scissor: Rect,
+    ) {
+        let rotated = sprite.rotation != 0.;
+        let same_texture = self
+            .texture
+            .is_some_and(|current| core::ptr::eq(current, texture));
+        if !same_texture || rotated != self.rotated || clip != self.run_clip {
+            self.draw_vertices(scissor);
+            self.texture = Some(texture);
+            self.rotated = rotated;
+            self.run_clip = clip;
+        }
+        let (origin_x, origin_y) = (
+            sprite.origin.0 * sprite.width,
+            sprite.origin.1 * sprite.height,
+        );
+        let (left, top) = (-origin_x, -origin_y);
+        let (right, bottom) = (sprite.width - origin_x, sprite.height - origin_y);
+        let uv = sprite.uv;
+        let (u0, v0) = (uv.x as f32, uv.y as f32);
+        let (u1, v1) = ((uv.x + uv.w) as f32, (uv.y + uv.h) as f32);
+        let (u0, u1) = if sprite.flip.0 { (u1, u0) } else { (u0, u1) };
+        let (v0, v1) = if sprite.flip.1 { (v1, v0) } else { (v0, v1) };
+        let vertex = |x: f32, y: f32, u: f32, v: f32| BatchVertex {
+            u,
+            v,
+            color: sprite.color,
+            x: sprite.x + x,
+            y: sprite.y + y,
+            ..BatchVertex::DEFAULT
+        };
+
+        if rotated {
+            let (sin, cos) = (libm::sinf(sprite.rotation), libm::cosf(sprite.rotation));
+            let rotate = |x: f32, y: f32| (x * cos - y * sin, x * sin + y * cos);
+            let corners = [
+                (rotate(left, top), u0, v0),
+                (rotate(right, top), u1, v0),
+                (rotate(right, bottom), u1, v1),
+                (rotate(left, bottom), u0, v1),
+            ];
+            for index in [0, 1, 2, 0, 2, 3] {
+                let ((x, y), u, v) = corners[index];
+                self.vertices.push(vertex(x, y, u, v));
+            }
+        } else {
+            self.vertices.push(vertex(left, top, u0, v0));
+            self.vertices.push(vertex(right, bottom, u1, v1));
+        }
+    }
+
+    /// Draw the accumulated vertices, clipped to the run clip rectangle inside of `scissor`
+    fn draw_vertices(&mut self, scissor: Rect) {
+        let Some(texture) = self.texture else {
+            return;
+        };
+        if self.vertices.is_empty() {
+            return;
+        }
+        let region = match self.run_clip {
+            Some(clip) => scissor.intersect(&clip),
+            None => scissor,
+        };
+        if region.is_empty() {
+            self.vertices.clear();
+            return;
+        }
+        if self.frame.scissor() != region {
+            self.frame.set_scissor(region);
+        }
+        if !self
+            .bound
+            .is_some_and(|bound| core::ptr::eq(bound, texture))
+        {
+            self.frame.bind_texture(texture);
+            self.bound = Some(texture);
+        }
+        let primitive = match self.rotated {
+            true => GuPrimitive::Triangles,
+            false => GuPrimitive::Sprites,
+        };
+        self.frame
+            .draw_array(primitive, &self.frame.get_memory(&self.vertices));
+        self.vertices.clear();
+    }
+}
+
+impl<'frame, 'gfx> Drop for SpriteBatch<'frame, 'gfx> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Start a new [`SpriteBatch`]
+    ///
+    /// Sprites are drawn with the texture modulated by the vertex colors, so sprite colors act as tints.
+    /// The texture function is restored after every flush
+    pub fn sprite_batch(&self) -> SpriteBatch<'_, 'gfx> {
+        SpriteBatch {
+            frame: self,
+            queue: Vec::new(),
+            clip: None,
+            run_clip: None,
+            texture: None,
+            bound: None,
+            rotated: false,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Draw a single sprite
+    ///
+    /// - `dst`: position and size on screen, in pixels
+    /// - `src`: texture region, in texels
+    /// - `tint`: color multiplied with the texture
+    /// - `rotation`: clockwise rotation around the center of `dst`, in radians
+    ///
+    /// For many sprites, use a [`SpriteBatch`] to save draw calls
+    pub fn draw_sprite(
+        &self,
+        texture: &'gfx Texture,
+        dst: Rect,
+        src: Rect,
+        tint: Color32,
+        rotation: f32,
+    ) {
+        let sprite = Sprite::from_rects(dst, src)
+            .with_color(tint)
+            .with_rotation(rotation);
+        self.sprite_batch().draw(texture, &sprite);
+    }
+}
@@ -0,0 +1,1072 @@
+use psp::sys::{
+    self, DisplayPixelFormat, FrontFaceDirection, GuPrimitive, GuState, TextureColorComponent,
+    TextureEffect,
+};
+
+use crate::{
+    Frame,
+    buffer::Buffer,
+    color::Color32,
+    patch::{self, PatchPrimitive},
+    texture::{TextureFilter, TextureWrap},
+    vertex::Vertex,
+    viewport::DepthRange,
+};
+
+/// Comparison used by the per-pixel tests
+///
+/// The incoming value is on the left side of the comparison, e.g. [`CompareFunc::Greater`] passes
+/// if the incoming value is greater than the reference (or stored) value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Always,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl From<CompareFunc> for sys::AlphaFunc {
+    fn from(func: CompareFunc) -> Self {
+        match func {
+            CompareFunc::Never => Self::Never,
+            CompareFunc::Always => Self::Always,
+            CompareFunc::Equal => Self::Equal,
+            CompareFunc::NotEqual => Self::NotEqual,
+            CompareFunc::Less => Self::Less,
+            CompareFunc::LessOrEqual => Self::LessOrEqual,
+            CompareFunc::Greater => Self::Greater,
+            CompareFunc::GreaterOrEqual => Self::GreaterOrEqual,
+        }
+    }
+}
+
+impl From<CompareFunc> for sys::DepthFunc {
+    fn from(func: CompareFunc) -> Self {
+        match func {
+            CompareFunc::Never => Self::Never,
+            CompareFunc::Always => Self::Always,
+            CompareFunc::Equal => Self::Equal,
+            CompareFunc::NotEqual => Self::NotEqual,
+            CompareFunc::Less => Self::Less,
+            CompareFunc::LessOrEqual => Self::LessOrEqual,
+            CompareFunc::Greater => Self::Greater,
+            CompareFunc::GreaterOrEqual => Self::GreaterOrEqual,
+        }
+    }
+}
+
+impl From<CompareFunc> for sys::StencilFunc {
+    fn from(func: CompareFunc) -> Self {
+        match func {
+            CompareFunc::Never => Self::Never,
+            CompareFunc::Always => Self::Always,
+            CompareFunc::Equal => Self::Equal,
+            CompareFunc::NotEqual => Self::NotEqual,
+            CompareFunc::Less => Self::Less,
+            CompareFunc::LessOrEqual => Self::LessOrEqual,
+            CompareFunc::Greater => Self::Greater,
+            CompareFunc::GreaterOrEqual => Self::GreaterOrEqual,
+        }
+    }
+}
+
+/// Discards pixels based on their alpha, see [`Frame::set_alpha_test`]
+///
+/// Allows cutout transparency (foliage, fences) without sorting or blending
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlphaTest {
+    /// Pixels pass if `alpha & mask` compares to `reference & mask` with this function
+    pub func: CompareFunc,
+    pub reference: u8,
+    pub mask: u8,
+}
+
+impl AlphaTest {
+    /// Discard fully transparent pixels
+    pub const CUTOUT: Self = Self::new(CompareFunc::Greater, 0);
+
+    pub const fn new(func: CompareFunc, reference: u8) -> Self {
+        Self {
+            func,
+            reference,
+            mask: 0xff,
+        }
+    }
+}
+
+/// Depth buffer configuration, see [`Frame::set_depth`]
+///
+/// With the default [`DepthRange::FULL`](crate::viewport::DepthRange::FULL) closer pixels have *larger* depth values,
+/// so the usual test is [`CompareFunc::GreaterOrEqual`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthState {
+    /// Depth test function, `None` disables the test (and with it depth writes)
+    pub test: Option<CompareFunc>,
+    /// Write the depth of passing pixels to the depth buffer
+    pub write: bool,
+}
+
+impl DepthState {
+    /// Regular opaque geometry
+    pub const DEFAULT: Self = Self::new(Some(CompareFunc::GreaterOrEqual), true);
+    /// Test against the depth buffer without modifying it, for transparent geometry drawn after opaque geometry
+    pub const READ_ONLY: Self = Self::new(Some(CompareFunc::GreaterOrEqual), false);
+    /// No depth testing
+    pub const DISABLED: Self = Self::new(None, false);
+
+    pub const fn new(test: Option<CompareFunc>, write: bool) -> Self {
+        Self { test, write }
+    }
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Action applied to the stored stencil value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    /// Replace with the reference value
+    Replace,
+    Invert,
+    Increment,
+    Decrement,
+}
+
+impl From<StencilOp> for sys::StencilOperation {
+    fn from(op: StencilOp) -> Self {
+        match op {
+            StencilOp::Keep => Self::Keep,
+            StencilOp::Zero => Self::Zero,
+            StencilOp::Replace => Self::Replace,
+            StencilOp::Invert => Self::Invert,
+            StencilOp::Increment => Self::Incr,
+            StencilOp::Decrement => Self::Decr,
+        }
+    }
+}
+
+/// Stencil test configuration, see [`Frame::set_stencil`]
+///
+/// The stencil buffer is stored in the alpha bits of the color buffer,
+/// so it's unavailable with the 5650 pixel format and only has 1 bit with 5551
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilState {
+    /// Pixels pass if `reference & mask` compares to `stored & mask` with this function
+    pub func: CompareFunc,
+    pub reference: u8,
+    pub mask: u8,
+    /// Action taken when the stencil test fails
+    pub fail: StencilOp,
+    /// Action taken when the stencil test passes, but the depth test fails
+    pub depth_fail: StencilOp,
+    /// Action taken when both tests pass
+    pub pass: StencilOp,
+}
+
+impl StencilState {
+    /// Write `reference` everywhere something is drawn, to build a mask
+    pub const fn write(reference: u8) -> Self {
+        Self {
+            func: CompareFunc::Always,
+            reference,
+            mask: 0xff,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Replace,
+        }
+    }
+
+    /// Only draw where the stored value compares to `reference` with `func`, leaving the buffer unchanged
+    pub const fn test(func: CompareFunc, reference: u8) -> Self {
+        Self {
+            func,
+            reference,
+            mask: 0xff,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+
+    pub const fn with_mask(self, mask: u8) -> Self {
+        Self { mask, ..self }
+    }
+
+    /// Set the actions for failing the stencil test, failing the depth test and passing both
+    pub const fn with_ops(self, fail: StencilOp, depth_fail: StencilOp, pass: StencilOp) -> Self {
+        Self {
+            fail,
+            depth_fail,
+            pass,
+            ..self
+        }
+    }
+}
+
+/// Color channels written by draw calls, see [`Frame::set_color_mask`]
+///
+/// The alpha channel also holds the stencil buffer, masking it prevents stencil updates too
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorMask {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl ColorMask {
+    pub const ALL: Self = Self::new(true, true, true, true);
+    /// Nothing is written to the color buffer, for depth-only passes
+    pub const NONE: Self = Self::new(false, false, false, false);
+    pub const RGB: Self = Self::new(true, true, true, false);
+    pub const ALPHA: Self = Self::new(false, false, false, true);
+
+    pub const fn new(red: bool, green: bool, blue: bool, alpha: bool) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// Bits of an A8B8G8R8 pixel that are *not* written, as expected by `sceGuPixelMask`
+    pub fn pixel_mask(&self) -> u32 {
+        let channel = |write: bool, shift: u32| if write { 0 } else { 0xff << shift };
+        channel(self.red, 0)
+            | channel(self.green, 8)
+            | channel(self.blue, 16)
+            | channel(self.alpha, 24)
+    }
+}
+
+impl Default for ColorMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Bitwise operation between the incoming (source) and stored (destination) pixel,
+/// see [`Frame::set_logic_op`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogicOp {
+    /// `0`
+    Clear,
+    /// `src & dst`
+    And,
+    /// `src & !dst`
+    AndReverse,
+    /// `src`
+    Copy,
+    /// `!src & dst`
+    AndInverted,
+    /// `dst`
+    Noop,
+    /// `src ^ dst`
+    Xor,
+    /// `src | dst`
+    Or,
+    /// `!(src | dst)`
+    Nor,
+    /// `!(src ^ dst)`
+    Equiv,
+    /// `!dst`
+    Invert,
+    /// `src | !dst`
+    OrReverse,
+    /// `!src`
+    CopyInverted,
+    /// `!src | dst`
+    OrInverted,
+    /// `!(src & dst)`
+    Nand,
+    /// all bits set
+    Set,
+}
+
+impl From<LogicOp> for sys::LogicalOperation {
+    fn from(op: LogicOp) -> Self {
+        match op {
+            LogicOp::Clear => Self::Clear,
+            LogicOp::And => Self::And,
+            LogicOp::AndReverse => Self::AndReverse,
+            LogicOp::Copy => Self::Copy,
+            LogicOp::AndInverted => Self::AndInverted,
+            LogicOp::Noop => Self::Noop,
+            LogicOp::Xor => Self::Xor,
+            LogicOp::Or => Self::Or,
+            LogicOp::Nor => Self::Nor,
+            LogicOp::Equiv => Self::Equiv,
+            LogicOp::Invert => Self::Inverted,
+            LogicOp::OrReverse => Self::OrReverse,
+            LogicOp::CopyInverted => Self::CopyInverted,
+            LogicOp::OrInverted => Self::OrInverted,
+            LogicOp::Nand => Self::Nand,
+            LogicOp::Set => Self::Set,
+        }
+    }
+}
+
+/// Faces discarded by the GE, based on their winding order on screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullMode {
+    /// Draw both sides
+    #[default]
+    None,
+    /// Discard clockwise faces, counter-clockwise faces are front faces
+    Clockwise,
+    /// Discard counter-clockwise faces, clockwise faces are front faces
+    CounterClockwise,
+}
+
+impl CullMode {
+    /// Winding of the faces that are kept, `None` if culling is disabled
+    fn front_face(self) -> Option<FrontFaceDirection> {
+        match self {
+            Self::None => None,
+            Self::Clockwise => Some(FrontFaceDirection::CounterClockwise),
+            Self::CounterClockwise => Some(FrontFaceDirection::Clockwise),
+        }
+    }
+}
+
+/// Comparison used by the color test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorTestFunc {
+    Never,
+    Always,
+    Equal,
+    NotEqual,
+}
+
+impl From<ColorTestFunc> for sys::ColorFunc {
+    fn from(func: ColorTestFunc) -> Self {
+        match func {
+            ColorTestFunc::Never => Self::Never,
+            ColorTestFunc::Always => Self::Always,
+            ColorTestFunc::Equal => Self::Equal,
+            ColorTestFunc::NotEqual => Self::NotEqual,
+        }
+    }
+}
+
+/// Discards pixels based on their color, see [`Frame::set_color_test`]
+///
+/// The alpha channel is not part of the test, use [`AlphaTest`] for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorTest {
+    /// Pixels pass if `color & mask` compares to `reference & mask` with this function
+    pub func: ColorTestFunc,
+    pub reference: Color32,
+    /// Compared bits of the color, in the A8B8G8R8 format
+    pub mask: u32,
+}
+
+impl ColorTest {
+    /// Discard pixels of exactly the `key` color, for assets using color-key transparency
+    pub const fn color_key(key: Color32) -> Self {
+        Self {
+            func: ColorTestFunc::NotEqual,
+            reference: key,
+            mask: 0xffffff,
+        }
+    }
+}
+
+/// Linear distance fog, see [`Frame::set_fog`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    /// View space distance where the fog starts
+    pub near: f32,
+    /// View space distance where the fog reaches full density
+    pub far: f32,
+    pub color: Color32,
+}
+
+impl Fog {
+    pub const fn new(near: f32, far: f32, color: Color32) -> Self {
+        Self { near, far, color }
+    }
+}
+
+/// Operation combining the weighted source and destination colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendOp {
+    /// `src * src_factor + dst * dst_factor`
+    Add,
+    /// `src * src_factor - dst * dst_factor`
+    Subtract,
+    /// `dst * dst_factor - src * src_factor`
+    ReverseSubtract,
+    /// `min(src, dst)`, factors are ignored
+    Min,
+    /// `max(src, dst)`, factors are ignored
+    Max,
+    /// `|src - dst|`, factors are ignored
+    Abs,
+}
+
+impl From<BlendOp> for sys::BlendOp {
+    fn from(op: BlendOp) -> Self {
+        match op {
+            BlendOp::Add => sys::BlendOp::Add,
+            BlendOp::Subtract => sys::BlendOp::Subtract,
+            BlendOp::ReverseSubtract => sys::BlendOp::ReverseSubtract,
+            BlendOp::Min => sys::BlendOp::Min,
+            BlendOp::Max => sys::BlendOp::Max,
+            BlendOp::Abs => sys::BlendOp::Abs,
+        }
+    }
+}
+
+/// Weight applied to a color before blending
+///
+/// The GE has no `One` or `Zero` factors, use [`BlendFactor::ONE`] and [`BlendFactor::ZERO`] instead
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendFactor {
+    /// The color of the other operand (destination color for the source factor and vice versa)
+    OtherColor,
+    OneMinusOtherColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    /// Constant color, the alpha is ignored
+    Fix(Color32),
+}
+
+impl BlendFactor {
+    pub const ONE: Self = Self::Fix(Color32::WHITE);
+    pub const ZERO: Self = Self::Fix(Color32::BLACK);
+
+    /// Check if the factor reads the alpha of the destination pixel
+    pub const fn uses_dst_alpha(&self) -> bool {
+        matches!(self, Self::DstAlpha | Self::OneMinusDstAlpha)
+    }
+
+    fn split(self) -> (sys::BlendFactor, u32) {
+        match self {
+            Self::OtherColor => (sys::BlendFactor::Color, 0),
+            Self::OneMinusOtherColor => (sys::BlendFactor::OneMinusColor, 0),
+            Self::SrcAlpha => (sys::BlendFactor::SrcAlpha, 0),
+            Self::OneMinusSrcAlpha => (sys::BlendFactor::OneMinusSrcAlpha, 0),
+            Self::DstAlpha => (sys::BlendFactor::DstAlpha, 0),
+            Self::OneMinusDstAlpha => (sys::BlendFactor::OneMinusDstAlpha, 0),
+            Self::Fix(color) => (sys::BlendFactor::Fix, color.as_abgr()),
+        }
+    }
+}
+
+/// Blending configuration, see [`Frame::set_blend`]
+///
+/// Except for [`BlendMode::DST_ALPHA_MASK`], the presets behave the same with every draw buffer pixel format:
+/// they only weight by the source alpha and use the pure white and black [`BlendFactor::Fix`] values,
+/// which are exact in every format. Modes reading the destination alpha are format dependent,
+/// and don't work with [`Psm5650`](DisplayPixelFormat::Psm5650), see [`BlendMode::supports_format`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendMode {
+    pub op: BlendOp,
+    pub src: BlendFactor,
+    pub dst: BlendFactor,
+}
+
+impl BlendMode {
+    /// Standard alpha blending, for colors that are not premultiplied
+    pub const ALPHA: Self = Self::new(
+        BlendOp::Add,
+        BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha,
+    );
+    /// Alpha blending for colors premultiplied by their alpha
+    pub const PREMULTIPLIED_ALPHA: Self = Self::new(
+        BlendOp::Add,
+        BlendFactor::ONE,
+        BlendFactor::OneMinusSrcAlpha,
+    );
+    /// Adds the source color weighted by its alpha, for lights, fire and glow effects
+    pub const ADDITIVE: Self = Self::new(BlendOp::Add, BlendFactor::SrcAlpha, BlendFactor::ONE);
+    /// Multiplies the destination by the source color, for shadows and tinting
+    pub const MULTIPLY: Self = Self::new(BlendOp::Add, BlendFactor::OtherColor, BlendFactor::ZERO);
+    /// Subtracts the source color weighted by its alpha from the destination
+    pub const SUBTRACT: Self = Self::new(
+        BlendOp::ReverseSubtract,
+        BlendFactor::SrcAlpha,
+        BlendFactor::ONE,
+    );
+
+    /// Weights by the destination alpha, to draw only where a previous pass wrote an opaque alpha mask
+    ///
+    /// Requires a draw buffer format with destination alpha, see [`BlendMode::supports_format`]
+    pub const DST_ALPHA_MASK: Self = Self::new(
+        BlendOp::Add,
+        BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha,
+    );
+
+    pub const fn new(op: BlendOp, src: BlendFactor, dst: BlendFactor) -> Self {
+        Self { op, src, dst }
+    }
+
+    /// Check if the mode gives the intended result when drawing to a buffer of the given pixel format
+    ///
+    /// - [`Psm8888`](DisplayPixelFormat::Psm8888) and [`Psm4444`](DisplayPixelFormat::Psm4444)
+    ///   support every mode, though 4444 only keeps 16 alpha levels
+    /// - [`Psm5551`](DisplayPixelFormat::Psm5551) only stores a single alpha bit,
+    ///   destination alpha factors are either fully on or off
+    /// - [`Psm5650`](DisplayPixelFormat::Psm5650) has no alpha channel at all,
+    ///   modes reading the destination alpha are not supported
+    ///
+    /// Fix values other than pure white and black are quantized to the color depth of 16-bit formats
+    pub const fn supports_format(&self, format: DisplayPixelFormat) -> bool {
+        let dst_alpha = self.src.uses_dst_alpha() || self.dst.uses_dst_alpha();
+        !(dst_alpha && matches!(format, DisplayPixelFormat::Psm5650))
+    }
+}
+
+/// How the texture color is combined with the vertex color, see [`Frame::set_texture_function`]
+#[derive(Clone, Copy, Debug)]
+pub struct TextureFunction {
+    pub effect: TextureEffect,
+    pub component: TextureColorComponent,
+}
+
+impl TextureFunction {
+    /// Function set up by [`PspGfx::init`](crate::PspGfx::init), the texture alpha is ignored
+    pub const DEFAULT: Self = Self::new(TextureEffect::Modulate, TextureColorComponent::Rgb);
+
+    pub const fn new(effect: TextureEffect, component: TextureColorComponent) -> Self {
+        Self { effect, component }
+    }
+}
+
+impl Default for TextureFunction {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+// The sys enums don't implement PartialEq
+impl PartialEq for TextureFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.effect as u32 == other.effect as u32 && self.component as u32 == other.component as u32
+    }
+}
+
+impl Eq for TextureFunction {}
+
+/// Complete raster state of the GE
+///
+/// Every state setter of [`Frame`] keeps track of the current state,
+/// which can be read back with [`Frame::render_state`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderState {
+    pub blend: Option<BlendMode>,
+    pub alpha_test: Option<AlphaTest>,
+    pub depth: DepthState,
+    pub stencil: Option<StencilState>,
+    pub color_mask: ColorMask,
+    pub logic_op: Option<LogicOp>,
+    pub cull_mode: CullMode,
+    pub fog: Option<Fog>,
+    pub color_test: Option<ColorTest>,
+    pub antialiasing: bool,
+    pub patch_cull_mode: CullMode,
+    pub patch_primitive: PatchPrimitive,
+    /// Patch subdivision along U and V
+    pub patch_divide: (u32, u32),
+    pub patch_normals_flipped: bool,
+    pub clipping: bool,
+    pub lighting: bool,
+    pub texturing: bool,
+    pub texture_function: TextureFunction,
+    pub texture_filter: TextureFilter,
+    /// Wrap modes along U and V
+    pub texture_wrap: (TextureWrap, TextureWrap),
+}
+
+static mut CURRENT: RenderState = RenderState::DEFAULT;
+
+impl RenderState {
+    /// State set up by [`PspGfx::init`](crate::PspGfx::init), everything except clipping is disabled
+    pub const DEFAULT: Self = Self {
+        blend: None,
+        alpha_test: None,
+        depth: DepthState::DISABLED,
+        stencil: None,
+        color_mask: ColorMask::ALL,
+        logic_op: None,
+        cull_mode: CullMode::None,
+        fog: None,
+        color_test: None,
+        antialiasing: false,
+        patch_cull_mode: CullMode::None,
+        patch_primitive: PatchPrimitive::Triangles,
+        patch_divide: patch::DEFAULT_DIVIDE,
+        patch_normals_flipped: false,
+        clipping: true,
+        lighting: false,
+        texturing: false,
+        texture_function: TextureFunction::DEFAULT,
+        texture_filter: TextureFilter::Nearest,
+        texture_wrap: (TextureWrap::Repeat, TextureWrap::Repeat),
+    };
+
+    /// Depth tested and written opaque geometry
+    pub const OPAQUE: Self = Self {
+        depth: DepthState::DEFAULT,
+        ..Self::DEFAULT
+    };
+
+    /// Alpha blended geometry drawn after the opaque geometry, depth tested without depth writes
+    pub const TRANSPARENT: Self = Self {
+        blend: Some(BlendMode::ALPHA),
+        depth: DepthState::READ_ONLY,
+        ..Self::DEFAULT
+    };
+
+    /// Alpha blended geometry drawn over everything else, like UI
+    pub const OVERLAY: Self = Self {
+        blend: Some(BlendMode::ALPHA),
+        ..Self::DEFAULT
+    };
+
+    /// Depth-only variant of this state, for the first pass of [`Frame::depth_prepass`]
+    ///
+    /// Color writes, blending, lighting and texturing are disabled, depth is tested and written
+    pub fn depth_only(&self) -> Self {
+        Self {
+            blend: None,
+            depth: DepthState::new(self.depth.test.or(DepthState::DEFAULT.test), true),
+            stencil: None,
+            color_mask: ColorMask::NONE,
+            logic_op: None,
+            fog: None,
+            lighting: false,
+            texturing: false,
+            ..*self
+        }
+    }
+
+    /// Variant of this state that only shades pixels left visible by a [`RenderState::depth_only`] pass
+    pub fn depth_equal(&self) -> Self {
+        Self {
+            depth: DepthState::new(Some(CompareFunc::Equal), false),
+            ..*self
+        }
+    }
+
+    /// Upload the whole state
+    ///
+    /// Like the state setters of [`Frame`], only call it while a frame is being built
+    pub fn apply(&self) {
+        Self::upload(None, self);
+    }
+
+    /// Switch from `prev` to `next`, only uploading the parts of the state that differ
+    ///
+    /// `prev` has to be the state that's currently active on the GE, see [`RenderState::apply`]
+    pub fn diff_apply(prev: &Self, next: &Self) {
+        Self::upload(Some(prev), next);
+    }
+
+    /// Switch back to `saved`, only uploading the parts that changed since it was active
+    pub(crate) fn restore(saved: &Self) {
+        Self::upload(Some(&current()), saved);
+    }
+
+    /// Upload the whole state without an active frame, used during initialization
+    pub(crate) fn reset() {
+        Self::upload(None, &Self::DEFAULT);
+    }
+
+    fn upload(prev: Option<&Self>, next: &Self) {
+        macro_rules! upload_changed {
+            ($($field:ident => $upload:ident),* $(,)?) => {
+                $(
+                    if prev.is_none_or(|prev| prev.$field != next.$field) {
+                        $upload(next.$field);
+                    }
+                )*
+            };
+        }
+        upload_changed! {
+            blend => upload_blend,
+            alpha_test => upload_alpha_test,
+            depth => upload_depth,
+            stencil => upload_stencil,
+            color_mask => upload_color_mask,
+            logic_op => upload_logic_op,
+            cull_mode => upload_cull_mode,
+            fog => upload_fog,
+            color_test => upload_color_test,
+            antialiasing => upload_antialiasing,
+            // Both set the patch front face, culling has to win
+            patch_normals_flipped => upload_patch_normals_flipped,
+            patch_cull_mode => upload_patch_cull_mode,
+            patch_primitive => upload_patch_primitive,
+            patch_divide => upload_patch_divide,
+            clipping => upload_clipping,
+            lighting => upload_lighting,
+            texturing => upload_texturing,
+            texture_function => upload_texture_function,
+            texture_filter => upload_texture_filter,
+            texture_wrap => upload_texture_wrap,
+        }
+    }
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+fn current() -> RenderState {
+    unsafe { CURRENT }
+}
+
+fn track(f: impl FnOnce(&mut RenderState)) {
+    unsafe { f(&mut CURRENT) }
+}
+
+/// Replace the tracked state without uploading it, returning the previous one
+///
+/// Used when commands outside of the setters changed the state on the GE
+pub(crate) fn replace_current(state: RenderState) -> RenderState {
+    unsafe { core::mem::replace(&mut CURRENT, state) }
+}
+
+fn upload_blend(blend: Option<BlendMode>) {
+    unsafe {
+        if let Some(blend) = blend {
+            let (src, src_fix) = blend.src.split();
+            let (dst, dst_fix) = blend.dst.split();
+            sys::sceGuBlendFunc(blend.op.into(), src, dst, src_fix, dst_fix);
+        }
+        sys::sceGuSetStatus(GuState::Blend, blend.is_some() as i32);
+    }
+    track(|state| state.blend = blend);
+}
+
+fn upload_alpha_test(test: Option<AlphaTest>) {
+    unsafe {
+        if let Some(test) = test {
+            sys::sceGuAlphaFunc(test.func.into(), test.reference as i32, test.mask as i32);
+        }
+        sys::sceGuSetStatus(GuState::AlphaTest, test.is_some() as i32);
+    }
+    track(|state| state.alpha_test = test);
+}
+
+fn upload_depth(depth: DepthState) {
+    unsafe {
+        if let Some(func) = depth.test {
+            sys::sceGuDepthFunc(func.into());
+        }
+        sys::sceGuSetStatus(GuState::DepthTest, depth.test.is_some() as i32);
+        // The mask disables writes when set
+        sys::sceGuDepthMask(!depth.write as i32);
+    }
+    track(|state| state.depth = depth);
+}
+
+fn upload_stencil(stencil: Option<StencilState>) {
+    unsafe {
+        if let Some(stencil) = stencil {
+            sys::sceGuStencilFunc(
+                stencil.func.into(),
+                stencil.reference as i32,
+                stencil.mask as i32,
+            );
+            sys::sceGuStencilOp(
+                stencil.fail.into(),
+                stencil.depth_fail.into(),
+                stencil.pass.into(),
+            );
+        }
+        sys::sceGuSetStatus(GuState::StencilTest, stencil.is_some() as i32);
+    }
+    track(|state| state.stencil = stencil);
+}
+
+fn upload_color_mask(mask: ColorMask) {
+    unsafe {
+        sys::sceGuPixelMask(mask.pixel_mask());
+    }
+    track(|state| state.color_mask = mask);
+}
+
+fn upload_logic_op(op: Option<LogicOp>) {
+    unsafe {
+        if let Some(op) = op {
+            sys::sceGuLogicalOp(op.into());
+        }
+        sys::sceGuSetStatus(GuState::ColorLogicOp, op.is_some() as i32);
+    }
+    track(|state| state.logic_op = op);
+}
+
+fn upload_cull_mode(mode: CullMode) {
+    let front_face = mode.front_face();
+    let enabled = front_face.is_some();
+    unsafe {
+        if let Some(front_face) = front_face {
+            sys::sceGuFrontFace(front_face);
+        }
+        sys::sceGuSetStatus(GuState::CullFace, enabled as i32);
+    }
+    track(|state| state.cull_mode = mode);
+}
+
+fn upload_fog(fog: Option<Fog>) {
+    unsafe {
+        if let Some(fog) = fog {
+            sys::sceGuFog(fog.near, fog.far, fog.color.as_abgr());
+        }
+        sys::sceGuSetStatus(GuState::Fog, fog.is_some() as i32);
+    }
+    track(|state| state.fog = fog);
+}
+
+fn upload_color_test(test: Option<ColorTest>) {
+    unsafe {
+        if let Some(test) = test {
+            sys::sceGuColorFunc(test.func.into(), test.reference.as_abgr(), test.mask);
+        }
+        sys::sceGuSetStatus(GuState::ColorTest, test.is_some() as i32);
+    }
+    track(|state| state.color_test = test);
+}
+
+fn upload_antialiasing(enabled: bool) {
+    unsafe {
+        sys::sceGuSetStatus(GuState::LineSmooth, enabled as i32);
+    }
+    track(|state| state.antialiasing = enabled);
+}
+
+fn upload_patch_cull_mode(mode: CullMode) {
+    let front_face = mode.front_face();
+    unsafe {
+        if let Some(front_face) = &front_face {
+            sys::sceGuPatchFrontFace(match front_face {
+                FrontFaceDirection::CounterClockwise => 0,
+                FrontFaceDirection::Clockwise => 1,
+            });
+        }
+        sys::sceGuSetStatus(GuState::PatchCullFace, front_face.is_some() as i32);
+    }
+    track(|state| state.patch_cull_mode = mode);
+}
+
+pub(crate) fn upload_patch_primitive(primitive: PatchPrimitive) {
+    unsafe {
+        sys::sceGuPatchPrim(primitive.into());
+    }
+    track(|state| state.patch_primitive = primitive);
+}
+
+pub(crate) fn upload_patch_divide(divide: (u32, u32)) {
+    unsafe {
+        sys::sceGuPatchDivide(divide.0, divide.1);
+    }
+    track(|state| state.patch_divide = divide);
+}
+
+pub(crate) fn upload_patch_normals_flipped(flipped: bool) {
+    unsafe {
+        sys::sceGuPatchFrontFace(flipped as u32);
+    }
+    track(|state| state.patch_normals_flipped = flipped);
+}
+
+fn upload_clipping(enabled: bool) {
+    unsafe {
+        sys::sceGuSetStatus(GuState::ClipPlanes, enabled as i32);
+    }
+    track(|state| state.clipping = enabled);
+}
+
+pub(crate) fn upload_lighting(enabled: bool) {
+    unsafe {
+        sys::sceGuSetStatus(GuState::Lighting, enabled as i32);
+    }
+    track(|state| state.lighting = enabled);
+}
+
+fn upload_texturing(enabled: bool) {
+    unsafe {
+        sys::sceGuSetStatus(GuState::Texture2D, enabled as i32);
+    }
+    track(|state| state.texturing = enabled);
+}
+
+pub(crate) fn upload_texture_function(function: TextureFunction) {
+    unsafe {
+        sys::sceGuTexFunc(function.effect, function.component);
+    }
+    track(|state| state.texture_function = function);
+}
+
+pub(crate) fn upload_texture_filter(filter: TextureFilter) {
+    unsafe {
+        sys::sceGuTexFilter(filter.into(), filter.into());
+    }
+    track(|state| state.texture_filter = filter);
+}
+
+pub(crate) fn upload_texture_wrap(wrap: (TextureWrap, TextureWrap)) {
+    unsafe {
+        sys::sceGuTexWrap(wrap.0.into(), wrap.1.into());
+    }
+    track(|state| state.texture_wrap = wrap);
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Get the current render state, as set through the state setters
+    ///
+    /// State changed with raw `sys` calls is not tracked
+    pub fn render_state(&self) -> RenderState {
+        current()
+    }
+
+    /// Set the blend mode for the following draw calls, `None` disables blending
+    pub fn set_blend(&self, blend: Option<BlendMode>) {
+        upload_blend(blend);
+    }
+
+    /// Set the alpha test for the following draw calls, `None` disables it
+    pub fn set_alpha_test(&self, test: Option<AlphaTest>) {
+        upload_alpha_test(test);
+    }
+
+    /// Set the depth test and depth writes for the following draw calls
+    pub fn set_depth(&self, depth: DepthState) {
+        upload_depth(depth);
+    }
+
+    /// Set the stencil test for the following draw calls, `None` disables it
+    pub fn set_stencil(&self, stencil: Option<StencilState>) {
+        upload_stencil(stencil);
+    }
+
+    /// Set which color channels the following draw calls write to
+    pub fn set_color_mask(&self, mask: ColorMask) {
+        upload_color_mask(mask);
+    }
+
+    /// Set the logical operation for the following draw calls, `None` disables it
+    ///
+    /// The operation is applied after blending, between the blended color and the stored pixel.
+    /// It also affects the alpha channel (and with it the stencil buffer), use [`Frame::set_color_mask`]
+    /// to protect it
+    pub fn set_logic_op(&self, op: Option<LogicOp>) {
+        upload_logic_op(op);
+    }
+
+    /// Set which faces are culled by the following draw calls
+    pub fn set_cull_mode(&self, mode: CullMode) {
+        upload_cull_mode(mode);
+    }
+
+    /// Enable linear fog for the following draw calls
+    ///
+    /// Geometry fades into `color` between the `near` and `far` view space distances,
+    /// a cheap way to hide the far plane
+    pub fn set_fog(&self, near: f32, far: f32, color: Color32) {
+        upload_fog(Some(Fog::new(near, far, color)));
+    }
+
+    pub fn disable_fog(&self) {
+        upload_fog(None);
+    }
+
+    /// Set the color test for the following draw calls, `None` disables it
+    pub fn set_color_test(&self, test: Option<ColorTest>) {
+        upload_color_test(test);
+    }
+
+    /// Enable or disable antialiasing of lines and patches for the following draw calls
+    pub fn set_antialiasing(&self, enabled: bool) {
+        upload_antialiasing(enabled);
+    }
+
+    /// Set which faces of bezier and spline patches are culled by the following draw calls
+    ///
+    /// Same as [`Frame::set_cull_mode`], but for the triangles generated from curved surfaces
+    pub fn set_patch_cull_mode(&self, mode: CullMode) {
+        upload_patch_cull_mode(mode);
+    }
+
+    /// Enable or disable clipping of primitives against the depth range
+    ///
+    /// With clipping disabled, triangles crossing the near plane are discarded entirely
+    pub fn set_clipping(&self, enabled: bool) {
+        upload_clipping(enabled);
+    }
+
+    /// Configure the viewport, depth range and clipping to minimize near-plane artifacts
+    ///
+    /// The GE culls whole primitives with a vertex outside of [`GUARD_BAND`](crate::viewport::GUARD_BAND) on X and Y,
+    /// or outside of the 16-bit depth range after the viewport transform. This
+    /// - resets the viewport to the full screen, which is centered in the guard band,
+    ///   leaving about 1800 pixels of room on every side,
+    /// - switches to [`DepthRange::GUARDED`], so primitives crossing the near or far plane
+    ///   stay inside of the 16-bit range and get cut per pixel instead of disappearing,
+    /// - enables clipping.
+    ///
+    /// The guarded range gives up about 40% of the depth precision for this.
+    /// Large triangles close to the camera can still leave the guard band, subdividing them is the only fix
+    pub fn set_safe_clipping(&self) {
+        self.reset_viewport();
+        self.set_depth_range(DepthRange::GUARDED);
+        self.set_clipping(true);
+    }
+
+    /// Enable or disable texturing for the following draw calls
+    pub fn set_texturing(&self, enabled: bool) {
+        upload_texturing(enabled);
+    }
+
+    /// Draw with a temporary render state, restoring the previous state afterwards
+    ///
+    /// Only the parts of the state that differ are uploaded, both before and after the draw
+    pub fn draw_with_state<V: Buffer>(
+        &self,
+        state: &RenderState,
+        primitive: GuPrimitive,
+        vertex_buf: &V,
+    ) where
+        V::Item: Vertex,
+    {
+        let previous = self.render_state();
+        RenderState::diff_apply(&previous, state);
+        self.draw_array(primitive, vertex_buf);
+        RenderState::diff_apply(state, &previous);
+    }
+
+    /// Draw the scene with a depth pre-pass, to shade every pixel only once in overdraw-heavy scenes
+    ///
+    /// `draw` is called twice: first with [`RenderState::depth_only`] to fill the depth buffer,
+    /// then with [`RenderState::depth_equal`] to shade only the visible surfaces.
+    /// Both passes must submit exactly the same geometry and transforms.
+    /// The current state is used as the base of both passes and restored afterwards
+    pub fn depth_prepass(&self, mut draw: impl FnMut(&Self)) {
+        let base = self.render_state();
+        RenderState::diff_apply(&base, &base.depth_only());
+        draw(self);
+        // `draw` may have changed the state itself
+        RenderState::diff_apply(&self.render_state(), &base.depth_equal());
+        draw(self);
+        RenderState::diff_apply(&self.render_state(), &base);
+    }
+}
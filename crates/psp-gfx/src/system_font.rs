@@ -0,0 +1,281 @@
+//! Firmware PGF fonts rasterized into a [`BitmapFont`]
+//!
+//! The fonts are rendered with the `sceLibFont` firmware module, which has to be loaded
+//! before a [`FontLibrary`] is created
+
+use alloc::{
+    alloc::{Layout, alloc, dealloc},
+    vec,
+    vec::Vec,
+};
+use core::{ffi::c_void, marker::PhantomData, ptr};
+use psp::sys::{
+    self, SceFontCharInfo, SceFontErrorCode, SceFontFamilyCode, SceFontGlyphImage, SceFontInfo,
+    SceFontLanguageCode, SceFontNewLibParams, SceFontPixelFormatCode, SceFontStyle,
+    SceFontStyleCode,
+};
+
+use crate::{
+    font::{BitmapFont, Glyph},
+    glyph_cache::{GlyphRasterizer, RasterGlyph},
+    rect::Rect,
+    texture::{MAX_TEXTURE_SIZE, Texture, TextureFormat},
+};
+
+/// Allocations made for the font library store their layout size in front of the returned pointer
+const HEADER: usize = 16;
+
+extern "C" fn font_alloc(_: *mut c_void, size: usize) -> *mut c_void {
+    unsafe {
+        let block = alloc(Layout::from_size_align_unchecked(size + HEADER, HEADER));
+        if block.is_null() {
+            return ptr::null_mut();
+        }
+        (block as *mut usize).write(size + HEADER);
+        block.add(HEADER) as *mut c_void
+    }
+}
+
+extern "C" fn font_free(_: *mut c_void, pointer: *mut c_void) {
+    if pointer.is_null() {
+        return;
+    }
+    unsafe {
+        let block = (pointer as *mut u8).sub(HEADER);
+        let size = (block as *mut usize).read();
+        dealloc(block, Layout::from_size_align_unchecked(size, HEADER));
+    }
+}
+
+fn succeeded(error: SceFontErrorCode) -> bool {
+    matches!(error, SceFontErrorCode::Success)
+}
+
+/// Handle to the firmware font library, used to open [`SystemFont`]s
+pub struct FontLibrary {
+    handle: u32,
+}
+
+impl FontLibrary {
+    /// Initialize the font library, allowing up to `max_fonts` fonts to be open at once
+    ///
+    /// Returns `None` if the library couldn't be initialized
+    pub fn new(max_fonts: u32) -> Option<Self> {
+        let params = SceFontNewLibParams {
+            user_data_addr: 0,
+            num_fonts: max_fonts,
+            cache_data: 0,
+            alloc_func: Some(font_alloc),
+            free_func: Some(font_free),
+            open_func: None,
+            close_func: None,
+            read_func: None,
+            seek_func: None,
+            error_func: None,
+            io_finish_func: None,
+        };
+        let mut error = SceFontErrorCode::Success;
+        let handle = unsafe { sys::sceFontNewLib(&params, &mut error) };
+        (succeeded(error) && handle != 0).then_some(Self { handle })
+    }
+
+    /// Open the installed font closest to the requested style
+    ///
+    /// - `size`: requested size in points, fonts are only installed in a few sizes
+    ///
+    /// Returns `None` if no matching font could be opened
+    pub fn open(
+        &self,
+        family: SceFontFamilyCode,
+        style: SceFontStyleCode,
+        language: SceFontLanguageCode,
+        size: f32,
+    ) -> Option<SystemFont<'_>> {
+        // SAFETY: all fields are numbers, byte arrays or enums with a zero variant
+        let mut request: SceFontStyle = unsafe { core::mem::zeroed() };
+        request.font_h = size;
+        request.font_v = size;
+        request.font_family = family;
+        request.font_style = style;
+        request.font_language = language;
+        let mut error = SceFontErrorCode::Success;
+        unsafe {
+            let index = sys::sceFontFindOptimumFont(self.handle, &request, &mut error);
+            if !succeeded(error) || index < 0 {
+                return None;
+            }
+            let handle = sys::sceFontOpen(self.handle, index as u32, 0, &mut error);
+            (succeeded(error) && handle != 0).then_some(SystemFont {
+                handle,
+                _library: PhantomData,
+            })
+        }
+    }
+
+    /// Open the default Latin sans-serif font, the one used by the system menus
+    pub fn open_default(&self) -> Option<SystemFont<'_>> {
+        self.open(
+            SceFontFamilyCode::SansSerif,
+            SceFontStyleCode::Regular,
+            SceFontLanguageCode::Latin,
+            0.,
+        )
+    }
+}
+
+impl Drop for FontLibrary {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceFontDoneLib(self.handle);
+        }
+    }
+}
+
+/// Font installed in the firmware, opened with [`FontLibrary::open`]
+pub struct SystemFont<'lib> {
+    handle: u32,
+    _library: PhantomData<&'lib FontLibrary>,
+}
+
+impl SystemFont<'_> {
+    fn info(&self) -> SceFontInfo {
+        // SAFETY: see `FontLibrary::open`
+        let mut info: SceFontInfo = unsafe { core::mem::zeroed() };
+        unsafe {
+            sys::sceFontGetFontInfo(self.handle, &mut info);
+        }
+        info
+    }
+
+    /// Get the metrics of a character, `None` if the font doesn't contain it
+    fn char_info(&self, c: char) -> Option<SceFontCharInfo> {
+        let mut info = SceFontCharInfo::default();
+        let result = unsafe { sys::sceFontGetCharInfo(self.handle, c as u32, &mut info) };
+        (result == 0 && info.sfp26_advance_h != 0).then_some(info)
+    }
+
+    /// Render the characters of `chars` into a `size` by `size` atlas and build a bitmap font from it
+    ///
+    /// Characters missing from the font are skipped, so include [`BitmapFont::FALLBACK`]
+    /// to have something drawn in their place. Returns `None` if the glyphs don't fit into the atlas
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` isn't a power of two up to [`MAX_TEXTURE_SIZE`]
+    pub fn rasterize(&self, chars: &str, size: u32) -> Option<BitmapFont> {
+        assert!(
+            size.is_power_of_two() && size <= MAX_TEXTURE_SIZE,
+            "texture sizes have to be powers of two up to {MAX_TEXTURE_SIZE}, got {size}"
+        );
+        let info = self.info();
+        let ascender = info.max_glyph_ascender_i / 64;
+        let line_height = (info.max_glyph_advance_y_i / 64).max(info.max_glyph_height as i32);
+
+        let mut alpha = vec![0u8; (size * size) as usize];
+        let mut glyphs = Vec::new();
+        let (mut shelf_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+        for c in chars.chars() {
+            let Some(char_info) = self.char_info(c) else {
+                continue;
+            };
+            let (width, height) = (
+                char_info.bitmap_width as i32,
+                char_info.bitmap_height as i32,
+            );
+            let (left, top) = (char_info.bitmap_left as i32, char_info.bitmap_top as i32);
+            // Leave a pixel of padding so filtering doesn't pick up neighbouring glyphs
+            if shelf_x + width + 1 > size as i32 {
+                (shelf_x, shelf_y, shelf_height) = (0, shelf_y + shelf_height + 1, 0);
+            }
+            if width > size as i32 || shelf_y + height > size as i32 {
+                return None;
+            }
+            if width > 0 && height > 0 {
+                let mut image = SceFontGlyphImage {
+                    pixel_format: SceFontPixelFormatCode::Format8,
+                    x_pos_64: (shelf_x - left) * 64,
+                    y_pos_64: (shelf_y + top) * 64,
+                    buf_width: size as u16,
+                    buf_height: size as u16,
+                    bytes_per_line: size as u16,
+                    pad: 0,
+                    buffer_ptr: alpha.as_mut_ptr() as u32,
+                };
+                unsafe {
+                    sys::sceFontGetCharGlyphImage(self.handle, c as u32, &mut image);
+                }
+            }
+            glyphs.push((
+                c,
+                Glyph {
+                    rect: Rect::new(shelf_x, shelf_y, width, height),
+                    x_offset: left,
+                    y_offset: ascender - top,
+                    x_advance: char_info.sfp26_advance_h / 64,
+                },
+            ));
+            shelf_x += width + 1;
+            shelf_height = shelf_height.max(height);
+        }
+
+        // White pixels with the rendered coverage as alpha
+        let pixels: Vec<u8> = alpha
+            .iter()
+            .flat_map(|&a| ((a as u16 >> 4) << 12 | 0x0fff).to_le_bytes())
+            .collect();
+        let texture = Texture::new(size, size, TextureFormat::Psm4444, &pixels);
+        let mut font = BitmapFont::new(texture, line_height);
+        for (c, glyph) in glyphs {
+            font.set_glyph(c, glyph);
+        }
+        Some(font)
+    }
+}
+
+impl GlyphRasterizer for SystemFont<'_> {
+    fn line_height(&self) -> i32 {
+        let info = self.info();
+        (info.max_glyph_advance_y_i / 64).max(info.max_glyph_height as i32)
+    }
+
+    fn rasterize_glyph(&self, c: char) -> Option<RasterGlyph> {
+        let char_info = self.char_info(c)?;
+        let (width, height) = (
+            char_info.bitmap_width as i32,
+            char_info.bitmap_height as i32,
+        );
+        let (left, top) = (char_info.bitmap_left as i32, char_info.bitmap_top as i32);
+        let mut coverage = vec![0u8; (width * height) as usize];
+        if width > 0 && height > 0 {
+            let mut image = SceFontGlyphImage {
+                pixel_format: SceFontPixelFormatCode::Format8,
+                x_pos_64: -left * 64,
+                y_pos_64: top * 64,
+                buf_width: width as u16,
+                buf_height: height as u16,
+                bytes_per_line: width as u16,
+                pad: 0,
+                buffer_ptr: coverage.as_mut_ptr() as u32,
+            };
+            unsafe {
+                sys::sceFontGetCharGlyphImage(self.handle, c as u32, &mut image);
+            }
+        }
+        Some(RasterGlyph {
+            width,
+            height,
+            x_offset: left,
+            y_offset: self.info().max_glyph_ascender_i / 64 - top,
+            x_advance: char_info.sfp26_advance_h / 64,
+            coverage,
+        })
+    }
+}
+
+impl Drop for SystemFont<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceFontClose(self.handle);
+        }
+    }
+}
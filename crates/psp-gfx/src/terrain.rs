@@ -0,0 +1,271 @@
+//! Terrain meshes generated from heightmaps, split into chunks that can be culled separately
+
+use alloc::vec::Vec;
+use psp::sys::GuPrimitive;
+
+use crate::{
+    Frame,
+    frustum::Frustum,
+    math::{Aabb, Vec3},
+    mesh::Mesh,
+    vertex::Vertex,
+};
+
+crate::define_vertex_layout! {
+    /// Vertex layout of the meshes generated by [`Terrain::new`]
+    pub TerrainVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_3D,
+        texture: TEXTURE_32BITF,
+        normal: NORMAL_32BITF,
+    }
+}
+
+/// Value of a heightmap sample
+pub trait HeightSample: Copy {
+    /// Get the height in the range from 0 to 1
+    fn normalized(self) -> f32;
+}
+
+impl HeightSample for u8 {
+    fn normalized(self) -> f32 {
+        self as f32 / u8::MAX as f32
+    }
+}
+
+impl HeightSample for u16 {
+    fn normalized(self) -> f32 {
+        self as f32 / u16::MAX as f32
+    }
+}
+
+/// How a heightmap is turned into a [`Terrain`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainSettings {
+    /// Distance between neighbouring samples along X and Z
+    pub cell_size: f32,
+    /// Height of the highest possible sample, the lowest one is at 0
+    pub height_scale: f32,
+    /// Width and depth of a chunk, in cells
+    pub chunk_size: u32,
+    /// Number of cells covered by one repeat of the texture
+    pub texture_cells: f32,
+}
+
+impl TerrainSettings {
+    pub const DEFAULT: Self = Self {
+        cell_size: 1.,
+        height_scale: 16.,
+        chunk_size: 16,
+        texture_cells: 4.,
+    };
+
+    pub const fn new() -> Self {
+        Self::DEFAULT
+    }
+
+    pub const fn with_cell_size(self, cell_size: f32) -> Self {
+        Self { cell_size, ..self }
+    }
+
+    pub const fn with_height_scale(self, height_scale: f32) -> Self {
+        Self {
+            height_scale,
+            ..self
+        }
+    }
+
+    pub const fn with_chunk_size(self, chunk_size: u32) -> Self {
+        Self { chunk_size, ..self }
+    }
+
+    pub const fn with_texture_cells(self, texture_cells: f32) -> Self {
+        Self {
+            texture_cells,
+            ..self
+        }
+    }
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Part of a [`Terrain`] of up to [`TerrainSettings::chunk_size`] cells squared, drawn as a single triangle strip
+pub struct TerrainChunk {
+    mesh: Mesh<TerrainVertex>,
+    bounds: Aabb,
+}
+
+impl TerrainChunk {
+    pub fn mesh(&self) -> &Mesh<TerrainVertex> {
+        &self.mesh
+    }
+
+    /// Get the bounding box of the chunk, in the model space of the terrain
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+/// Heightmap turned into chunked meshes with normals and tiled texture coordinates
+///
+/// The terrain spans from the origin along +X (heightmap columns) and +Z (heightmap rows),
+/// with front faces wound counterclockwise seen from above
+pub struct Terrain {
+    chunks: Vec<TerrainChunk>,
+    columns: u32,
+    rows: u32,
+    size: Vec3,
+}
+
+impl Terrain {
+    /// Generate the meshes from a `width` by `depth` heightmap, row by row
+    ///
+    /// # Panics
+    ///
+    /// Panics if the heightmap is smaller than 2x2 samples, if it doesn't hold exactly
+    /// `width * depth` samples, or if the chunk size isn't between 1 and 254
+    pub fn new<T: HeightSample>(
+        width: u32,
+        depth: u32,
+        heights: &[T],
+        settings: &TerrainSettings,
+    ) -> Self {
+        assert!(
+            width >= 2 && depth >= 2,
+            "terrains need at least 2x2 samples, got {width}x{depth}"
+        );
+        assert_eq!(
+            heights.len(),
+            (width * depth) as usize,
+            "the heightmap doesn't hold {width}x{depth} samples"
+        );
+        // Chunks have (size + 1)^2 vertices, which have to be addressable with 16-bit indices
+        assert!(
+            (1..=254).contains(&settings.chunk_size),
+            "the chunk size has to be between 1 and 254 cells, got {}",
+            settings.chunk_size
+        );
+        let height = |x: u32, z: u32| {
+            let (x, z) = (x.min(width - 1), z.min(depth - 1));
+            heights[(z * width + x) as usize].normalized() * settings.height_scale
+        };
+        let vertex = |x: u32, z: u32| {
+            // Central differences, clamped at the edges so neighbouring chunks share their normals
+            let dx = height(x.saturating_sub(1), z) - height(x + 1, z);
+            let dz = height(x, z.saturating_sub(1)) - height(x, z + 1);
+            let normal = Vec3::new(dx, 2. * settings.cell_size, dz).normalize();
+            TerrainVertex {
+                u: x as f32 / settings.texture_cells,
+                v: z as f32 / settings.texture_cells,
+                x: x as f32 * settings.cell_size,
+                y: height(x, z),
+                z: z as f32 * settings.cell_size,
+                ..TerrainVertex::DEFAULT
+            }
+            .with_normal(normal.x, normal.y, normal.z)
+        };
+
+        let (cells_x, cells_z) = (width - 1, depth - 1);
+        let columns = cells_x.div_ceil(settings.chunk_size);
+        let rows = cells_z.div_ceil(settings.chunk_size);
+        let mut chunks = Vec::with_capacity((columns * rows) as usize);
+        for chunk_z in 0..rows {
+            for chunk_x in 0..columns {
+                let (start_x, start_z) =
+                    (chunk_x * settings.chunk_size, chunk_z * settings.chunk_size);
+                let chunk_width = settings.chunk_size.min(cells_x - start_x);
+                let chunk_depth = settings.chunk_size.min(cells_z - start_z);
+                let vertices: Vec<TerrainVertex> = (0..=chunk_depth)
+                    .flat_map(|z| (0..=chunk_width).map(move |x| (x, z)))
+                    .map(|(x, z)| vertex(start_x + x, start_z + z))
+                    .collect();
+                let indices = strip_indices(chunk_width, chunk_depth);
+                let bounds =
+                    Aabb::from_points(vertices.iter().map(Vertex::position)).unwrap_or_default();
+                let mut mesh = Mesh::new_indexed(GuPrimitive::TriangleStrip, vertices, indices);
+                mesh.set_bounds(Some(bounds));
+                chunks.push(TerrainChunk { mesh, bounds });
+            }
+        }
+        Self {
+            chunks,
+            columns,
+            rows,
+            size: Vec3::new(
+                cells_x as f32 * settings.cell_size,
+                settings.height_scale,
+                cells_z as f32 * settings.cell_size,
+            ),
+        }
+    }
+
+    /// Get all chunks, row by row
+    pub fn chunks(&self) -> &[TerrainChunk] {
+        &self.chunks
+    }
+
+    /// Get the number of chunks along X and Z
+    pub fn chunk_count(&self) -> (u32, u32) {
+        (self.columns, self.rows)
+    }
+
+    /// Get the chunk at the position in the chunk grid, `None` if it's outside of the terrain
+    pub fn chunk(&self, x: u32, z: u32) -> Option<&TerrainChunk> {
+        if x >= self.columns || z >= self.rows {
+            return None;
+        }
+        self.chunks.get((z * self.columns + x) as usize)
+    }
+
+    /// Get the size of the terrain along each axis, with the height being the highest possible one
+    pub fn size(&self) -> Vec3 {
+        self.size
+    }
+
+    /// Get the chunks whose bounds intersect `frustum`, given in the model space of the terrain
+    pub fn visible_chunks<'a>(
+        &'a self,
+        frustum: &'a Frustum,
+    ) -> impl Iterator<Item = &'a TerrainChunk> + 'a {
+        self.chunks
+            .iter()
+            .filter(|chunk| frustum.intersects_aabb(&chunk.bounds))
+    }
+}
+
+/// Indices of a `width` by `depth` cell grid as a single triangle strip
+///
+/// Rows are joined with two degenerate triangles, which keeps the winding of every row the same
+fn strip_indices(width: u32, depth: u32) -> Vec<u16> {
+    let stride = width + 1;
+    let mut indices = Vec::with_capacity((depth * (2 * stride + 2)) as usize);
+    for z in 0..depth {
+        if z > 0 {
+            indices.extend([indices[indices.len() - 1], (z * stride) as u16]);
+        }
+        for x in 0..stride {
+            indices.extend([(z * stride + x) as u16, ((z + 1) * stride + x) as u16]);
+        }
+    }
+    indices
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw the chunks of a [`Terrain`] inside the view frustum, returning how many were drawn
+    ///
+    /// Chunks are culled against the current projection and view matrices,
+    /// so the terrain has to be drawn with an identity model matrix
+    pub fn draw_terrain(&self, terrain: &Terrain) -> usize {
+        let frustum = self.frustum();
+        let mut drawn = 0;
+        for chunk in terrain.visible_chunks(&frustum) {
+            self.draw_mesh(&chunk.mesh);
+            drawn += 1;
+        }
+        drawn
+    }
+}
@@ -0,0 +1,172 @@
+//! Word wrapping and alignment of text drawn with a [`BitmapFont`]
+
+use alloc::vec::Vec;
+
+use crate::{Frame, color::Color32, font::BitmapFont, sprite::SpriteBatch};
+
+/// Horizontal alignment of the lines of a [`TextLayout`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// How text is broken into lines and positioned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextLayout {
+    /// Width lines are wrapped at, in pixels, or `None` to only break lines at `\n`
+    pub max_width: Option<i32>,
+    /// Alignment within `max_width`, or within the widest line if there is no maximum
+    pub align: TextAlign,
+    /// Extra space between lines, in pixels, on top of the font line height
+    pub line_spacing: i32,
+}
+
+impl TextLayout {
+    pub const DEFAULT: Self = Self {
+        max_width: None,
+        align: TextAlign::Left,
+        line_spacing: 0,
+    };
+
+    pub const fn new() -> Self {
+        Self::DEFAULT
+    }
+
+    pub const fn with_max_width(self, max_width: i32) -> Self {
+        Self {
+            max_width: Some(max_width),
+            ..self
+        }
+    }
+
+    pub const fn with_align(self, align: TextAlign) -> Self {
+        Self { align, ..self }
+    }
+
+    pub const fn with_line_spacing(self, line_spacing: i32) -> Self {
+        Self {
+            line_spacing,
+            ..self
+        }
+    }
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl BitmapFont {
+    fn line_width(&self, line: &str) -> i32 {
+        self.measure(line).0
+    }
+
+    /// Split the text into the lines it's drawn as
+    ///
+    /// Lines are broken at `\n`, and at spaces to fit into [`TextLayout::max_width`].
+    /// Words that don't fit on a line of their own are broken between characters
+    pub fn wrap<'t>(&self, text: &'t str, layout: &TextLayout) -> Vec<&'t str> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let Some(max_width) = layout.max_width else {
+                lines.push(paragraph);
+                continue;
+            };
+            let (mut start, mut end) = (0, 0);
+            for word in paragraph.split(' ') {
+                let word_start = word.as_ptr() as usize - paragraph.as_ptr() as usize;
+                let word_end = word_start + word.len();
+                if end > start && self.line_width(&paragraph[start..word_end]) > max_width {
+                    lines.push(&paragraph[start..end]);
+                    start = word_start;
+                }
+                end = word_end;
+                // Break words too long for a line of their own, keeping at least one character per line
+                while self.line_width(&paragraph[start..end]) > max_width {
+                    let line = &paragraph[start..end];
+                    let split = line
+                        .char_indices()
+                        .skip(1)
+                        .map(|(i, _)| i)
+                        .take_while(|&i| self.line_width(&line[..i]) <= max_width)
+                        .last()
+                        .unwrap_or_else(|| line.chars().next().map_or(line.len(), char::len_utf8));
+                    if split == line.len() {
+                        break;
+                    }
+                    lines.push(&line[..split]);
+                    start += split;
+                }
+            }
+            lines.push(&paragraph[start..end]);
+        }
+        lines
+    }
+
+    fn line_advance(&self, layout: &TextLayout) -> i32 {
+        self.line_height() + layout.line_spacing
+    }
+
+    /// Get the width and height of the laid out text, in pixels
+    ///
+    /// The width is the one of the widest line, which is at most [`TextLayout::max_width`]
+    /// unless a single character doesn't fit
+    pub fn measure_layout(&self, text: &str, layout: &TextLayout) -> (i32, i32) {
+        if text.is_empty() {
+            return (0, 0);
+        }
+        let lines = self.wrap(text, layout);
+        let width = lines
+            .iter()
+            .map(|line| self.line_width(line))
+            .max()
+            .unwrap_or(0);
+        let height = lines.len() as i32 * self.line_advance(layout) - layout.line_spacing;
+        (width, height)
+    }
+
+    /// Queue the laid out text into a sprite batch, with the top-left corner of the text box at the position
+    pub fn draw_layout<'gfx>(
+        &'gfx self,
+        batch: &mut SpriteBatch<'_, 'gfx>,
+        x: f32,
+        y: f32,
+        text: &str,
+        layout: &TextLayout,
+        color: Color32,
+    ) {
+        let lines = self.wrap(text, layout);
+        let widths: Vec<i32> = lines.iter().map(|line| self.line_width(line)).collect();
+        let box_width = layout
+            .max_width
+            .unwrap_or_else(|| widths.iter().copied().max().unwrap_or(0));
+        for (i, (line, width)) in lines.iter().zip(widths).enumerate() {
+            let offset = match layout.align {
+                TextAlign::Left => 0,
+                TextAlign::Center => (box_width - width) / 2,
+                TextAlign::Right => box_width - width,
+            };
+            let line_y = y + (i as i32 * self.line_advance(layout)) as f32;
+            self.draw(batch, x + offset as f32, line_y, line, color);
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Draw wrapped and aligned text with a bitmap font, see [`BitmapFont::draw_layout`]
+    pub fn draw_text_layout(
+        &self,
+        font: &'gfx BitmapFont,
+        x: f32,
+        y: f32,
+        text: &str,
+        layout: &TextLayout,
+        color: Color32,
+    ) {
+        font.draw_layout(&mut self.sprite_batch(), x, y, text, layout, color);
+    }
+}
@@ -0,0 +1,350 @@
+use alloc::{vec, vec::Vec};
+use core::ffi::c_void;
+use psp::{
+    Align16, BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH,
+    sys::{self, ClutPixelFormat, DisplayPixelFormat, MipmapLevel, TexturePixelFormat},
+};
+
+use crate::{Frame, color::Color32, rect::Rect, state};
+
+/// Maximum width and height of a texture
+pub const MAX_TEXTURE_SIZE: u32 = 512;
+
+/// Pixel format of a [`Texture`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    Psm5650,
+    Psm5551,
+    Psm4444,
+    Psm8888,
+    /// 8-bit indices into the palette set with [`Texture::set_palette`]
+    T8,
+}
+
+impl TextureFormat {
+    /// Get the size of a single pixel in bytes
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Psm8888 => 4,
+            Self::T8 => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl From<TextureFormat> for TexturePixelFormat {
+    fn from(format: TextureFormat) -> Self {
+        match format {
+            TextureFormat::Psm5650 => Self::Psm5650,
+            TextureFormat::Psm5551 => Self::Psm5551,
+            TextureFormat::Psm4444 => Self::Psm4444,
+            TextureFormat::Psm8888 => Self::Psm8888,
+            TextureFormat::T8 => Self::PsmT8,
+        }
+    }
+}
+
+/// How textures are sampled when drawn scaled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Use the closest texel, the filter set up by [`PspGfx::init`](crate::PspGfx::init)
+    #[default]
+    Nearest,
+    /// Interpolate between the four closest texels
+    Linear,
+}
+
+impl From<TextureFilter> for sys::TextureFilter {
+    fn from(filter: TextureFilter) -> Self {
+        match filter {
+            TextureFilter::Nearest => Self::Nearest,
+            TextureFilter::Linear => Self::Linear,
+        }
+    }
+}
+
+/// What texture coordinates outside of the texture sample
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureWrap {
+    /// Tile the texture, the mode set up by [`PspGfx::init`](crate::PspGfx::init)
+    #[default]
+    Repeat,
+    /// Stretch the edge texels
+    Clamp,
+}
+
+impl From<TextureWrap> for sys::GuTexWrapMode {
+    fn from(wrap: TextureWrap) -> Self {
+        match wrap {
+            TextureWrap::Repeat => Self::Repeat,
+            TextureWrap::Clamp => Self::Clamp,
+        }
+    }
+}
+
+/// Memory holding the pixels of a [`Texture`]
+enum Pixels {
+    Ram(Vec<Align16<[u8; 16]>>),
+    /// Absolute address of pixels owned by something else, e.g. a [`RenderTarget`](crate::render_target::RenderTarget)
+    Vram(*mut u8),
+}
+
+/// Image stored in RAM or VRAM that can be bound with [`Frame::bind_texture`]
+pub struct Texture {
+    pixels: Pixels,
+    palette: Vec<Align16<[u32; 4]>>,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+}
+
+impl Texture {
+    /// Create a texture from raw pixel data
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size isn't a power of two up to [`MAX_TEXTURE_SIZE`],
+    /// or if the length of `pixels` doesn't match the size and format
+    pub fn new(width: u32, height: u32, format: TextureFormat, pixels: &[u8]) -> Self {
+        for size in [width, height] {
+            assert!(
+                size.is_power_of_two() && size <= MAX_TEXTURE_SIZE,
+                "texture sizes have to be powers of two up to {MAX_TEXTURE_SIZE}, got {size}"
+            );
+        }
+        let len = (width * height) as usize * format.bytes_per_pixel();
+        assert_eq!(
+            pixels.len(),
+            len,
+            "pixel data doesn't match the texture size"
+        );
+        let mut data = vec![Align16([0; 16]); len.div_ceil(16)];
+        for (chunk, pixels) in data.iter_mut().zip(pixels.chunks(16)) {
+            chunk.0[..pixels.len()].copy_from_slice(pixels);
+        }
+        unsafe {
+            sys::sceKernelDcacheWritebackRange(
+                data.as_ptr() as *const c_void,
+                core::mem::size_of_val(data.as_slice()) as u32,
+            );
+        }
+        Self {
+            pixels: Pixels::Ram(data),
+            palette: Vec::new(),
+            format,
+            width,
+            height,
+        }
+    }
+
+    /// Create a texture reading pixels that are already in VRAM
+    ///
+    /// The rows are `width` pixels apart, `address` is absolute
+    /// (see [`sceGeEdramGetAddr`](sys::sceGeEdramGetAddr)) and has to stay valid while the texture is used
+    pub(crate) unsafe fn from_vram(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        address: *mut u8,
+    ) -> Self {
+        Self {
+            pixels: Pixels::Vram(address),
+            palette: Vec::new(),
+            format,
+            width,
+            height,
+        }
+    }
+
+    /// Create a 32-bit texture from colors
+    ///
+    /// See [`Texture::new`] for the size restrictions
+    pub fn from_colors(width: u32, height: u32, colors: &[Color32]) -> Self {
+        let pixels: Vec<u8> = colors
+            .iter()
+            .flat_map(|color| color.as_abgr().to_le_bytes())
+            .collect();
+        Self::new(width, height, TextureFormat::Psm8888, &pixels)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Get the address of the first pixel
+    fn as_ptr(&self) -> *const u8 {
+        match &self.pixels {
+            Pixels::Ram(data) => data.as_ptr() as *const u8,
+            Pixels::Vram(address) => *address,
+        }
+    }
+
+    /// Replace the pixels of a region of the texture
+    ///
+    /// `pixels` holds the rows of the region without any padding, in the format of the texture.
+    /// With [pipelining](crate::PspGfx::set_pipelined), the GE may still be reading the texture for the previous frame,
+    /// see [`PspGfx::sync`](crate::PspGfx::sync)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the region isn't inside of the texture, if the length of `pixels` doesn't match it,
+    /// or if the texture is in VRAM
+    pub fn write(&mut self, region: Rect, pixels: &[u8]) {
+        let Pixels::Ram(data) = &mut self.pixels else {
+            panic!("only textures stored in RAM can be written to");
+        };
+        assert!(
+            region.x >= 0
+                && region.y >= 0
+                && region.w >= 0
+                && region.h >= 0
+                && (region.x + region.w) as u32 <= self.width
+                && (region.y + region.h) as u32 <= self.height,
+            "region {region:?} is outside of the {}x{} texture",
+            self.width,
+            self.height
+        );
+        let bpp = self.format.bytes_per_pixel();
+        let row_len = region.w as usize * bpp;
+        assert_eq!(
+            pixels.len(),
+            row_len * region.h as usize,
+            "pixel data doesn't match the region size"
+        );
+        if row_len == 0 {
+            return;
+        }
+        // Align16 only adds alignment, the data is a contiguous array of bytes
+        let data = unsafe {
+            core::slice::from_raw_parts_mut(
+                data.as_mut_ptr() as *mut u8,
+                core::mem::size_of_val(data.as_slice()),
+            )
+        };
+        let stride = self.width as usize * bpp;
+        for (row, src) in pixels.chunks(row_len).enumerate() {
+            let start = (region.y as usize + row) * stride + region.x as usize * bpp;
+            data[start..start + row_len].copy_from_slice(src);
+        }
+        let start = region.y as usize * stride;
+        let len = region.h as usize * stride;
+        unsafe {
+            sys::sceKernelDcacheWritebackRange(
+                data[start..start + len].as_ptr() as *const c_void,
+                len as u32,
+            );
+        }
+    }
+
+    /// Set the colors the pixels of a [`TextureFormat::T8`] texture index into
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 256 colors, or if the count isn't a multiple of 8
+    pub fn set_palette(&mut self, colors: &[Color32]) {
+        assert!(
+            colors.len() <= 256 && colors.len().is_multiple_of(8),
+            "palettes have to be made of up to 256 colors in blocks of 8, got {}",
+            colors.len()
+        );
+        self.palette = colors
+            .chunks(4)
+            .map(|chunk| Align16(core::array::from_fn(|i| chunk[i].as_abgr())))
+            .collect();
+        unsafe {
+            sys::sceKernelDcacheWritebackRange(
+                self.palette.as_ptr() as *const c_void,
+                core::mem::size_of_val(self.palette.as_slice()) as u32,
+            );
+        }
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Use the texture for the following draw calls and enable texturing
+    ///
+    /// The texture has to outlive the frame, as the GE reads it after this returns
+    pub fn bind_texture(&self, texture: &'gfx Texture) {
+        unsafe {
+            if !texture.palette.is_empty() {
+                sys::sceGuClutMode(ClutPixelFormat::Psm8888, 0, 0xff, 0);
+                sys::sceGuClutLoad(
+                    texture.palette.len() as i32 / 2,
+                    texture.palette.as_ptr() as *const c_void,
+                );
+            }
+            sys::sceGuTexMode(texture.format.into(), 0, 0, 0);
+            sys::sceGuTexImage(
+                MipmapLevel::None,
+                texture.width as i32,
+                texture.height as i32,
+                texture.width as i32,
+                texture.as_ptr() as *const c_void,
+            );
+            sys::sceGuTexFlush();
+        }
+        self.set_texturing(true);
+    }
+
+    /// Set the filter used for both minification and magnification
+    pub fn set_texture_filter(&self, filter: TextureFilter) {
+        state::upload_texture_filter(filter);
+    }
+
+    /// Get the current texture filter
+    pub fn texture_filter(&self) -> TextureFilter {
+        self.render_state().texture_filter
+    }
+
+    /// Set how texture coordinates outside of the texture are handled, along each axis
+    pub fn set_texture_wrap(&self, u: TextureWrap, v: TextureWrap) {
+        state::upload_texture_wrap((u, v));
+    }
+
+    /// Get the current texture wrap modes along each axis
+    pub fn texture_wrap(&self) -> (TextureWrap, TextureWrap) {
+        self.render_state().texture_wrap
+    }
+
+    /// Copy what has been drawn so far in this frame into a texture, e.g. for cross-fades
+    ///
+    /// The copy is done by the GE when it reaches this point of the frame, so the texture
+    /// only holds the screen once the frame is done. The screen lands in the top-left corner
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture isn't a [`TextureFormat::Psm8888`] texture at least as large as the screen
+    pub fn capture_screen(&self, texture: &'gfx mut Texture) {
+        assert!(
+            texture.format == TextureFormat::Psm8888
+                && texture.width >= SCREEN_WIDTH
+                && texture.height >= SCREEN_HEIGHT,
+            "screen captures need a 32-bit texture of at least {SCREEN_WIDTH}x{SCREEN_HEIGHT}"
+        );
+        unsafe {
+            let draw_buffer = sys::sceGeEdramGetAddr().add(self.gfx.draw_buffer() as usize);
+            sys::sceGuCopyImage(
+                DisplayPixelFormat::Psm8888,
+                0,
+                0,
+                SCREEN_WIDTH as i32,
+                SCREEN_HEIGHT as i32,
+                BUF_WIDTH as i32,
+                draw_buffer as *mut c_void,
+                0,
+                0,
+                texture.width as i32,
+                texture.as_ptr() as *mut c_void,
+            );
+            sys::sceGuTexSync();
+        }
+    }
+}
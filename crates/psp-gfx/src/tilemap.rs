@@ -0,0 +1,236 @@
+//! Grid of tiles drawn from a texture atlas, baked into display lists per chunk
+
+use alloc::vec::Vec;
+use psp::{
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+    sys::{GuPrimitive, TextureColorComponent, TextureEffect},
+};
+
+use crate::{
+    Frame,
+    display_list::DisplayList,
+    math::{Mat4, Vec3},
+    matrix::MatrixKind,
+    state::{RenderState, TextureFunction},
+    texture::{Texture, TextureFilter, TextureWrap},
+};
+
+crate::define_vertex_layout! {
+    TileVertex {
+        vertex: VERTEX_32BITF,
+        transform: TRANSFORM_3D,
+        texture: TEXTURE_32BITF,
+    }
+}
+
+/// Width and height of a tilemap chunk, in tiles
+pub const TILEMAP_CHUNK_SIZE: u32 = 16;
+
+/// Commands recorded around the vertices of a chunk, like the render state upload
+const CHUNK_LIST_OVERHEAD: usize = 1024;
+
+struct Chunk {
+    list: Option<DisplayList<'static>>,
+    dirty: bool,
+}
+
+/// Scrollable grid of tiles from a texture atlas
+///
+/// The map is split into chunks of [`TILEMAP_CHUNK_SIZE`] tiles, which are recorded into display lists
+/// the first time they're drawn. Changing a tile only records its chunk again,
+/// so static levels are drawn with one call per visible chunk
+pub struct Tilemap {
+    atlas: Texture,
+    tile_width: u32,
+    tile_height: u32,
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<u16>>,
+    chunks: Vec<Chunk>,
+}
+
+impl Tilemap {
+    /// Create an empty map of `width` by `height` tiles
+    ///
+    /// The tiles of the atlas are numbered left to right, top to bottom, starting at 0
+    pub fn new(atlas: Texture, tile_width: u32, tile_height: u32, width: u32, height: u32) -> Self {
+        let chunk_count = width.div_ceil(TILEMAP_CHUNK_SIZE) * height.div_ceil(TILEMAP_CHUNK_SIZE);
+        Self {
+            atlas,
+            tile_width,
+            tile_height,
+            width,
+            height,
+            tiles: alloc::vec![None; (width * height) as usize],
+            chunks: (0..chunk_count)
+                .map(|_| Chunk {
+                    list: None,
+                    dirty: true,
+                })
+                .collect(),
+        }
+    }
+
+    /// Get the size of the map in tiles
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Get the size of a single tile in pixels
+    pub fn tile_size(&self) -> (u32, u32) {
+        (self.tile_width, self.tile_height)
+    }
+
+    pub fn atlas(&self) -> &Texture {
+        &self.atlas
+    }
+
+    /// Get the tile at the position, `None` if it's empty or outside of the map
+    pub fn tile(&self, x: u32, y: u32) -> Option<u16> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    /// Replace the tile at the position, marking its chunk to be recorded again if it changed
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside of the map
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<u16>) {
+        assert!(
+            x < self.width && y < self.height,
+            "tile ({x}, {y}) is outside of the {}x{} map",
+            self.width,
+            self.height
+        );
+        let index = (y * self.width + x) as usize;
+        if self.tiles[index] != tile {
+            self.tiles[index] = tile;
+            let chunk = self.chunk_index(x / TILEMAP_CHUNK_SIZE, y / TILEMAP_CHUNK_SIZE);
+            self.chunks[chunk].dirty = true;
+        }
+    }
+
+    fn chunk_index(&self, chunk_x: u32, chunk_y: u32) -> usize {
+        (chunk_y * self.width.div_ceil(TILEMAP_CHUNK_SIZE) + chunk_x) as usize
+    }
+
+    /// Record the display list drawing a chunk, `None` if all of its tiles are empty
+    fn record_chunk(
+        &self,
+        frame: &Frame,
+        chunk_x: u32,
+        chunk_y: u32,
+    ) -> Option<DisplayList<'static>> {
+        let columns = (self.atlas.width() / self.tile_width).max(1);
+        let (atlas_width, atlas_height) = (self.atlas.width() as f32, self.atlas.height() as f32);
+        let mut vertices = Vec::new();
+        for y in chunk_y * TILEMAP_CHUNK_SIZE..((chunk_y + 1) * TILEMAP_CHUNK_SIZE).min(self.height)
+        {
+            for x in
+                chunk_x * TILEMAP_CHUNK_SIZE..((chunk_x + 1) * TILEMAP_CHUNK_SIZE).min(self.width)
+            {
+                let Some(tile) = self.tile(x, y) else {
+                    continue;
+                };
+                let (u, v) = (
+                    (tile as u32 % columns * self.tile_width) as f32,
+                    (tile as u32 / columns * self.tile_height) as f32,
+                );
+                let (px, py) = ((x * self.tile_width) as f32, (y * self.tile_height) as f32);
+                vertices.extend([
+                    TileVertex {
+                        u: u / atlas_width,
+                        v: v / atlas_height,
+                        x: px,
+                        y: py,
+                        ..TileVertex::DEFAULT
+                    },
+                    TileVertex {
+                        u: (u + self.tile_width as f32) / atlas_width,
+                        v: (v + self.tile_height as f32) / atlas_height,
+                        x: px + self.tile_width as f32,
+                        y: py + self.tile_height as f32,
+                        ..TileVertex::DEFAULT
+                    },
+                ]);
+            }
+        }
+        if vertices.is_empty() {
+            return None;
+        }
+        let capacity = core::mem::size_of_val(vertices.as_slice()) + CHUNK_LIST_OVERHEAD;
+        Some(frame.record_list(capacity, |list| {
+            // Calling a list switches to the state it was recorded with, so the texture setup is part of it
+            list.set_render_state(&RenderState {
+                texturing: true,
+                texture_function: TextureFunction::new(
+                    TextureEffect::Replace,
+                    TextureColorComponent::Rgba,
+                ),
+                texture_filter: TextureFilter::Nearest,
+                texture_wrap: (TextureWrap::Clamp, TextureWrap::Clamp),
+                ..list.render_state()
+            });
+            list.draw_inline(GuPrimitive::Sprites, &vertices)
+        }))
+    }
+
+    /// Draw the part of the map visible on screen, with the map pixel at `(scroll_x, scroll_y)`
+    /// in the top-left corner of the screen
+    ///
+    /// Chunks with changed tiles are recorded again first, freeing their old lists waits for
+    /// a [pipelined](crate::PspGfx::set_pipelined) previous frame that could still be drawing them.
+    /// The map stays borrowed until the end of the frame, as the GE reads the chunk lists after this returns.
+    /// Tiles are drawn with the plain atlas colors and nearest filtering, which stays the active texture setup
+    pub fn draw<'gfx>(&'gfx mut self, frame: &Frame<'gfx>, scroll_x: f32, scroll_y: f32) {
+        let _guard = frame.begin_2d();
+
+        let chunk_width = (TILEMAP_CHUNK_SIZE * self.tile_width) as f32;
+        let chunk_height = (TILEMAP_CHUNK_SIZE * self.tile_height) as f32;
+        let chunk_range = |scroll: f32, screen: u32, size: f32, count: u32| {
+            let start = libm::floorf(scroll / size).max(0.) as u32;
+            let end = libm::ceilf((scroll + screen as f32) / size).max(0.) as u32;
+            start..end.min(count)
+        };
+        let columns = chunk_range(
+            scroll_x,
+            SCREEN_WIDTH,
+            chunk_width,
+            self.width.div_ceil(TILEMAP_CHUNK_SIZE),
+        );
+        let rows = chunk_range(
+            scroll_y,
+            SCREEN_HEIGHT,
+            chunk_height,
+            self.height.div_ceil(TILEMAP_CHUNK_SIZE),
+        );
+
+        for chunk_y in rows.clone() {
+            for chunk_x in columns.clone() {
+                let index = self.chunk_index(chunk_x, chunk_y);
+                if self.chunks[index].dirty {
+                    self.chunks[index] = Chunk {
+                        list: self.record_chunk(frame, chunk_x, chunk_y),
+                        dirty: false,
+                    };
+                }
+            }
+        }
+
+        let this: &'gfx Self = self;
+        frame.bind_texture(&this.atlas);
+        frame
+            .matrix(MatrixKind::Model)
+            .load(&Mat4::from_translation(Vec3::new(-scroll_x, -scroll_y, 0.)));
+        for chunk_y in rows {
+            for chunk_x in columns.clone() {
+                if let Some(list) = &this.chunks[this.chunk_index(chunk_x, chunk_y)].list {
+                    frame.call_list(list);
+                }
+            }
+        }
+    }
+}
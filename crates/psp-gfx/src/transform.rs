@@ -0,0 +1,122 @@
+use crate::{
+    Frame,
+    math::{Mat4, Vec3},
+    matrix::MatrixKind,
+};
+
+/// Billboard orientation mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Billboard {
+    /// Always fully face the camera (particles, sprites)
+    Spherical,
+    /// Only rotate around the world Y axis to face the camera (trees, health bars)
+    Cylindrical,
+}
+
+/// Chained model transform builder
+///
+/// Operations are applied in local space in the order they are specified, same as with GUM:
+/// ```ignore
+/// let transform = Transform::new()
+///     .translate(Vec3::new(0., 1., -5.))
+///     .rotate_y(angle)
+///     .scale_uniform(2.);
+/// frame.set_transform(&transform);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+}
+
+impl Transform {
+    pub const fn new() -> Self {
+        Self {
+            matrix: Mat4::IDENTITY,
+        }
+    }
+
+    /// Transform placing an object at `position`, rotated to face the camera described by `view`
+    ///
+    /// The object is expected to face the +Z axis in model space
+    pub fn billboard(view: &Mat4, position: Vec3, mode: Billboard) -> Self {
+        let [r0, r1, r2] = [0, 1, 2].map(|i| {
+            let [x, y, z, _] = view.row(i);
+            Vec3::new(x, y, z)
+        });
+        let (right, up, back) = match mode {
+            Billboard::Spherical => (r0, r1, r2),
+            Billboard::Cylindrical => {
+                let right = Vec3::new(r0.x, 0., r0.z).normalize();
+                (right, Vec3::Y, right.cross(Vec3::Y))
+            }
+        };
+        Self {
+            matrix: Mat4::from_cols([
+                [right.x, right.y, right.z, 0.],
+                [up.x, up.y, up.z, 0.],
+                [back.x, back.y, back.z, 0.],
+                [position.x, position.y, position.z, 1.],
+            ]),
+        }
+    }
+
+    pub fn translate(self, v: Vec3) -> Self {
+        self.then(&Mat4::from_translation(v))
+    }
+
+    /// Rotate around the X axis, `angle` is in radians
+    pub fn rotate_x(self, angle: f32) -> Self {
+        self.then(&Mat4::from_rotation_x(angle))
+    }
+
+    /// Rotate around the Y axis, `angle` is in radians
+    pub fn rotate_y(self, angle: f32) -> Self {
+        self.then(&Mat4::from_rotation_y(angle))
+    }
+
+    /// Rotate around the Z axis, `angle` is in radians
+    pub fn rotate_z(self, angle: f32) -> Self {
+        self.then(&Mat4::from_rotation_z(angle))
+    }
+
+    pub fn scale(self, v: Vec3) -> Self {
+        self.then(&Mat4::from_scale(v))
+    }
+
+    pub fn scale_uniform(self, s: f32) -> Self {
+        self.scale(Vec3::splat(s))
+    }
+
+    /// Apply an arbitrary matrix
+    pub fn then(self, matrix: &Mat4) -> Self {
+        Self {
+            matrix: self.matrix * *matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        self.matrix
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(transform: Transform) -> Self {
+        transform.matrix
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Load the transform into the model matrix
+    pub fn set_transform(&self, transform: &Transform) {
+        self.matrix(MatrixKind::Model).load(&transform.matrix);
+    }
+
+    /// Load a model matrix placing an object at `position`, facing the camera
+    ///
+    /// The current view matrix is used to determine the camera orientation.
+    /// Further transformations (like scaling) can be applied with [`MatrixStack::mult`](crate::matrix::MatrixStack::mult)
+    pub fn set_billboard(&self, position: Vec3, mode: Billboard) {
+        let view = self.matrix(MatrixKind::View).get();
+        self.set_transform(&Transform::billboard(&view, position, mode));
+    }
+}
@@ -0,0 +1,159 @@
+//! Full-screen fades, flashes and cross-fades played over several frames
+
+use psp::sys::GuPrimitive;
+
+use crate::{
+    Frame,
+    color::Color32,
+    sprite::Sprite,
+    state::{DepthState, RenderState},
+    texture::Texture,
+    viewport::FULL_SCREEN,
+};
+
+crate::define_vertex_layout! {
+    FadeVertex {
+        vertex: VERTEX_16BIT,
+        transform: TRANSFORM_2D,
+        color: COLOR_8888,
+    }
+}
+
+/// Curve mapping the linear progress of a [`Transition`] to its visible progress
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slow and speeds up
+    EaseIn,
+    /// Starts fast and slows down
+    EaseOut,
+    /// Starts and ends slow
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map `t` from 0 to 1 through the curve
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2. - t),
+            Self::EaseInOut => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+/// What a [`Transition`] draws over the screen
+#[derive(Clone, Copy)]
+pub enum TransitionEffect<'a> {
+    /// Fade from the scene to a solid color
+    FadeOut(Color32),
+    /// Fade from a solid color to the scene
+    FadeIn(Color32),
+    /// Fade to a solid color halfway through and back to the scene
+    Flash(Color32),
+    /// Fade from a captured screen to the scene, see [`Frame::capture_screen`]
+    CrossFade(&'a Texture),
+}
+
+/// Full-screen effect advanced once per frame
+///
+/// Draw it after the scene every frame with [`Transition::draw`], then call [`Transition::update`]
+#[derive(Clone, Copy)]
+pub struct Transition<'a> {
+    effect: TransitionEffect<'a>,
+    easing: Easing,
+    duration: u32,
+    elapsed: u32,
+}
+
+impl<'a> Transition<'a> {
+    /// Create a transition lasting `duration` frames
+    pub const fn new(effect: TransitionEffect<'a>, duration: u32) -> Self {
+        Self {
+            effect,
+            easing: Easing::Linear,
+            duration,
+            elapsed: 0,
+        }
+    }
+
+    pub const fn with_easing(self, easing: Easing) -> Self {
+        Self { easing, ..self }
+    }
+
+    pub fn effect(&self) -> TransitionEffect<'a> {
+        self.effect
+    }
+
+    /// Advance the transition by one frame
+    pub fn update(&mut self) {
+        self.elapsed = (self.elapsed + 1).min(self.duration);
+    }
+
+    /// Start the transition over
+    pub fn restart(&mut self) {
+        self.elapsed = 0;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Get the eased progress, from 0 at the start to 1 when finished
+    pub fn progress(&self) -> f32 {
+        if self.duration == 0 {
+            return 1.;
+        }
+        self.easing
+            .apply(self.elapsed as f32 / self.duration as f32)
+    }
+
+    /// Get the opacity of the overlay drawn over the scene
+    fn opacity(&self) -> f32 {
+        match self.effect {
+            TransitionEffect::FadeOut(_) => self.progress(),
+            TransitionEffect::Flash(_) => 1. - (2. * self.progress() - 1.).abs(),
+            TransitionEffect::FadeIn(_) | TransitionEffect::CrossFade(_) => 1. - self.progress(),
+        }
+    }
+
+    /// Draw the transition over everything drawn so far
+    ///
+    /// The render state is restored afterwards
+    pub fn draw(&self, frame: &Frame<'a>) {
+        let alpha = (self.opacity() * 255.) as u32;
+        if alpha == 0 {
+            return;
+        }
+        let saved = frame.render_state();
+        let overlay = RenderState {
+            depth: DepthState::DISABLED,
+            texturing: false,
+            ..RenderState::OVERLAY
+        };
+        RenderState::diff_apply(&saved, &overlay);
+        match self.effect {
+            TransitionEffect::FadeOut(color)
+            | TransitionEffect::FadeIn(color)
+            | TransitionEffect::Flash(color) => {
+                let color = Color32::from_abgr(color.as_abgr() & 0x00ff_ffff | alpha << 24);
+                let (w, h) = (FULL_SCREEN.w as u16, FULL_SCREEN.h as u16);
+                let vertices = [
+                    FadeVertex::from_position2_color(0, 0, color),
+                    FadeVertex::from_position2_color(w, h, color),
+                ];
+                frame.draw_array(GuPrimitive::Sprites, &frame.get_memory(&vertices));
+            }
+            TransitionEffect::CrossFade(texture) => {
+                let tint = Color32::from_abgr(0x00ff_ffff | alpha << 24);
+                frame
+                    .sprite_batch()
+                    .draw(texture, &Sprite::new(0., 0., FULL_SCREEN).with_color(tint));
+            }
+        }
+        RenderState::restore(&saved);
+    }
+}
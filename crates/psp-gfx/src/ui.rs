@@ -0,0 +1,313 @@
+//! Minimal immediate-mode UI for tools and debug menus
+//!
+//! Widgets are laid out top to bottom and drawn through a [`SpriteBatch`]. Focus moves
+//! between buttons with the D-pad, and follows the pointer if there is one
+
+use psp::sys::CtrlButtons;
+
+use crate::{
+    Frame,
+    color::Color32,
+    debug_text::{self, DEBUG_FONT_SIZE},
+    font::BitmapFont,
+    rect::Rect,
+    sprite::{Sprite, SpriteBatch},
+    state::{DepthState, RenderState},
+    texture::{Texture, TextureFormat},
+};
+
+/// Size of the blank texture stretched over panels and buttons
+const BLANK_SIZE: u32 = 8;
+
+static mut BLANK: Option<Texture> = None;
+
+fn blank() -> &'static Texture {
+    unsafe {
+        BLANK.get_or_insert_with(|| {
+            let pixels = [0xff; (BLANK_SIZE * BLANK_SIZE * 2) as usize];
+            Texture::new(BLANK_SIZE, BLANK_SIZE, TextureFormat::Psm4444, &pixels)
+        })
+    }
+}
+
+/// Input driving a [`Ui`] for a single frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UiInput {
+    /// Move the focus to the previous or next widget, set only on the frame the button is pressed
+    pub up: bool,
+    pub down: bool,
+    /// Activate the focused widget, set only on the frame the button is pressed
+    pub confirm: bool,
+    /// Whether the confirm button is held down, for the pressed visuals
+    pub confirm_held: bool,
+    /// Pointer position in pixels, e.g. from a cursor moved with the analog stick
+    pub pointer: Option<(i32, i32)>,
+}
+
+impl UiInput {
+    /// Build the input from the buttons held in the previous and current frame
+    ///
+    /// The D-pad moves the focus and cross confirms
+    pub fn from_buttons(previous: CtrlButtons, current: CtrlButtons) -> Self {
+        let pressed = current & !previous;
+        Self {
+            up: pressed.contains(CtrlButtons::UP),
+            down: pressed.contains(CtrlButtons::DOWN),
+            confirm: pressed.contains(CtrlButtons::CROSS),
+            confirm_held: current.contains(CtrlButtons::CROSS),
+            pointer: None,
+        }
+    }
+}
+
+/// Colors and spacing of the widgets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UiStyle {
+    pub panel: Color32,
+    pub text: Color32,
+    pub button: Color32,
+    pub button_focused: Color32,
+    pub button_pressed: Color32,
+    /// Space around the text of buttons, in pixels
+    pub padding: i32,
+    /// Space between widgets, in pixels
+    pub spacing: i32,
+}
+
+impl UiStyle {
+    pub const DEFAULT: Self = Self {
+        panel: Color32::from_rgba(0x202020e0),
+        text: Color32::WHITE,
+        button: Color32::from_rgba(0x404040ff),
+        button_focused: Color32::from_rgba(0x4060a0ff),
+        button_pressed: Color32::from_rgba(0x203060ff),
+        padding: 4,
+        spacing: 4,
+    };
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Interaction with a button in the current frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ButtonResponse {
+    /// Identifier of the button, in the order the focusable widgets were added
+    pub id: usize,
+    pub focused: bool,
+    /// Whether the button is focused while confirm is held
+    pub pressed: bool,
+    /// Whether the button was activated this frame
+    pub clicked: bool,
+}
+
+/// Focus state kept between the frames of a [`Ui`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UiState {
+    focus: usize,
+}
+
+impl UiState {
+    pub const fn new() -> Self {
+        Self { focus: 0 }
+    }
+
+    /// Get the identifier of the focused widget, see [`ButtonResponse::id`]
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    /// Move the focus to a widget, e.g. when a menu is opened
+    pub fn set_focus(&mut self, id: usize) {
+        self.focus = id;
+    }
+}
+
+/// Font the UI text is drawn with
+#[derive(Clone, Copy)]
+pub enum UiFont<'gfx> {
+    /// The built-in 8x8 debug font
+    Debug,
+    Bitmap(&'gfx BitmapFont),
+}
+
+impl UiFont<'_> {
+    fn measure(&self, text: &str) -> (i32, i32) {
+        match self {
+            Self::Debug => {
+                let columns = text.lines().map(|line| line.chars().count()).max();
+                let rows = text.split('\n').count();
+                (
+                    columns.unwrap_or(0) as i32 * DEBUG_FONT_SIZE,
+                    rows as i32 * DEBUG_FONT_SIZE,
+                )
+            }
+            Self::Bitmap(font) => font.measure(text),
+        }
+    }
+}
+
+/// Immediate-mode UI drawn over the frame, see [`Frame::ui`]
+///
+/// The render state is restored when the UI is dropped
+pub struct Ui<'s, 'frame, 'gfx> {
+    batch: SpriteBatch<'frame, 'gfx>,
+    state: &'s mut UiState,
+    input: UiInput,
+    style: UiStyle,
+    font: UiFont<'gfx>,
+    saved: RenderState,
+    x: i32,
+    y: i32,
+    width: i32,
+    /// Number of focusable widgets added so far
+    focusable: usize,
+}
+
+impl<'s, 'frame, 'gfx> Ui<'s, 'frame, 'gfx> {
+    pub fn set_style(&mut self, style: UiStyle) {
+        self.style = style;
+    }
+
+    pub fn set_font(&mut self, font: UiFont<'gfx>) {
+        self.font = font;
+    }
+
+    pub fn style(&self) -> &UiStyle {
+        &self.style
+    }
+
+    /// Get the position the next widget is placed at
+    pub fn cursor(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    /// Leave empty space before the next widget
+    pub fn space(&mut self, height: i32) {
+        self.y += height;
+    }
+
+    /// Draw a filled rectangle, e.g. as the background of a group of widgets
+    pub fn panel(&mut self, rect: Rect) {
+        self.fill(rect, self.style.panel);
+    }
+
+    fn fill(&mut self, rect: Rect, color: Color32) {
+        let uv = Rect::new(0, 0, BLANK_SIZE as i32, BLANK_SIZE as i32);
+        let sprite = Sprite::new(rect.x as f32, rect.y as f32, uv)
+            .with_size(rect.w as f32, rect.h as f32)
+            .with_color(color);
+        self.batch.draw(blank(), &sprite);
+    }
+
+    fn text_at(&mut self, x: i32, y: i32, text: &str) {
+        let color = self.style.text;
+        match self.font {
+            UiFont::Debug => debug_text::queue_text(&mut self.batch, x, y, text, color),
+            UiFont::Bitmap(font) => font.draw(&mut self.batch, x as f32, y as f32, text, color),
+        }
+    }
+
+    /// Add a line of text
+    pub fn label(&mut self, text: &str) {
+        let (_, height) = self.font.measure(text);
+        self.text_at(self.x, self.y, text);
+        self.y += height + self.style.spacing;
+    }
+
+    /// Add a button spanning the width of the UI, returns `true` if it was activated this frame
+    pub fn button(&mut self, text: &str) -> bool {
+        self.button_response(text).clicked
+    }
+
+    /// Add a button spanning the width of the UI
+    pub fn button_response(&mut self, text: &str) -> ButtonResponse {
+        let id = self.focusable;
+        self.focusable += 1;
+        let (text_width, text_height) = self.font.measure(text);
+        let rect = Rect::new(
+            self.x,
+            self.y,
+            self.width,
+            text_height + 2 * self.style.padding,
+        );
+        if let Some((x, y)) = self.input.pointer
+            && !rect.intersect(&Rect::new(x, y, 1, 1)).is_empty()
+        {
+            self.state.focus = id;
+        }
+        let focused = self.state.focus == id;
+        let response = ButtonResponse {
+            id,
+            focused,
+            pressed: focused && self.input.confirm_held,
+            clicked: focused && self.input.confirm,
+        };
+        let color = match (response.pressed, focused) {
+            (true, _) => self.style.button_pressed,
+            (false, true) => self.style.button_focused,
+            (false, false) => self.style.button,
+        };
+        self.fill(rect, color);
+        self.text_at(
+            rect.x + (rect.w - text_width) / 2,
+            rect.y + self.style.padding,
+            text,
+        );
+        self.y += rect.h + self.style.spacing;
+        response
+    }
+}
+
+impl Drop for Ui<'_, '_, '_> {
+    fn drop(&mut self) {
+        // Navigation applies to the next frame, when the widget count is known
+        let count = self.focusable;
+        if count > 0 {
+            let focus = self.state.focus.min(count - 1);
+            self.state.focus = match (self.input.up, self.input.down) {
+                (true, false) => (focus + count - 1) % count,
+                (false, true) => (focus + 1) % count,
+                _ => focus,
+            };
+        }
+        self.batch.flush();
+        RenderState::restore(&self.saved);
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Start laying out a UI at the position (in pixels), with widgets `width` pixels wide
+    ///
+    /// The UI is drawn alpha blended over everything else, with the debug font and default style
+    pub fn ui<'s>(
+        &self,
+        state: &'s mut UiState,
+        input: UiInput,
+        x: i32,
+        y: i32,
+        width: i32,
+    ) -> Ui<'s, '_, 'gfx> {
+        let saved = self.render_state();
+        let overlay = RenderState {
+            depth: DepthState::DISABLED,
+            ..RenderState::OVERLAY
+        };
+        RenderState::diff_apply(&saved, &overlay);
+        Ui {
+            batch: self.sprite_batch(),
+            state,
+            input,
+            style: UiStyle::DEFAULT,
+            font: UiFont::Debug,
+            saved,
+            x,
+            y,
+            width,
+            focusable: 0,
+        }
+    }
+}
@@ -1,29 +1,136 @@
 use psp::sys::VertexType;
 
+use crate::{color::Color32, math::Vec3, rect::Rect};
+
 pub trait Vertex {
     fn vtype() -> VertexType;
+
+    /// Position of the vertex as seen by the GE
+    ///
+    /// Fixed point 3D positions are rescaled to model units
+    fn position(&self) -> Vec3;
+
+    /// Normal of the vertex, `None` if the layout has no normals
+    fn normal(&self) -> Option<Vec3> {
+        None
+    }
+
+    /// Overwrite the vertex color, does nothing if the layout has no color
+    fn set_color(&mut self, color: Color32) {
+        let _ = color;
+    }
+
+    /// Build a vertex from its position, normal and normalized texture coordinates,
+    /// dropping the attributes the layout doesn't have
+    ///
+    /// Fixed point components are rescaled the same way the GE reads them in 3D, colors are set to white
+    fn from_attributes(position: Vec3, normal: Vec3, uv: [f32; 2]) -> Self
+    where
+        Self: Sized;
+}
+
+crate::define_vertex_layout! {
+    /// Vertex layout used for drawing textured quads with [`GuPrimitive::Sprites`](psp::sys::GuPrimitive::Sprites)
+    pub SpriteVertex {
+        vertex: VERTEX_16BIT,
+        transform: TRANSFORM_2D,
+        texture: TEXTURE_16BIT,
+        color: COLOR_8888,
+    }
+}
+
+impl SpriteVertex {
+    /// Build the vertex pair describing a single sprite
+    ///
+    /// - `rect`: position and size of the sprite on screen
+    /// - `uv`: texture region in texels
+    pub const fn quad(rect: Rect, uv: Rect, color: Color32) -> [Self; 2] {
+        [
+            Self {
+                u: uv.x as u16,
+                v: uv.y as u16,
+                color,
+                x: rect.x as u16,
+                y: rect.y as u16,
+                ..Self::DEFAULT
+            },
+            Self {
+                u: (uv.x + uv.w) as u16,
+                v: (uv.y + uv.h) as u16,
+                color,
+                x: (rect.x + rect.w) as u16,
+                y: (rect.y + rect.h) as u16,
+                ..Self::DEFAULT
+            },
+        ]
+    }
+}
+
+/// Normal vector packed into signed 8-bit components (for `NORMAL_8BIT` layouts)
+///
+/// The GE reads 8-bit normals as signed fixed point values with 7 fractional bits
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct PackedNormal8 {
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
 }
 
-// TODO support multiple weights/vertices (GU_WEIGHTS(n), GU_VERTICES(n))
+impl PackedNormal8 {
+    /// Fixed point scale used by the GE for 8-bit normals
+    pub const SCALE: f32 = 128.0;
+
+    /// Pack a normal vector
+    ///
+    /// The input does not have to be normalized, it's rescaled to unit length automatically.
+    /// Zero-length vectors are packed as a zero normal.
+    pub fn encode(x: f32, y: f32, z: f32) -> Self {
+        let len = libm::sqrtf(x * x + y * y + z * z);
+        if len <= f32::EPSILON {
+            return Self::default();
+        }
+        let scale = Self::SCALE / len;
+        let pack = |c: f32| libm::roundf(c * scale).clamp(-127., 127.) as i8;
+        Self {
+            x: pack(x),
+            y: pack(y),
+            z: pack(z),
+        }
+    }
+
+    /// Unpack the normal back into floating point components
+    pub fn decode(&self) -> [f32; 3] {
+        [
+            self.x as f32 / Self::SCALE,
+            self.y as f32 / Self::SCALE,
+            self.z as f32 / Self::SCALE,
+        ]
+    }
+}
+
+// TODO support multiple vertices (GU_VERTICES(n))
 #[macro_export]
 macro_rules! define_vertex_layout {
     (
-        $name:ident {
+        $(#[$meta:meta])*
+        $vis:vis $name:ident {
             vertex: $vertex:ident,
             transform: $transform:ident
             $(, texture: $texture:ident)?
             $(, color: $color:ident)?
             $(, normal: $normal:ident)?
-            $(, weight: $weight:ident)?
+            $(, weight: $weight:ident $([$weight_count:literal])?)?
             $(, index: $index:ident)?
             $(,)?
         } $(;)?
     ) => {
+        $(#[$meta])*
         #[repr(C, align(4))]
         #[derive(::core::marker::Copy, ::core::clone::Clone)]
-        struct $name {
+        $vis struct $name {
             $(
-                pub weight: $crate::define_vertex_layout!(@weight $weight),
+                pub weight: $crate::define_vertex_layout!(@weight_field $weight $($weight_count)?),
             )?
             $(
                 pub u: $crate::define_vertex_layout!(@texture $texture),
@@ -58,7 +165,7 @@ macro_rules! define_vertex_layout {
                         + (3 * ::core::mem::size_of::<$crate::define_vertex_layout!(@normal $normal)>())
                     )?
                     $(
-                        + ::core::mem::size_of::<$crate::define_vertex_layout!(@weight $weight)>()
+                        + ::core::mem::size_of::<$crate::define_vertex_layout!(@weight_field $weight $($weight_count)?)>()
                     )?
                     + (3 * ::core::mem::size_of::<$crate::define_vertex_layout!(@vertex $vertex)>())
                 };
@@ -66,23 +173,23 @@ macro_rules! define_vertex_layout {
             };
             const DEFAULT: Self = Self {
                 $(
-                    weight: { stringify!($weight); 0 },
+                    weight: { stringify!($weight); $crate::define_vertex_layout!(@weight_default $($weight_count)?) },
                 )?
                 $(
-                    u: { stringify!($texture); 0 },
-                    v: 0,
+                    u: { stringify!($texture); 0 as _ },
+                    v: 0 as _,
                 )?
                 $(
                     color: $crate::define_vertex_layout!(@color_default $color),
                 )?
                 $(
-                    normal_x: { stringify!($normal); 0 },
-                    normal_y: 0,
-                    normal_z: 0,
+                    normal_x: { stringify!($normal); 0 as _ },
+                    normal_y: 0 as _,
+                    normal_z: 0 as _,
                 )?
-                x: 0,
-                y: 0,
-                z: 0,
+                x: 0 as _,
+                y: 0 as _,
+                z: 0 as _,
                 _padding: [0; Self::PADDING],
             };
 
@@ -173,6 +280,22 @@ macro_rules! define_vertex_layout {
                     }
                 }
             )?
+
+            $(
+                pub const fn with_normal(
+                    self,
+                    normal_x: $crate::define_vertex_layout!(@normal $normal),
+                    normal_y: $crate::define_vertex_layout!(@normal $normal),
+                    normal_z: $crate::define_vertex_layout!(@normal $normal),
+                ) -> Self {
+                    Self {
+                        normal_x,
+                        normal_y,
+                        normal_z,
+                        ..self
+                    }
+                }
+            )?
         }
 
         impl ::core::default::Default for $name {
@@ -186,7 +309,7 @@ macro_rules! define_vertex_layout {
                 ::psp::sys::VertexType::empty()
                 $(
                     | ::psp::sys::VertexType::$weight
-                    | ::psp::sys::VertexType::WEIGHTS1
+                    | $crate::define_vertex_layout!(@weight_count $($weight_count)?)
                 )?
                 $(
                     | ::psp::sys::VertexType::$texture
@@ -204,9 +327,98 @@ macro_rules! define_vertex_layout {
                 | ::psp::sys::VertexType::VERTICES1
                 | ::psp::sys::VertexType::$transform
             }
+
+            fn position(&self) -> $crate::math::Vec3 {
+                $crate::math::Vec3::new(
+                    $crate::define_vertex_layout!(@position $transform $vertex self.x),
+                    $crate::define_vertex_layout!(@position $transform $vertex self.y),
+                    $crate::define_vertex_layout!(@position $transform $vertex self.z),
+                )
+            }
+
+            $(
+                fn normal(&self) -> ::core::option::Option<$crate::math::Vec3> {
+                    ::core::option::Option::Some($crate::math::Vec3::new(
+                        $crate::define_vertex_layout!(@normal_value $normal self.normal_x),
+                        $crate::define_vertex_layout!(@normal_value $normal self.normal_y),
+                        $crate::define_vertex_layout!(@normal_value $normal self.normal_z),
+                    ))
+                }
+            )?
+
+            $(
+                fn set_color(&mut self, color: $crate::color::Color32) {
+                    self.color = $crate::define_vertex_layout!(@color_from $color color);
+                }
+            )?
+
+            fn from_attributes(
+                position: $crate::math::Vec3,
+                normal: $crate::math::Vec3,
+                uv: [f32; 2],
+            ) -> Self {
+                let _ = (normal, uv);
+                Self {
+                    $(
+                        u: $crate::define_vertex_layout!(@texture_from $texture uv[0]),
+                        v: $crate::define_vertex_layout!(@texture_from $texture uv[1]),
+                    )?
+                    $(
+                        color: {
+                            let white = $crate::color::Color32::WHITE;
+                            $crate::define_vertex_layout!(@color_from $color white)
+                        },
+                    )?
+                    $(
+                        normal_x: $crate::define_vertex_layout!(@normal_from $normal normal.x),
+                        normal_y: $crate::define_vertex_layout!(@normal_from $normal normal.y),
+                        normal_z: $crate::define_vertex_layout!(@normal_from $normal normal.z),
+                    )?
+                    x: $crate::define_vertex_layout!(@position_from $transform $vertex position.x),
+                    y: $crate::define_vertex_layout!(@position_from $transform $vertex position.y),
+                    z: $crate::define_vertex_layout!(@position_from $transform $vertex position.z),
+                    ..Self::DEFAULT
+                }
+            }
         }
     };
 
+    (@position TRANSFORM_3D VERTEX_8BIT $value:expr) => {
+        ($value as i8) as f32 / 128.
+    };
+    (@position TRANSFORM_3D VERTEX_16BIT $value:expr) => {
+        ($value as i16) as f32 / 32768.
+    };
+    (@position $transform:ident VERTEX_32BITF $value:expr) => {
+        $value
+    };
+    (@position TRANSFORM_2D $vertex:ident $value:expr) => {
+        $value as f32
+    };
+
+    (@position_from TRANSFORM_3D VERTEX_8BIT $value:expr) => {
+        ($value * 128.) as i8 as u8
+    };
+    (@position_from TRANSFORM_3D VERTEX_16BIT $value:expr) => {
+        ($value * 32768.) as i16 as u16
+    };
+    (@position_from $transform:ident VERTEX_32BITF $value:expr) => {
+        $value
+    };
+    (@position_from TRANSFORM_2D $vertex:ident $value:expr) => {
+        $value as _
+    };
+
+    (@texture_from TEXTURE_8BIT $value:expr) => {
+        ($value * 128.) as u8
+    };
+    (@texture_from TEXTURE_16BIT $value:expr) => {
+        ($value * 32768.) as u16
+    };
+    (@texture_from TEXTURE_32BITF $value:expr) => {
+        $value
+    };
+
     (@texture TEXTURE_8BIT) => {
         u8
     };
@@ -230,6 +442,25 @@ macro_rules! define_vertex_layout {
         $crate::color::Color32
     };
 
+    (@color_from COLOR_5650 $color:ident) => {
+        (($color.r() as u16 >> 3) | (($color.g() as u16 >> 2) << 5) | (($color.b() as u16 >> 3) << 11))
+    };
+    (@color_from COLOR_5551 $color:ident) => {
+        (($color.r() as u16 >> 3)
+            | (($color.g() as u16 >> 3) << 5)
+            | (($color.b() as u16 >> 3) << 10)
+            | (($color.a() as u16 >> 7) << 15))
+    };
+    (@color_from COLOR_4444 $color:ident) => {
+        (($color.r() as u16 >> 4)
+            | (($color.g() as u16 >> 4) << 4)
+            | (($color.b() as u16 >> 4) << 8)
+            | (($color.a() as u16 >> 4) << 12))
+    };
+    (@color_from COLOR_8888 $color:ident) => {
+        $color
+    };
+
     (@color_default COLOR_5650) => {
         0
     };
@@ -244,16 +475,37 @@ macro_rules! define_vertex_layout {
     };
 
     (@normal NORMAL_8BIT) => {
-        u8
+        i8
     };
     (@normal NORMAL_16BIT) => {
-        u16
+        i16
     };
     (@normal NORMAL_32BITF) => {
         f32
 
     };
 
+    (@normal_from NORMAL_8BIT $value:expr) => {
+        // Saturates, so a full length component ends up as 127
+        ($value * $crate::vertex::PackedNormal8::SCALE) as i8
+    };
+    (@normal_from NORMAL_16BIT $value:expr) => {
+        ($value * 32767.) as i16
+    };
+    (@normal_from NORMAL_32BITF $value:expr) => {
+        $value
+    };
+
+    (@normal_value NORMAL_8BIT $value:expr) => {
+        $value as f32 / $crate::vertex::PackedNormal8::SCALE
+    };
+    (@normal_value NORMAL_16BIT $value:expr) => {
+        $value as f32 / 32767.
+    };
+    (@normal_value NORMAL_32BITF $value:expr) => {
+        $value
+    };
+
     (@vertex VERTEX_8BIT) => {
         u8
     };
@@ -264,6 +516,33 @@ macro_rules! define_vertex_layout {
         f32
     };
 
+    (@weight_field $weight:ident) => {
+        $crate::define_vertex_layout!(@weight $weight)
+    };
+    (@weight_field $weight:ident $count:literal) => {
+        [$crate::define_vertex_layout!(@weight $weight); $count]
+    };
+
+    (@weight_default) => {
+        0 as _
+    };
+    (@weight_default $count:literal) => {
+        [0 as _; $count]
+    };
+
+    (@weight_count) => {
+        ::psp::sys::VertexType::WEIGHTS1
+    };
+    (@weight_count $count:literal) => {{
+        const {
+            assert!(
+                $count >= 1 && $count <= 8,
+                "vertices hold between 1 and 8 weights"
+            )
+        };
+        ::psp::sys::VertexType::from_bits_retain(((($count - 1) & 7) << 14) as i32)
+    }};
+
     (@weight WEIGHT_8BIT) => {
         u8
     };
@@ -0,0 +1,181 @@
+use psp::{SCREEN_HEIGHT, SCREEN_WIDTH, sys};
+
+use crate::{
+    Frame,
+    math::{Mat4, Vec3},
+    matrix::MatrixKind,
+    rect::Rect,
+};
+
+/// Center of the GE's virtual drawing space, the screen is centered around it
+pub(crate) const VIRTUAL_CENTER: i32 = 2048;
+
+/// Viewport set up by [`PspGfx::init`](crate::PspGfx::init)
+pub(crate) const FULL_SCREEN: Rect = Rect::new(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+
+/// Region of the screen (in pixels) covered by the GE's 4096x4096 virtual drawing space
+///
+/// Primitives with a vertex projected outside of it are culled entirely, there's no clipping on X and Y
+pub const GUARD_BAND: Rect = Rect::new(
+    (SCREEN_WIDTH / 2) as i32 - VIRTUAL_CENTER,
+    (SCREEN_HEIGHT / 2) as i32 - VIRTUAL_CENTER,
+    2 * VIRTUAL_CENTER,
+    2 * VIRTUAL_CENTER,
+);
+
+static mut VIEWPORT: Rect = FULL_SCREEN;
+static mut DEPTH_RANGE: DepthRange = DepthRange::FULL;
+
+/// Range of depth buffer values the normalized device depth is mapped to
+///
+/// The GE uses an inverted depth buffer by default, with `near` being the larger value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthRange {
+    pub near: u16,
+    pub far: u16,
+}
+
+impl DepthRange {
+    /// Full depth range, as set up by [`PspGfx::init`](crate::PspGfx::init)
+    pub const FULL: Self = Self::new(u16::MAX, 0);
+
+    /// Depth range leaving headroom on both sides, as used by the PSPSDK samples
+    ///
+    /// Vertices in front of the near plane or behind the far plane still land inside of the
+    /// 16-bit depth range, so their primitives are kept and the pixels outside of the range
+    /// are rejected one by one, instead of whole primitives being culled. See [`Frame::set_safe_clipping`]
+    pub const GUARDED: Self = Self::new(50000, 10000);
+
+    pub const fn new(near: u16, far: u16) -> Self {
+        Self { near, far }
+    }
+}
+
+impl Default for DepthRange {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl<'gfx> Frame<'gfx> {
+    /// Set the region of the screen (in pixels) that normalized device coordinates are mapped to
+    ///
+    /// This does not limit drawing to the region, use [`Frame::set_scissor`] for that
+    pub fn set_viewport(&self, viewport: Rect) {
+        unsafe {
+            VIEWPORT = viewport;
+        }
+        let origin_x = VIRTUAL_CENTER - (SCREEN_WIDTH / 2) as i32;
+        let origin_y = VIRTUAL_CENTER - (SCREEN_HEIGHT / 2) as i32;
+        unsafe {
+            sys::sceGuViewport(
+                origin_x + viewport.x + viewport.w / 2,
+                origin_y + viewport.y + viewport.h / 2,
+                viewport.w,
+                viewport.h,
+            );
+        }
+    }
+
+    /// Reset the viewport to cover the whole screen
+    pub fn reset_viewport(&self) {
+        self.set_viewport(FULL_SCREEN);
+    }
+
+    /// Get the current viewport, in pixels
+    pub fn viewport(&self) -> Rect {
+        unsafe { VIEWPORT }
+    }
+
+    /// Set the range of depth buffer values used for rendering
+    ///
+    /// Splitting the range allows partitioning the depth buffer between multiple passes
+    pub fn set_depth_range(&self, range: DepthRange) {
+        unsafe {
+            DEPTH_RANGE = range;
+            sys::sceGuDepthRange(range.near as i32, range.far as i32);
+        }
+    }
+
+    /// Get the current depth range
+    pub fn depth_range(&self) -> DepthRange {
+        unsafe { DEPTH_RANGE }
+    }
+
+    /// Draw the scene in multiple depth slices to work around the limited precision of the 16-bit depth buffer
+    ///
+    /// `planes` holds the view distances splitting the slices, starting with the near plane and ending with
+    /// the far plane, e.g. `[0.1, 10., 1000.]` for a near and a far slice.
+    /// Each slice gets its own perspective projection and an equal share of the current depth range,
+    /// nearer slices always ending up in front of farther ones.
+    ///
+    /// `draw` is called once per slice, nearest first, with the near and far distance of the slice,
+    /// and should draw everything overlapping it. Geometry crossing the slice boundaries is cut
+    /// by the GE as long as clipping is enabled (see [`Frame::set_clipping`]).
+    /// The projection matrix and depth range are restored afterwards
+    ///
+    /// See [`Mat4::perspective`] for `fov_y` and `aspect`
+    pub fn draw_depth_partitioned(
+        &self,
+        fov_y: f32,
+        aspect: f32,
+        planes: &[f32],
+        mut draw: impl FnMut(&Self, f32, f32),
+    ) {
+        let Some(slices) = planes.len().checked_sub(1).filter(|&n| n > 0) else {
+            return;
+        };
+        let range = self.depth_range();
+        let (near, far) = (range.near as i32, range.far as i32);
+        let projection = self.matrix(MatrixKind::Projection).push();
+        for (i, distances) in planes.windows(2).enumerate() {
+            let slice_near = near + (far - near) * i as i32 / slices as i32;
+            let slice_far = near + (far - near) * (i as i32 + 1) / slices as i32;
+            self.set_depth_range(DepthRange::new(slice_near as u16, slice_far as u16));
+            projection.load(&Mat4::perspective(
+                fov_y,
+                aspect,
+                distances[0],
+                distances[1],
+            ));
+            draw(self, distances[0], distances[1]);
+        }
+        drop(projection);
+        self.set_depth_range(range);
+    }
+
+    /// Convert a world-space point to screen space using the current projection, view and viewport
+    ///
+    /// The result holds the pixel coordinates (origin at the top left) in `x` and `y`,
+    /// and the normalized depth (0 at the near plane, 1 at the far plane) in `z`.
+    /// Returns `None` if the point is behind the camera
+    pub fn project(&self, world: Vec3) -> Option<Vec3> {
+        let ndc = self.view_projection().project_point(world)?;
+        let viewport = self.viewport();
+        Some(Vec3::new(
+            viewport.x as f32 + (ndc.x + 1.) * 0.5 * viewport.w as f32,
+            viewport.y as f32 + (1. - ndc.y) * 0.5 * viewport.h as f32,
+            (ndc.z + 1.) * 0.5,
+        ))
+    }
+
+    /// Convert a screen-space point back to world space, the inverse of [`Frame::project`]
+    ///
+    /// `depth` is the normalized depth, 0 at the near plane and 1 at the far plane.
+    /// Unprojecting the same pixel at both depths gives a picking ray.
+    /// Returns `None` if the current matrices are not invertible
+    pub fn unproject(&self, x: f32, y: f32, depth: f32) -> Option<Vec3> {
+        let viewport = self.viewport();
+        let ndc = Vec3::new(
+            (x - viewport.x as f32) / viewport.w as f32 * 2. - 1.,
+            1. - (y - viewport.y as f32) / viewport.h as f32 * 2.,
+            depth * 2. - 1.,
+        );
+        self.view_projection().inverse()?.project_point(ndc)
+    }
+
+    /// Combined projection and view matrix, as currently loaded in the GUM stacks
+    pub(crate) fn view_projection(&self) -> Mat4 {
+        self.matrix(MatrixKind::Projection).get() * self.matrix(MatrixKind::View).get()
+    }
+}
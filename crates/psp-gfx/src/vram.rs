@@ -0,0 +1,90 @@
+//! Allocator for the VRAM left after the frame and depth buffers
+
+use alloc::vec::Vec;
+
+use crate::PspGfx;
+
+/// Alignment of every allocation, as required for textures
+const ALIGNMENT: u32 = 16;
+
+/// Free and allocated VRAM, in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VramUsage {
+    /// Bytes currently allocated, e.g. by render targets
+    pub used: u32,
+    /// Bytes available after the frame and depth buffers
+    pub total: u32,
+}
+
+impl VramUsage {
+    pub const fn free(&self) -> u32 {
+        self.total - self.used
+    }
+}
+
+struct Heap {
+    start: u32,
+    end: u32,
+    /// Offset and size of the allocated blocks, sorted by offset
+    blocks: Vec<(u32, u32)>,
+}
+
+static mut HEAP: Heap = Heap {
+    start: 0,
+    end: 0,
+    blocks: Vec::new(),
+};
+
+/// Hand the VRAM from `start` to `end` (offsets from zero) over to the allocator
+pub(crate) fn init(start: u32, end: u32) {
+    unsafe {
+        HEAP = Heap {
+            start: start.next_multiple_of(ALIGNMENT),
+            end,
+            blocks: Vec::new(),
+        };
+    }
+}
+
+/// Allocate `size` bytes, returns the offset from zero or `None` if there's no large enough gap
+pub(crate) fn alloc(size: u32) -> Option<u32> {
+    let heap = unsafe { &mut HEAP };
+    let size = size.next_multiple_of(ALIGNMENT);
+    let mut offset = heap.start;
+    let mut index = 0;
+    // First fit between the existing blocks
+    for &(block, block_size) in &heap.blocks {
+        if block - offset >= size {
+            break;
+        }
+        offset = block + block_size;
+        index += 1;
+    }
+    if heap.end.checked_sub(offset)? < size {
+        return None;
+    }
+    heap.blocks.insert(index, (offset, size));
+    Some(offset)
+}
+
+/// Free a block returned by [`alloc`]
+pub(crate) fn free(offset: u32) {
+    let heap = unsafe { &mut HEAP };
+    if let Ok(index) = heap
+        .blocks
+        .binary_search_by_key(&offset, |&(block, _)| block)
+    {
+        heap.blocks.remove(index);
+    }
+}
+
+impl PspGfx {
+    /// Get how much of the VRAM left for render targets is in use
+    pub fn vram_usage(&self) -> VramUsage {
+        let heap = unsafe { &HEAP };
+        VramUsage {
+            used: heap.blocks.iter().map(|&(_, size)| size).sum(),
+            total: heap.end - heap.start,
+        }
+    }
+}